@@ -80,6 +80,24 @@ fn main() -> isize {
 
                 services::kill(pid.unwrap());
             }
+            "attach" => {
+                if line.len() < 2 {
+                    println!("Usage: attach <image>");
+                    continue;
+                }
+
+                services::attach(line[1], root_dir.as_str());
+            }
+            "detach" => {
+                if line.len() < 2 {
+                    println!("Usage: detach <n>");
+                    continue;
+                }
+
+                services::detach(line[1]);
+            }
+            "df" => services::df(),
+            "fsck" => sys_fsck(line.get(1) == Some(&"-r")),
             "rand" => {
                 let len = if line.len() < 2 {
                     16
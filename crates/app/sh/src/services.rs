@@ -1,4 +1,4 @@
-use alloc::{format, string::*, vec};
+use alloc::{format, string::*, vec, vec::Vec};
 
 use lib::*;
 
@@ -54,8 +54,7 @@ pub fn cat(path: &str, root_dir: &str) {
         String::from(path)
     } else {
         format!("{}{}", root_dir, path)
-    }
-    .to_ascii_uppercase();
+    };
 
     let fd = sys_open(path.as_str(), FileMode::ReadOnly);
 
@@ -92,20 +91,19 @@ pub fn cat(path: &str, root_dir: &str) {
 
 pub fn cd(path: &str, root_dir: &mut String) {
     if path.starts_with('/') {
-        *root_dir = String::from(path).to_ascii_uppercase();
+        *root_dir = String::from(path);
         if !root_dir.ends_with('/') {
             root_dir.push('/');
         }
     } else {
         root_dir.push_str(path);
         root_dir.push('/');
-        *root_dir = root_dir.to_ascii_uppercase();
     }
     canonicalize(root_dir)
 }
 
 pub fn exec(path: &str, root_dir: &str) {
-    let path = format!("{}{}", root_dir, path).to_ascii_uppercase();
+    let path = format!("{}{}", root_dir, path);
     let start = sys_time();
 
     let pid = sys_spawn(path.as_str());
@@ -126,7 +124,7 @@ pub fn exec(path: &str, root_dir: &str) {
 }
 
 pub fn nohup(path: &str, root_dir: &str) {
-    let path = format!("{}{}", root_dir, path).to_ascii_uppercase();
+    let path = format!("{}{}", root_dir, path);
 
     let pid = sys_spawn(path.as_str());
 
@@ -176,6 +174,92 @@ pub fn gen_random_bytes(len: usize) {
     }
 }
 
+/// Mount a disk image as a loop device
+pub fn attach(path: &str, root_dir: &str) {
+    let path = if path.starts_with('/') {
+        String::from(path)
+    } else {
+        format!("{}{}", root_dir, path)
+    };
+
+    match sys_attach(path.as_str()) {
+        Some(index) => println!("{} attached at /mnt/loop{}", path, index),
+        None => errln!("Cannot attach {}", path),
+    }
+}
+
+pub fn detach(index: &str) {
+    let index = index.trim_start_matches("/mnt/loop");
+
+    match index.parse::<usize>() {
+        Ok(index) if sys_detach(index) => println!("/mnt/loop{} detached", index),
+        Ok(index) => errln!("Cannot detach /mnt/loop{}", index),
+        Err(_) => errln!("Cannot parse loop index"),
+    }
+}
+
+/// Size and usage of every mounted filesystem, listed by /proc/mounts
+pub fn df() {
+    let fd = sys_open("/proc/mounts", FileMode::ReadOnly);
+
+    if fd == 0 {
+        errln!("Cannot open /proc/mounts");
+        return;
+    }
+
+    let mut buf = vec![0; 512];
+    let mut mounts = Vec::new();
+
+    loop {
+        match sys_read(fd, &mut buf) {
+            Some(0) | None => break,
+            Some(size) => mounts.extend_from_slice(&buf[..size]),
+        }
+    }
+
+    sys_close(fd);
+
+    println!(
+        "{:<12} {:>8} {:>8} {:>8} {:>5}",
+        "Mounted on", "Size", "Used", "Avail", "Use%"
+    );
+
+    for point in String::from_utf8_lossy(&mounts).lines() {
+        let Some(stat) = sys_statfs(point) else {
+            println!("{:<12} {:>8} {:>8} {:>8} {:>5}", point, "-", "-", "-", "-");
+            continue;
+        };
+
+        let size = stat.total_blocks * stat.block_size;
+        let avail = stat.free_blocks * stat.block_size;
+        let used = size.saturating_sub(avail);
+        let percent = (used * 100).checked_div(size).unwrap_or(0);
+
+        println!(
+            "{:<12} {:>8} {:>8} {:>8} {:>4}%{}",
+            point,
+            human_size(size),
+            human_size(used),
+            human_size(avail),
+            percent,
+            if stat.read_only { " (ro)" } else { "" }
+        );
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+
+    let mut size = bytes as f32;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1}{}", size, UNITS[unit])
+}
+
 pub fn canonicalize(path: &mut String) {
     // If the path is not absolute, return an error
     if !path.starts_with('/') {
@@ -36,7 +36,7 @@ const VERSION_STR: &str = concat!("YatSenOS shell v", env!("CARGO_PKG_VERSION"))
 
 struct Action(&'static str, Option<&'static str>, &'static str);
 
-const ACTIONS_MAP: [Action; 9] = [
+const ACTIONS_MAP: [Action; 13] = [
     Action("help", None, "show this help"),
     Action("ps", None, "show process list"),
     Action("ls", None, "list directory"),
@@ -45,6 +45,10 @@ const ACTIONS_MAP: [Action; 9] = [
     Action("exec", Some("<file>"), "execute file"),
     Action("nohup", Some("<file>"), "execute file in background"),
     Action("kill", Some("<pid>"), "kill process"),
+    Action("attach", Some("<image>"), "mount a disk image"),
+    Action("detach", Some("<n>"), "unmount /mnt/loop<n>"),
+    Action("df", None, "show filesystem usage"),
+    Action("fsck", Some("[-r]"), "check the root volume, -r to repair"),
     Action("clear", None, "clear screen"),
 ];
 
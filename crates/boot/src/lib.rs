@@ -1,7 +1,7 @@
 #![no_std]
 use core::ptr::NonNull;
 
-use arrayvec::{ArrayString, ArrayVec};
+use arrayvec::ArrayVec;
 pub use uefi::{
     Status,
     boot::{MemoryAttribute, MemoryDescriptor, MemoryType},
@@ -9,7 +9,6 @@ pub use uefi::{
     proto::console::gop::{GraphicsOutput, ModeInfo},
 };
 use x86_64::structures::paging::page::PageRangeInclusive;
-use xmas_elf::ElfFile;
 
 pub mod allocator;
 pub mod config;
@@ -34,19 +33,14 @@ pub struct BootInfo {
     // Kernel pages
     pub kernel_pages: KernelPages,
 
-    // Loaded apps
-    pub loaded_apps: Option<ArrayVec<App<'static>, 16>>,
+    // The initramfs archive (cpio newc), if any
+    pub initramfs: Option<&'static [u8]>,
 
     // Log Level
     pub log_level: &'static str,
-}
 
-/// App information
-pub struct App<'a> {
-    /// The name of app
-    pub name: ArrayString<16>,
-    /// The ELF file
-    pub elf: ElfFile<'a>,
+    // Kernel command line, words separated by spaces
+    pub cmdline: &'static str,
 }
 
 pub type MemoryMap = ArrayVec<MemoryDescriptor, 256>;
@@ -43,13 +43,12 @@ fn efi_main() -> Status {
 
     set_entry(elf.header.pt2.entry_point() as usize);
 
-    let apps = if config.load_apps {
-        info!("Loading apps...");
-        Some(load_apps())
-    } else {
-        info!("Skip loading apps");
-        None
-    };
+    let initramfs = config.initramfs.map(|path| {
+        info!("Loading initramfs...");
+        let mut file = open_file(path);
+        let buf = load_file(&mut file);
+        (buf.as_ptr() as u64, buf.len())
+    });
 
     // 3. Calculate max physical address
     let mmap = uefi::boot::memory_map(MemoryType::LOADER_DATA).expect("Failed to get memory map");
@@ -117,12 +116,19 @@ fn efi_main() -> Status {
     // NOTE: alloc & log can no longer be used
 
     // 7. Construct BootInfo
+    // the loaded pages are kept by the kernel, but the low half is left to user
+    // programs, so the archive is reached through the physical memory mapping
+    let initramfs = initramfs.map(|(addr, len)| unsafe {
+        core::slice::from_raw_parts((config.physical_memory_offset + addr) as *const u8, len)
+    });
+
     let bootinfo = BootInfo {
         memory_map: mmap.entries().copied().collect(),
         kernel_pages: get_page_usage(&elf),
         physical_memory_offset: config.physical_memory_offset,
-        loaded_apps: apps,
+        initramfs,
         log_level: config.log_level,
+        cmdline: config.cmdline,
         system_table,
     };
 
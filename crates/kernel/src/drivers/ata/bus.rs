@@ -132,16 +132,21 @@ impl AtaBus {
         warn!("ATA status register : {:?}", self.status());
     }
 
-    /// Writes the given command
+    /// Writes the given command for `count` sectors starting at `block`
     ///
     /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
-    fn write_command(&mut self, drive: u8, block: u32, cmd: AtaCommand) -> storage::FsResult {
+    fn write_command(
+        &mut self,
+        drive: u8,
+        block: u32,
+        count: u8,
+        cmd: AtaCommand,
+    ) -> storage::FsResult {
         let bytes = block.to_le_bytes();
 
         unsafe {
             self.drive.write(0xE0 | (drive << 4) | (bytes[3] & 0x0F));
-            // just 1 sector for current implementation
-            self.sector_count.write(1);
+            self.sector_count.write(count);
             self.lba_low.write(bytes[0]);
             self.lba_mid.write(bytes[1]);
             self.lba_high.write(bytes[2]);
@@ -174,7 +179,7 @@ impl AtaBus {
         info!("Identifying drive {}", drive);
 
         if self
-            .write_command(drive, 0, AtaCommand::IdentifyDevice)
+            .write_command(drive, 0, 1, AtaCommand::IdentifyDevice)
             .is_err()
         {
             if self.status().is_empty() {
@@ -195,17 +200,28 @@ impl AtaBus {
         })
     }
 
-    /// Reads a block from the given drive and block number into the given
-    /// buffer.
+    /// Reads consecutive blocks from the given drive and block number into
+    /// the given buffer, at most 255 blocks in one command.
     ///
     /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
     /// reference: https://wiki.osdev.org/IDE#Read.2FWrite_From_ATA_Drive
     pub(super) fn read_pio(&mut self, drive: u8, block: u32, buf: &mut [u8]) -> storage::FsResult {
-        self.write_command(drive, block, AtaCommand::ReadPio)?;
+        let count = buf.len() / SECTOR_SIZE;
+        debug_assert!((1..=u8::MAX as usize).contains(&count));
+
+        self.write_command(drive, block, count as u8, AtaCommand::ReadPio)?;
 
-        for chunk in buf.chunks_mut(2) {
-            let data = self.read_data().to_le_bytes();
-            chunk.clone_from_slice(&data);
+        for (index, sector) in buf.chunks_mut(SECTOR_SIZE).enumerate() {
+            // the drive raises DRQ again when the next sector is ready
+            if index > 0 {
+                self.poll(AtaStatus::BUSY, false);
+                self.poll(AtaStatus::DATA_REQUEST_READY, true);
+            }
+
+            for chunk in sector.chunks_mut(2) {
+                let data = self.read_data().to_le_bytes();
+                chunk.clone_from_slice(&data);
+            }
         }
 
         if self.is_error() {
@@ -223,7 +239,7 @@ impl AtaBus {
     /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
     /// reference: https://wiki.osdev.org/IDE#Read.2FWrite_From_ATA_Drive
     pub(super) fn write_pio(&mut self, drive: u8, block: u32, buf: &[u8]) -> storage::FsResult {
-        self.write_command(drive, block, AtaCommand::WritePio)?;
+        self.write_command(drive, block, 1, AtaCommand::WritePio)?;
 
         for chunk in buf.chunks(2) {
             let data = u16::from_le_bytes(chunk.try_into().unwrap());
@@ -1,5 +1,8 @@
 use alloc::boxed::Box;
 
+/// Bytes transferred for each sector
+pub(super) const SECTOR_SIZE: usize = 512;
+
 bitflags! {
     /// The possible error values found in an ATA drive's error port.
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
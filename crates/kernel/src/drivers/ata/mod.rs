@@ -8,7 +8,7 @@
 mod bus;
 mod consts;
 
-use alloc::{boxed::Box, string::String};
+use alloc::{boxed::Box, format, string::String, vec};
 
 use bus::AtaBus;
 use consts::AtaDeviceType;
@@ -52,6 +52,7 @@ impl AtaDrive {
                 blocks,
             };
             info!("Drive {} opened", drive);
+            super::devfs::register(&drive.name(), BlockFile::new(drive.clone()));
             Some(drive)
         } else {
             warn!("Drive {}@{} is not a PATA drive", bus, dsk);
@@ -59,6 +60,11 @@ impl AtaDrive {
         }
     }
 
+    /// Name of the drive under `/dev`, from `hda` to `hdd`
+    pub fn name(&self) -> String {
+        format!("hd{}", (b'a' + self.bus * 2 + self.drive) as char)
+    }
+
     fn humanized_size(&self) -> (f32, &'static str) {
         let size = self.block_size();
         let count = self.block_count().unwrap();
@@ -75,7 +81,7 @@ impl core::fmt::Display for AtaDrive {
     }
 }
 
-use storage::{Block512, BlockDevice};
+use storage::{Block512, BlockDevice, devfs::BlockFile};
 
 impl BlockDevice<Block512> for AtaDrive {
     fn block_count(&self) -> storage::FsResult<usize> {
@@ -88,6 +94,25 @@ impl BlockDevice<Block512> for AtaDrive {
             .read_pio(self.drive, offset as u32, block.as_mut())
     }
 
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> storage::FsResult {
+        let mut buf = vec![0u8; blocks.len() * consts::SECTOR_SIZE];
+        let mut bus = BUSES[self.bus as usize].lock();
+
+        // the sector count register is 8 bits wide
+        let chunks = buf.chunks_mut(u8::MAX as usize * consts::SECTOR_SIZE);
+        for (index, chunk) in chunks.enumerate() {
+            let block = offset + index * u8::MAX as usize;
+            bus.read_pio(self.drive, block as u32, chunk)?;
+        }
+        drop(bus);
+
+        for (block, sector) in blocks.iter_mut().zip(buf.chunks(consts::SECTOR_SIZE)) {
+            block.as_mut().copy_from_slice(sector);
+        }
+
+        Ok(())
+    }
+
     fn write_block(&self, offset: usize, block: &Block512) -> storage::FsResult {
         BUSES[self.bus as usize]
             .lock()
@@ -1,4 +1,4 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::num::NonZeroUsize;
 
 use lru::LruCache;
@@ -37,4 +37,24 @@ impl CacheManager<Block512> for LruCacheImpl {
         let mut inner = self.inner.lock();
         inner.put(key, Arc::new(RwLock::new(value)));
     }
+
+    fn put_if_absent(&self, key: usize, value: BlockCache<Block512>) {
+        let mut inner = self.inner.lock();
+        if !inner.contains(&key) {
+            inner.put(key, Arc::new(RwLock::new(value)));
+        }
+    }
+
+    fn contains(&self, key: &usize) -> bool {
+        self.inner.lock().contains(key)
+    }
+
+    fn dirty(&self) -> Vec<LruValue> {
+        let inner = self.inner.lock();
+        inner
+            .iter()
+            .filter(|(_, value)| value.read().is_modified())
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
 }
@@ -0,0 +1,113 @@
+//! Device nodes under `/dev`
+//!
+//! Drivers call `register` once they are initialized, the nodes that need no
+//! hardware are added by `init`.
+
+use alloc::{boxed::Box, string::String};
+
+use pc_keyboard::DecodedKey;
+use storage::{devfs::*, random::Random, *};
+
+use super::{input::try_get_key, serial::get_serial};
+
+lazy_static! {
+    pub static ref DEVFS: DevFs = DevFs::new();
+}
+
+/// Adds a device node to `/dev`
+pub fn register(name: &str, node: impl DeviceNode + 'static) {
+    match DEVFS.register(name, node) {
+        Ok(()) => debug!("Registered device /dev/{}", name),
+        Err(e) => warn!("Failed to register device /dev/{}: {:?}", name, e),
+    }
+}
+
+pub fn init() {
+    register("null", Null);
+    register("zero", Zero);
+    register("random", Random::new());
+    register("urandom", Random::new());
+    register("console", Console);
+    register("ttyS0", Serial);
+
+    info!("Initialized Device Filesystem.");
+}
+
+/// The keyboard and the screen, like the standard streams of a process
+#[derive(Clone, Copy)]
+pub struct Console;
+
+impl Read for Console {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        if buf.len() < 4 {
+            return Ok(0);
+        }
+
+        match try_get_key() {
+            Some(DecodedKey::Unicode(k)) => Ok(k.encode_utf8(buf).len()),
+            _ => Ok(0),
+        }
+    }
+}
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        print!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for Console {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl DeviceNode for Console {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        Ok(Box::new(Console))
+    }
+}
+
+/// Raw bytes to COM1
+///
+/// Received bytes are turned into keys by the serial interrupt, so they are
+/// read through the console instead.
+#[derive(Clone, Copy)]
+pub struct Serial;
+
+impl Read for Serial {
+    fn read(&mut self, _buf: &mut [u8]) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Serial {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        let mut serial = get_serial().ok_or(DeviceError::Busy)?;
+        for &byte in buf {
+            serial.send_raw(byte);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for Serial {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl DeviceNode for Serial {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        Ok(Box::new(Serial))
+    }
+}
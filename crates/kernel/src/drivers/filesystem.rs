@@ -1,48 +1,389 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
 
+use boot::BootInfo;
 use chrono::DateTime;
-use storage::{fat16::Fat16, mbr::*, *};
+use storage::{
+    cpio::CpioFs,
+    devfs::BlockFile,
+    ext2::Ext2,
+    fat16::{Fat16, bpb::Fat16Bpb},
+    fat32::{Fat32, bpb::Fat32Bpb},
+    gpt::*,
+    mbr::*,
+    tmpfs::TmpFs,
+    *,
+};
 
-use super::{ata::*, cache::*};
+use super::{ata::*, cache::*, devfs};
 
-pub static ROOTFS: spin::Once<Mount> = spin::Once::new();
+pub static ROOTFS: MountTable = MountTable::new();
 
-pub fn get_rootfs() -> &'static Mount {
-    ROOTFS.get().unwrap()
+pub fn get_rootfs() -> &'static MountTable {
+    &ROOTFS
 }
 
 static CACHE: spin::Once<LruSharedInner> = spin::Once::new();
+static CACHE_STATS: spin::Once<Arc<CacheStats>> = spin::Once::new();
 
+/// Sectors read in one command once the disk is read sequentially
+const READ_AHEAD: usize = 32;
+
+/// Bytes of kernel heap the files in /tmp may take
+const TMPFS_CAPACITY: usize = crate::memory::allocator::HEAP_SIZE / 4;
+
+/// The root volume, kept aside for `fsck` when it is FAT16
+static ROOT_FAT16: spin::Once<Fat16> = spin::Once::new();
+
+/// Used and total blocks of the disk cache, zero when there is no disk
 pub fn cache_usage() -> (usize, usize) {
-    let cache = CACHE.get().unwrap().lock();
-    (cache.len(), cache.cap().into())
+    CACHE.get().map_or((0, 0), |cache| {
+        let cache = cache.lock();
+        (cache.len(), cache.cap().into())
+    })
 }
 
-pub fn init() {
+/// Hit and read-ahead counters of the disk cache
+pub fn cache_stats() -> Option<Arc<CacheStats>> {
+    CACHE_STATS.get().cloned()
+}
+
+/// Write back everything the mounted filesystems still hold in memory
+pub fn sync() {
+    if let Err(err) = ROOTFS.sync() {
+        warn!("Failed to sync filesystems: {:?}", err);
+    }
+}
+
+/// Mount the disk as root and the initramfs at `/initrd`, or the initramfs as
+/// root when there is no disk or none of its volumes can be mounted
+pub fn init(boot_info: &'static BootInfo) {
+    let initramfs = boot_info.initramfs.map(|archive| {
+        info!("Found initramfs, size = {}", archive.len());
+        Box::new(CpioFs::new(archive).expect("Failed to parse initramfs"))
+    });
+
     info!("Opening disk device...");
 
-    let drive = AtaDrive::open(0, 0).expect("Failed to open disk device");
+    let overlay = boot_info
+        .cmdline
+        .split_whitespace()
+        .any(|arg| arg == "overlay");
+
+    let disk = AtaDrive::open(0, 0).and_then(|drive| {
+        mount_disk(drive, overlay)
+            .inspect_err(|e| warn!("Failed to mount the disk: {:?}", e))
+            .ok()
+    });
 
-    // only get the first partition
-    let part = MbrTable::parse(drive)
-        .expect("Failed to parse MBR")
-        .partitions()
-        .expect("Failed to get partitions")
-        .remove(0);
+    match (disk, initramfs) {
+        (Some(()), Some(initramfs)) => ROOTFS
+            .mount("/initrd", initramfs)
+            .expect("Failed to mount /initrd"),
+        (Some(()), None) => {}
+        (None, initramfs) => {
+            info!("No disk mounted, mounting initramfs as root...");
+
+            ROOTFS
+                .mount("/", initramfs.expect("No disk or initramfs to mount"))
+                .expect("Failed to mount root filesystem");
+        }
+    }
+
+    ROOTFS
+        .mount("/dev", Box::new(devfs::DEVFS.clone()))
+        .expect("Failed to mount /dev");
+
+    ROOTFS
+        .mount("/proc", Box::new(crate::proc::ProcFs))
+        .expect("Failed to mount /proc");
+
+    ROOTFS
+        .mount("/tmp", Box::new(TmpFs::new(TMPFS_CAPACITY)))
+        .expect("Failed to mount /tmp");
+
+    trace!("Mounted filesystems: {:#?}", ROOTFS);
+
+    info!("Initialized Filesystem.");
+}
+
+/// Mount as root the first FAT or Linux partition of the drive that a driver
+/// can read, with `overlay` the partition is never written and changes are
+/// lost at reset
+fn mount_disk(drive: AtaDrive, overlay: bool) -> FsResult {
+    let name = drive.name();
+    let mut parts = partitions(drive.clone())?;
+
+    let mut candidates: Vec<usize> = (0..parts.len())
+        .filter(|&i| is_fat(parts[i].kind()) || is_linux(parts[i].kind()))
+        .collect();
+    if candidates.is_empty() {
+        candidates.push(0);
+    }
+
+    let (index, volume) = candidates
+        .into_iter()
+        .find_map(|i| {
+            let part = parts.get(i)?;
+            probe(part, part.kind())
+                .inspect_err(|e| warn!("Skipping partition {}{}: {:?}", name, i + 1, e))
+                .ok()
+                .map(|volume| (i, volume))
+        })
+        .ok_or(FsError::NotSupported)?;
+
+    for (i, part) in parts.iter().enumerate() {
+        if i != index {
+            devfs::register(&format!("{}{}", name, i + 1), BlockFile::new(part.clone()));
+        }
+    }
+
+    // the drive covers the mounted partition, writes would go under its cache
+    if devfs::DEVFS.unregister(&name).is_ok() {
+        devfs::register(&name, BlockFile::read_only(drive));
+    }
+
+    let part = parts.swap_remove(index);
 
     let lru = LruCacheImpl::new();
 
     CACHE.call_once(|| lru.inner());
 
-    let cache_layer = ATACachedDevice::new(part, lru);
+    let part: Box<dyn BlockDevice<Block512>> = if overlay {
+        info!("Writes to the disk are kept in memory.");
+        Box::new(OverlayDevice::new(part, RamDelta::new()))
+    } else {
+        Box::new(part)
+    };
+
+    let cache_layer = Arc::new(ATACachedDevice::new(part, lru).with_read_ahead(READ_AHEAD));
+    CACHE_STATS.call_once(|| cache_layer.stats());
+
+    // reads of the mounted partition see what the cache has not written yet
+    devfs::register(
+        &format!("{}{}", name, index + 1),
+        BlockFile::read_only(cache_layer.clone()),
+    );
 
     info!("Mounting filesystem...");
 
-    ROOTFS.call_once(|| Mount::new(Box::new(Fat16::new(cache_layer)), "/".into()));
+    // a journal formatted on the partition holds the FAT in front of its log
+    let fs = if JournalDevice::detect(&cache_layer) {
+        let journal = JournalDevice::open(cache_layer)?;
+        info!("Found journal: {:?}", journal);
+        open_root(journal, volume)?
+    } else {
+        open_root(cache_layer, volume)?
+    };
 
-    trace!("Root filesystem: {:#?}", ROOTFS.get().unwrap());
+    ROOTFS.mount("/", fs)
+}
 
-    info!("Initialized Filesystem.");
+/// Read the partitions of a drive, GPT if a valid one is found, MBR otherwise
+fn partitions<T>(drive: T) -> FsResult<Vec<Partition<T, Block512>>>
+where
+    T: BlockDevice<Block512> + Clone,
+{
+    match GptTable::parse(drive.clone()) {
+        Ok(table) => {
+            info!("Found GPT partition table.");
+            table.partitions()
+        }
+        Err(FsError::InvalidPartitionTable) => {
+            info!("Found MBR partition table.");
+            MbrTable::parse(drive)?.partitions()
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FatVersion {
+    Fat16,
+    Fat32,
+}
+
+/// FAT version given by a MBR partition type byte, if it tells
+fn fat_version(kind: PartitionKind) -> Option<FatVersion> {
+    match kind {
+        PartitionKind::Mbr(0x04 | 0x06 | 0x0e) => Some(FatVersion::Fat16),
+        PartitionKind::Mbr(0x0b | 0x0c) => Some(FatVersion::Fat32),
+        _ => None,
+    }
+}
+
+fn is_fat(kind: PartitionKind) -> bool {
+    fat_version(kind).is_some()
+        || kind == PartitionKind::Gpt(Guid::BASIC_DATA)
+        || kind == PartitionKind::Gpt(Guid::EFI_SYSTEM)
+}
+
+/// Pick the FAT driver from the partition type, or from the boot sector if
+/// the type does not tell (FAT32 has an extended BPB)
+fn detect_fat(device: &impl BlockDevice<Block512>, kind: PartitionKind) -> FsResult<FatVersion> {
+    let mut block = Block512::default();
+    device.read_block(0, &mut block)?;
+
+    let fat32 = Fat32Bpb::new(block.as_ref()).is_ok();
+    let version = fat_version(kind).unwrap_or(if fat32 {
+        FatVersion::Fat32
+    } else {
+        FatVersion::Fat16
+    });
+
+    // the drivers take the boot sector for granted
+    let valid = match version {
+        FatVersion::Fat16 => Fat16Bpb::new(block.as_ref()).is_ok(),
+        FatVersion::Fat32 => fat32,
+    };
+
+    valid.then_some(version).ok_or(FsError::NotSupported)
+}
+
+fn is_linux(kind: PartitionKind) -> bool {
+    kind == PartitionKind::Mbr(0x83) || kind == PartitionKind::Gpt(Guid::LINUX_FILESYSTEM)
+}
+
+/// The filesystem on a volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Volume {
+    Ext2,
+    Fat(FatVersion),
+}
+
+/// Find the filesystem of a volume from its superblock or boot sector,
+/// fails if no driver can read it
+fn probe(device: &impl BlockDevice<Block512>, kind: PartitionKind) -> FsResult<Volume> {
+    if Ext2::detect(device) {
+        return Ok(Volume::Ext2);
+    }
+
+    detect_fat(device, kind).map(Volume::Fat)
+}
+
+/// Open the root volume, a FAT16 one is kept aside for `fsck`
+fn open_root(device: impl BlockDevice<Block512>, volume: Volume) -> FsResult<Box<dyn FileSystem>> {
+    info!("Found {:?} volume.", volume);
+
+    if volume == Volume::Fat(FatVersion::Fat16) {
+        let fs = Fat16::new(device);
+        ROOT_FAT16.call_once(|| fs.clone());
+        return Ok(Box::new(fs));
+    }
+
+    if volume == Volume::Ext2 {
+        info!("Ext2 is mounted read-only.");
+    }
+
+    open_volume(device, volume)
+}
+
+/// Mount points of the attached loop devices, `None` for a free index
+static LOOPS: spin::Mutex<Vec<Option<String>>> = spin::Mutex::new(Vec::new());
+
+/// Attach the disk image at `path` as a loop device and mount its first FAT
+/// or ext2 volume at `/mnt/loop<N>`, returns N
+///
+/// An image without a partition table is mounted as a single volume.
+pub fn attach(path: &str) -> FsResult<usize> {
+    let device = Arc::new(LoopDevice::new(ROOTFS.open_file(path)?)?);
+    info!("Attaching {:?}", device);
+
+    let part = partitions(device.clone()).ok().and_then(|parts| {
+        parts.into_iter().find_map(|part| {
+            let kind = part.kind();
+            (is_fat(kind) || is_linux(kind))
+                .then(|| probe(&part, kind).ok())
+                .flatten()
+                .map(|volume| (part, volume))
+        })
+    });
+
+    let fs = match part {
+        Some((part, volume)) => open_volume(part, volume)?,
+        None => {
+            let volume = probe(&device, PartitionKind::Mbr(0))?;
+            open_volume(device, volume)?
+        }
+    };
+
+    let mut loops = LOOPS.lock();
+    let index = loops
+        .iter()
+        .position(Option::is_none)
+        .unwrap_or(loops.len());
+    let mount_point = format!("/mnt/loop{}", index);
+
+    ROOTFS.mount(&mount_point, fs)?;
+
+    if index == loops.len() {
+        loops.push(Some(mount_point));
+    } else {
+        loops[index] = Some(mount_point);
+    }
+
+    Ok(index)
+}
+
+/// Open a volume with the driver `probe` found for it
+fn open_volume(
+    device: impl BlockDevice<Block512>,
+    volume: Volume,
+) -> FsResult<Box<dyn FileSystem>> {
+    Ok(match volume {
+        Volume::Ext2 => Box::new(Ext2::new(device)?),
+        Volume::Fat(FatVersion::Fat16) => Box::new(Fat16::new(device)),
+        Volume::Fat(FatVersion::Fat32) => Box::new(Fat32::new(device)),
+    })
+}
+
+/// Unmount loop device `index`, what it buffers is written to the image
+pub fn detach(index: usize) -> FsResult {
+    let mut loops = LOOPS.lock();
+    let mount_point = loops
+        .get_mut(index)
+        .and_then(Option::take)
+        .ok_or(FsError::NotMounted)?;
+
+    ROOTFS.unmount(&mount_point)?;
+
+    // the image is a file of another mount, its blocks may still be cached
+    ROOTFS.sync()
+}
+
+/// Check the root volume and print the problems found, fixing what can be
+/// fixed if `repair` is set
+pub fn fsck(repair: bool) {
+    let Some(fs) = ROOT_FAT16.get() else {
+        warn!("fsck: only FAT16 root volumes can be checked");
+        return;
+    };
+
+    // the check reads through the same cache the volume writes to, files
+    // still open may not have their size written yet
+    let report = match fs.check(repair) {
+        Ok(report) => report,
+        Err(FsError::Busy) => {
+            warn!("fsck: files on the volume are open, close them to repair");
+            return;
+        }
+        Err(err) => {
+            warn!("fsck: {:?}", err);
+            return;
+        }
+    };
+
+    for problem in &report.problems {
+        println!("  {}", problem);
+    }
+
+    println!(
+        "{} files, {} directories, {} clusters used, {} problems, {} repaired",
+        report.files,
+        report.dirs,
+        report.used_clusters,
+        report.problems.len(),
+        report.repaired
+    );
 }
 
 pub fn ls(root_path: &str) {
@@ -71,7 +412,11 @@ pub fn ls(root_path: &str) {
                         .format("%Y/%m/%d %H:%M:%S")
                 ),
             meta.name,
-            if meta.is_dir() { "/" } else { "" }
+            match meta.entry_type {
+                FileType::Directory => "/",
+                FileType::Symlink => "@",
+                FileType::File => "",
+            }
         );
     }
 }
@@ -2,6 +2,7 @@ mod cache;
 mod uart16550;
 
 pub mod ata;
+pub mod devfs;
 pub mod filesystem;
 pub mod input;
 pub mod serial;
@@ -50,6 +50,8 @@ pub fn dispatcher(context: &mut ProcessContext) {
         Syscall::Open => context.set_rax(sys_open(&args)),
         // fd: arg0 as u8 -> success: bool
         Syscall::Close => context.set_rax(sys_close(&args)),
+        // fd: arg0 as u8, offset: arg1 as isize, whence: arg2 as u8 -> offset: usize
+        Syscall::Seek => context.set_rax(sys_seek(&args)),
         // addr: usize -> success: bool
         Syscall::Brk => context.set_rax(sys_brk(&args)),
         // None -> pid: u16
@@ -70,6 +72,12 @@ pub fn dispatcher(context: &mut ProcessContext) {
         Syscall::Time => context.set_rax(sys_clock() as usize),
         // None
         Syscall::Stat => list_process(),
+        // op: u8 (arg0), path: &str (arg1 as *const u8, arg2 as len) / index: arg1 -> ret: usize
+        Syscall::Loop => context.set_rax(sys_loop(&args)),
+        // path: &str (arg0 as *const u8, arg1 as len), buf: arg2 as *mut StatFs -> success: bool
+        Syscall::StatFs => context.set_rax(sys_statfs(&args)),
+        // repair: arg0 as bool
+        Syscall::Fsck => fsck(&args),
         // path: &str (arg0 as *const u8, arg1 as len)
         Syscall::ListDir => list_dir(&args),
         // layout: arg0 as *const Layout -> ptr: *mut u8
@@ -1,5 +1,8 @@
 use core::alloc::Layout;
 
+use storage::{FileSystem, SeekFrom};
+use syscall_def::{FileMode, StatFs};
+
 use super::SyscallArgs;
 use crate::{memory::*, proc::*, utils::*};
 
@@ -118,7 +121,12 @@ pub fn sys_open(args: &SyscallArgs) -> usize {
         None => return 0,
     };
 
-    match open(path) {
+    let mode = match FileMode::try_from(args.arg2 as u8) {
+        Ok(mode) => mode,
+        Err(_) => return 0,
+    };
+
+    match open(path, mode) {
         Some(fd) => fd as usize,
         None => {
             warn!("sys_open: failed to open: {}", path);
@@ -127,6 +135,17 @@ pub fn sys_open(args: &SyscallArgs) -> usize {
     }
 }
 
+pub fn sys_seek(args: &SyscallArgs) -> usize {
+    let pos = match args.arg2 {
+        0 => SeekFrom::Start(args.arg1),
+        1 => SeekFrom::Current(args.arg1 as isize),
+        2 => SeekFrom::End(args.arg1 as isize),
+        _ => return usize::MAX,
+    };
+
+    seek(args.arg0 as u8, pos) as usize
+}
+
 pub fn sys_brk(args: &SyscallArgs) -> usize {
     let new_heap_end = if args.arg0 == 0 {
         None
@@ -148,6 +167,73 @@ pub fn list_process() {
     print_process_list();
 }
 
+pub fn sys_statfs(args: &SyscallArgs) -> usize {
+    if args.arg1 > 0x100 {
+        warn!("sys_statfs: path too long");
+        return 0;
+    }
+
+    let path = match as_user_str(args.arg0, args.arg1) {
+        Some(path) => path,
+        None => return 0,
+    };
+
+    let buf = match as_user_slice_mut(args.arg2, core::mem::size_of::<StatFs>()) {
+        Some(buf) => buf,
+        None => return 0,
+    };
+
+    let stat = match crate::filesystem::get_rootfs().statfs(path) {
+        Ok(stat) => stat,
+        Err(err) => {
+            debug!("sys_statfs: {}: {:?}", path, err);
+            return 0;
+        }
+    };
+
+    let stat = StatFs {
+        block_size: stat.block_size as u64,
+        total_blocks: stat.total_blocks as u64,
+        free_blocks: stat.free_blocks as u64,
+        max_name_len: stat.max_name_len as u64,
+        read_only: stat.read_only,
+    };
+
+    unsafe { (buf.as_mut_ptr() as *mut StatFs).write_unaligned(stat) };
+    1
+}
+
+/// 0: attach the image at path, returns the loop index
+/// 1: detach loop `index`, returns 0
+///
+/// `usize::MAX` on failure
+pub fn sys_loop(args: &SyscallArgs) -> usize {
+    let result = match args.arg0 {
+        0 => {
+            if args.arg2 > 0x100 {
+                warn!("sys_loop: path too long");
+                return usize::MAX;
+            }
+
+            match as_user_str(args.arg1, args.arg2) {
+                Some(path) => crate::filesystem::attach(path),
+                None => return usize::MAX,
+            }
+        }
+        1 => crate::filesystem::detach(args.arg1).map(|_| 0),
+        _ => return usize::MAX,
+    };
+
+    result.unwrap_or_else(|err| {
+        warn!("sys_loop: {:?}", err);
+        usize::MAX
+    })
+}
+
+pub fn fsck(args: &SyscallArgs) {
+    crate::filesystem::fsck(args.arg0 != 0);
+}
+
 pub fn list_dir(args: &SyscallArgs) {
     if args.arg1 > 0x100 {
         warn!("sys_list_dir: path too long");
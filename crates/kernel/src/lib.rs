@@ -43,7 +43,8 @@ pub fn init(boot_info: &'static BootInfo) {
     memory::init(boot_info); // init memory manager
     memory::user::init(); // init user heap allocator
     proc::init(boot_info); // init task manager
-    filesystem::init(); // init filesystem
+    devfs::init(); // init device nodes
+    filesystem::init(boot_info); // init filesystem
 
     x86_64::instructions::interrupts::enable();
     info!("Interrupts Enabled.");
@@ -90,5 +91,6 @@ pub fn wait(init: proc::ProcessId) {
 
 pub fn shutdown() -> ! {
     info!("YatSenOS shutting down.");
+    filesystem::sync();
     uefi::runtime::reset(ResetType::SHUTDOWN, Status::SUCCESS, None);
 }
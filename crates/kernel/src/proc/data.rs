@@ -42,6 +42,10 @@ impl ProcessData {
         self.resources.read().write(fd, buf)
     }
 
+    pub fn seek(&self, fd: u8, pos: storage::SeekFrom) -> isize {
+        self.resources.read().seek(fd, pos)
+    }
+
     pub fn env(&self, key: &str) -> Option<String> {
         self.env.read().get(key).cloned()
     }
@@ -6,7 +6,7 @@ use alloc::{
 
 use hashbrown::HashMap;
 use spin::{Mutex, RwLock};
-use storage::random::Random;
+use storage::{FileHandle, FsError, FsResult};
 
 use super::*;
 use crate::{
@@ -62,10 +62,23 @@ impl ProcessManager {
     }
 
     #[inline]
-    fn get_proc(&self, pid: &ProcessId) -> Option<Arc<Process>> {
+    pub(super) fn get_proc(&self, pid: &ProcessId) -> Option<Arc<Process>> {
         self.processes.read().get(pid).cloned()
     }
 
+    /// Processes that have not exited, ordered by pid
+    pub fn live_processes(&self) -> Vec<Arc<Process>> {
+        let mut procs: Vec<_> = self
+            .processes
+            .read()
+            .values()
+            .filter(|p| p.read().status() != ProgramStatus::Dead)
+            .cloned()
+            .collect();
+        procs.sort_by_key(|p| p.pid());
+        procs
+    }
+
     pub fn current(&self) -> Arc<Process> {
         self.get_proc(&processor::current_pid())
             .expect("No current process")
@@ -119,14 +132,13 @@ impl ProcessManager {
         pid
     }
 
-    pub fn open(&self, path: &str) -> Option<u8> {
-        let res = match path {
-            // as a special case, we can open "/dev/random" to get random numbers
-            "/dev/random" => Resource::Random(Random::new()),
-            _ => match get_rootfs().open_file(path) {
-                Ok(file) => Resource::File(file),
-                Err(_) => return None,
-            },
+    pub fn open(&self, path: &str, mode: FileMode) -> Option<u8> {
+        let res = match open_file(path, mode) {
+            Ok(file) => Resource::File(file, mode),
+            Err(e) => {
+                debug!("Failed to open {} as {:?}: {:?}", path, mode, e);
+                return None;
+            }
         };
 
         trace!("Opening {}...", path);
@@ -154,6 +166,11 @@ impl ProcessManager {
         self.current().read().write(fd, buf)
     }
 
+    #[inline]
+    pub fn seek(&self, fd: u8, pos: storage::SeekFrom) -> isize {
+        self.current().read().seek(fd, pos)
+    }
+
     pub fn spawn(
         &self,
         elf: &ElfFile,
@@ -295,6 +312,25 @@ impl ProcessManager {
     }
 }
 
+fn open_file(path: &str, mode: FileMode) -> FsResult<FileHandle> {
+    let fs = get_rootfs();
+
+    match mode {
+        FileMode::ReadOnly => fs.open_file(path),
+        FileMode::ReadWriteAppend => fs.append_file(path),
+        FileMode::ReadWriteCreate => fs.create_file(path),
+        FileMode::ReadWriteCreateOrAppend => match fs.append_file(path) {
+            Err(FsError::FileNotFound) => fs.create_file(path),
+            res => res,
+        },
+        FileMode::ReadWriteTruncate => fs.truncate_file(path),
+        FileMode::ReadWriteCreateOrTruncate => match fs.truncate_file(path) {
+            Err(FsError::FileNotFound) => fs.create_file(path),
+            res => res,
+        },
+    }
+}
+
 fn format_usage(name: &str, used: usize, total: usize) -> String {
     let (used_float, used_unit) = humanized_size(used as u64);
     let (total_float, total_unit) = humanized_size(total as u64);
@@ -5,6 +5,7 @@ mod paging;
 mod pid;
 mod process;
 mod processor;
+mod procfs;
 mod sync;
 mod vm;
 
@@ -20,8 +21,10 @@ use manager::*;
 pub use paging::PageTableContext;
 pub use pid::ProcessId;
 use process::*;
+pub use procfs::ProcFs;
 use storage::FileSystem;
 use sync::*;
+use syscall_def::FileMode;
 pub use vm::*;
 use x86_64::{VirtAddr, structures::idt::PageFaultErrorCode};
 use xmas_elf::ElfFile;
@@ -110,8 +113,12 @@ pub fn write(fd: u8, buf: &[u8]) -> isize {
     x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().write(fd, buf))
 }
 
-pub fn open(path: &str) -> Option<u8> {
-    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().open(path))
+pub fn seek(fd: u8, pos: storage::SeekFrom) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().seek(fd, pos))
+}
+
+pub fn open(path: &str, mode: FileMode) -> Option<u8> {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().open(path, mode))
 }
 
 pub fn close(fd: u8) -> bool {
@@ -143,6 +143,23 @@ impl ProcessInner {
         self.status == ProgramStatus::Ready
     }
 
+    pub fn ticks_passed(&self) -> usize {
+        self.ticks_passed
+    }
+
+    /// Memory used by the process, 0 once it has exited
+    pub fn memory_usage(&self) -> u64 {
+        self.proc_vm.as_ref().map_or(0, |vm| vm.memory_usage())
+    }
+
+    /// The mapped regions of the process, empty once it has exited
+    pub fn maps(&self) -> String {
+        self.proc_vm
+            .as_ref()
+            .map(|vm| vm.maps())
+            .unwrap_or_default()
+    }
+
     pub fn exit_code(&self) -> Option<isize> {
         self.exit_code
     }
@@ -314,7 +331,7 @@ impl core::fmt::Debug for Process {
 impl core::fmt::Display for Process {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         let inner = self.inner.read();
-        let (size, unit) = humanized_size(inner.proc_vm.as_ref().map_or(0, |vm| vm.memory_usage()));
+        let (size, unit) = humanized_size(inner.memory_usage());
         write!(
             f,
             " #{:-3} | #{:-3} | {:12} | {:7} | {:>5.1} {} | {:?}",
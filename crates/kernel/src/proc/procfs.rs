@@ -0,0 +1,276 @@
+//! Process information under `/proc`
+//!
+//! Nothing is stored, every file is generated when it is opened:
+//!
+//!   - `/proc/<pid>/status`: name, parent, state, ticks and memory usage
+//!   - `/proc/<pid>/maps`: the mapped regions of the process
+//!   - `/proc/meminfo`: frames and kernel / user heaps
+//!   - `/proc/cache`: the block cache of the root filesystem and its counters
+//!   - `/proc/mounts`: the mount points, one per line
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::fmt::Write as _;
+
+use storage::*;
+
+use super::*;
+use crate::{
+    filesystem::{cache_stats, cache_usage, get_rootfs},
+    memory::{
+        PAGE_SIZE,
+        allocator::{ALLOCATOR, HEAP_SIZE},
+        get_frame_alloc_for_sure,
+        user::{USER_ALLOCATOR, USER_HEAP_SIZE},
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct ProcFs;
+
+enum Entry {
+    Root,
+    Process,
+    Status(Arc<Process>),
+    Maps(Arc<Process>),
+    MemInfo,
+    Cache,
+    Mounts,
+}
+
+impl Entry {
+    fn lookup(path: &str) -> FsResult<Self> {
+        let parts: Vec<_> = path
+            .split(PATH_SEPARATOR)
+            .filter(|part| !part.is_empty())
+            .collect();
+
+        match parts.as_slice() {
+            [] => Ok(Entry::Root),
+            ["meminfo"] => Ok(Entry::MemInfo),
+            ["cache"] => Ok(Entry::Cache),
+            ["mounts"] => Ok(Entry::Mounts),
+            [pid] => process(pid).map(|_| Entry::Process),
+            [pid, "status"] => process(pid).map(Entry::Status),
+            [pid, "maps"] => process(pid).map(Entry::Maps),
+            [pid, _] => process(pid).and(Err(FsError::FileNotFound)),
+            _ => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self, Entry::Root | Entry::Process)
+    }
+
+    fn content(&self) -> String {
+        match self {
+            Entry::Status(proc) => status(proc),
+            Entry::Maps(proc) => proc.read().maps(),
+            Entry::MemInfo => meminfo(),
+            Entry::Cache => cache(),
+            Entry::Mounts => mounts(),
+            Entry::Root | Entry::Process => String::new(),
+        }
+    }
+
+    fn meta(&self, name: &str) -> Metadata {
+        let (entry_type, len) = if self.is_dir() {
+            (FileType::Directory, 0)
+        } else {
+            (FileType::File, self.content().len())
+        };
+
+        Metadata::new(name.into(), entry_type, len, None, None, None)
+    }
+}
+
+fn process(pid: &str) -> FsResult<Arc<Process>> {
+    let pid = pid.parse().map_err(|_| FsError::FileNotFound)?;
+
+    get_process_manager()
+        .get_proc(&ProcessId(pid))
+        .filter(|proc| proc.read().status() != ProgramStatus::Dead)
+        .ok_or(FsError::FileNotFound)
+}
+
+fn status(proc: &Process) -> String {
+    let inner = proc.read();
+    let mut out = String::new();
+
+    writeln!(out, "Name:     {}", inner.name()).unwrap();
+    writeln!(out, "Pid:      {}", proc.pid()).unwrap();
+    writeln!(
+        out,
+        "PPid:     {}",
+        inner.parent().map(|p| p.pid().0).unwrap_or(0)
+    )
+    .unwrap();
+    writeln!(out, "State:    {:?}", inner.status()).unwrap();
+    writeln!(out, "Ticks:    {}", inner.ticks_passed()).unwrap();
+    writeln!(out, "Memory:   {}", inner.memory_usage()).unwrap();
+
+    let children: Vec<_> = inner.children().iter().map(|c| c.pid().0).collect();
+    writeln!(out, "Children: {:?}", children).unwrap();
+
+    out
+}
+
+fn meminfo() -> String {
+    // take the numbers first, formatting allocates from the kernel heap
+    let kernel_used = ALLOCATOR.lock().used();
+    let user_used = USER_ALLOCATOR.lock().used();
+
+    let (frames_used, frames_recycled, frames_total) = {
+        let alloc = get_frame_alloc_for_sure();
+        (
+            alloc.frames_used(),
+            alloc.frames_recycled(),
+            alloc.frames_total(),
+        )
+    };
+
+    let page_size = PAGE_SIZE as usize;
+    let mut out = String::new();
+
+    writeln!(out, "MemTotal:       {}", frames_total * page_size).unwrap();
+    writeln!(
+        out,
+        "MemUsed:        {}",
+        (frames_used - frames_recycled) * page_size
+    )
+    .unwrap();
+    writeln!(out, "FramesTotal:    {}", frames_total).unwrap();
+    writeln!(out, "FramesUsed:     {}", frames_used).unwrap();
+    writeln!(out, "FramesRecycled: {}", frames_recycled).unwrap();
+    writeln!(out, "KernelHeap:     {}", HEAP_SIZE).unwrap();
+    writeln!(out, "KernelHeapUsed: {}", kernel_used).unwrap();
+    writeln!(out, "UserHeap:       {}", USER_HEAP_SIZE).unwrap();
+    writeln!(out, "UserHeapUsed:   {}", user_used).unwrap();
+
+    out
+}
+
+fn cache() -> String {
+    let (used, capacity) = cache_usage();
+    let mut out = format!("Blocks:     {}\nCapacity:   {}\n", used, capacity);
+
+    if let Some(stats) = cache_stats() {
+        writeln!(out, "Hits:       {}", stats.hits()).unwrap();
+        writeln!(out, "Misses:     {}", stats.misses()).unwrap();
+        writeln!(out, "Reads:      {}", stats.reads()).unwrap();
+        writeln!(out, "Prefetched: {}", stats.prefetched()).unwrap();
+    }
+
+    out
+}
+
+fn mounts() -> String {
+    let mut points = get_rootfs().mount_points();
+    points.sort();
+
+    points.iter().fold(String::new(), |mut out, point| {
+        writeln!(out, "{}", point).unwrap();
+        out
+    })
+}
+
+impl FileSystem for ProcFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let names: Vec<String> = match Entry::lookup(path)? {
+            Entry::Root => ["meminfo", "cache", "mounts"]
+                .into_iter()
+                .map(String::from)
+                .chain(
+                    get_process_manager()
+                        .live_processes()
+                        .iter()
+                        .map(|proc| proc.pid().to_string()),
+                )
+                .collect(),
+            Entry::Process => ["status", "maps"].into_iter().map(String::from).collect(),
+            _ => return Err(FsError::NotADirectory),
+        };
+
+        let base = path.trim_end_matches(PATH_SEPARATOR);
+        let entries: Vec<_> = names
+            .iter()
+            .filter_map(|name| {
+                let entry = Entry::lookup(&format!("{}/{}", base, name)).ok()?;
+                Some(entry.meta(name))
+            })
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let entry = Entry::lookup(path)?;
+
+        if entry.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        let name = path.rsplit(PATH_SEPARATOR).next().unwrap_or_default();
+        let data = entry.content().into_bytes();
+        let meta = Metadata::new(name.into(), FileType::File, data.len(), None, None, None);
+
+        Ok(FileHandle::new(
+            meta,
+            Box::new(ProcFile { data, offset: 0 }),
+        ))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let name = path
+            .trim_end_matches(PATH_SEPARATOR)
+            .rsplit(PATH_SEPARATOR)
+            .next()
+            .unwrap_or_default();
+
+        Ok(Entry::lookup(path)?.meta(name))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        Ok(Entry::lookup(path).is_ok())
+    }
+}
+
+/// A snapshot of a generated file
+struct ProcFile {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ProcFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let rest = &self.data[self.offset.min(self.data.len())..];
+        let len = rest.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.offset += len;
+
+        Ok(len)
+    }
+}
+
+impl Write for ProcFile {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for ProcFile {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.data.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        self.offset = offset.ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
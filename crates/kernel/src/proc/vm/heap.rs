@@ -3,7 +3,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use x86_64::{
     VirtAddr,
-    structures::paging::{Page, mapper::UnmapError},
+    structures::paging::{Page, mapper::UnmapError, page::PageRange},
 };
 
 use super::*;
@@ -25,6 +25,18 @@ pub struct Heap {
 }
 
 impl Heap {
+    /// The mapped pages of the heap, empty before the first `brk`
+    pub fn range(&self) -> PageRange {
+        let base = Page::containing_address(self.base);
+        let end = self.end.load(Ordering::Relaxed);
+
+        if end == self.base.as_u64() {
+            Page::range(base, base)
+        } else {
+            Page::range(base, Page::containing_address(VirtAddr::new(end)) + 1)
+        }
+    }
+
     pub fn fork(&self) -> Self {
         Self {
             base: self.base,
@@ -77,7 +89,8 @@ impl Heap {
                 elf::map_range(range, mapper, alloc, true).ok()?;
             }
             core::cmp::Ordering::Less => {
-                // heap: [base, new_end, cur_end) -> unmap [new_end, cur_end - 1]
+                // heap: [base, new_end, cur_end) -> unmap [new_end, cur_end -
+                // 1]
                 let range = Page::range_inclusive(new_end_page, cur_end_page - 1);
                 elf::unmap_range(range, mapper, alloc, true).ok()?;
             }
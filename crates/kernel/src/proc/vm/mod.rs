@@ -1,4 +1,5 @@
-use alloc::{format, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
 
 use boot::KernelPages;
 use x86_64::{
@@ -129,6 +130,36 @@ impl ProcessVm {
         self.stack.handle_page_fault(addr, mapper, alloc)
     }
 
+    /// One line per mapped region, as shown in `/proc/<pid>/maps`
+    pub(super) fn maps(&self) -> String {
+        let mut maps = String::new();
+        let mut region = |start: Page, end: Page, name: &str| {
+            if start != end {
+                writeln!(
+                    maps,
+                    "{:016x}-{:016x} {:>8} {}",
+                    start.start_address().as_u64(),
+                    end.start_address().as_u64(),
+                    end - start,
+                    name
+                )
+                .unwrap();
+            }
+        };
+
+        for range in self.code.iter() {
+            region(range.start, range.end + 1, "code");
+        }
+
+        let heap = self.heap.range();
+        region(heap.start, heap.end, "heap");
+
+        let stack = self.stack.range();
+        region(stack.start, stack.end, "stack");
+
+        maps
+    }
+
     pub(super) fn memory_usage(&self) -> u64 {
         self.stack.memory_usage() + self.heap.memory_usage() + self.code_usage
     }
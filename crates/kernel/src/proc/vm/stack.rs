@@ -62,6 +62,11 @@ impl Stack {
         }
     }
 
+    /// The pages reserved for the stack
+    pub fn range(&self) -> PageRange {
+        self.range
+    }
+
     pub const fn kstack() -> Self {
         Self {
             range: Page::range(KSTACK_INIT_PAGE, KSTACK_INIT_TOP_PAGE),
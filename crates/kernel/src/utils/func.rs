@@ -38,4 +38,14 @@ pub fn load_file_test() {
     let diff = end - start;
 
     debug!("Load test: {}", diff);
+
+    if let Some(stats) = crate::filesystem::cache_stats() {
+        debug!(
+            "Cache: {} hits, {} misses, {} reads, {} prefetched",
+            stats.hits(),
+            stats.misses(),
+            stats.reads(),
+            stats.prefetched()
+        );
+    }
 }
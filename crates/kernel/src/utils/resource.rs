@@ -3,7 +3,8 @@ use alloc::{string::String, vec::Vec};
 use hashbrown::HashMap;
 use pc_keyboard::DecodedKey;
 use spin::Mutex;
-use storage::{Device, FileHandle, random::Random};
+use storage::{FileHandle, SeekFrom};
+use syscall_def::FileMode;
 
 use crate::input::try_get_key;
 
@@ -68,19 +69,25 @@ impl ResourceSet {
             None => -1,
         }
     }
+
+    pub fn seek(&self, fd: u8, pos: SeekFrom) -> isize {
+        match self.handles.get(&fd).and_then(|h| h.lock().seek(pos)) {
+            Some(offset) => offset as isize,
+            None => -1,
+        }
+    }
 }
 
 pub enum Resource {
-    File(FileHandle),
+    /// An open file and the mode it was opened with
+    File(FileHandle, FileMode),
     Console(StdIO),
-    Random(Random),
-    Null,
 }
 
 impl Resource {
     fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
         match self {
-            Resource::File(file) => {
+            Resource::File(file, _) => {
                 let ret = file.read(buf);
                 if let Err(e) = ret {
                     error!("Failed to read file: {:?}", e);
@@ -100,14 +107,39 @@ impl Resource {
                 }),
                 _ => Some(0),
             },
-            Resource::Random(random) => Some(random.read(buf, 0, buf.len()).unwrap()),
-            Resource::Null => Some(0),
+        }
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Option<usize> {
+        match self {
+            Resource::File(file, _) => {
+                let ret = file.seek(pos);
+                if let Err(e) = ret {
+                    error!("Failed to seek file: {:?}", e);
+                    None
+                } else {
+                    Some(ret.unwrap())
+                }
+            }
+            _ => None,
         }
     }
 
     fn write(&mut self, buf: &[u8]) -> Option<usize> {
         match self {
-            Resource::File(_) => None,
+            Resource::File(_, FileMode::ReadOnly) => {
+                error!("Failed to write file: opened read-only");
+                None
+            }
+            Resource::File(file, _) => {
+                let ret = file.write(buf);
+                if let Err(e) = ret {
+                    error!("Failed to write file: {:?}", e);
+                    None
+                } else {
+                    Some(ret.unwrap())
+                }
+            }
             Resource::Console(stdio) => match *stdio {
                 StdIO::Stdin => Some(0),
                 StdIO::Stdout => {
@@ -119,8 +151,6 @@ impl Resource {
                     Some(buf.len())
                 }
             },
-            Resource::Random(_) => Some(0),
-            Resource::Null => Some(buf.len()),
         }
     }
 }
@@ -128,10 +158,8 @@ impl Resource {
 impl core::fmt::Debug for Resource {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Resource::File(h) => write!(f, "File({})", h.meta.name),
+            Resource::File(h, mode) => write!(f, "File({}, {:?})", h.meta.name, mode),
             Resource::Console(c) => write!(f, "Console({:?})", c),
-            Resource::Random(_) => write!(f, "Random"),
-            Resource::Null => write!(f, "Null"),
         }
     }
 }
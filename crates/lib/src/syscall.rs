@@ -69,6 +69,34 @@ pub fn sys_stat() {
     syscall!(Syscall::Stat);
 }
 
+#[inline(always)]
+pub fn sys_statfs(path: &str) -> Option<crate::StatFs> {
+    let mut stat = crate::StatFs::default();
+    let ret = syscall!(
+        Syscall::StatFs,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        &mut stat as *mut _
+    );
+    (ret != 0).then_some(stat)
+}
+
+#[inline(always)]
+pub fn sys_attach(path: &str) -> Option<usize> {
+    let ret = syscall!(Syscall::Loop, 0, path.as_ptr() as u64, path.len() as u64);
+    (ret != usize::MAX).then_some(ret)
+}
+
+#[inline(always)]
+pub fn sys_detach(index: usize) -> bool {
+    syscall!(Syscall::Loop, 1, index as u64) == 0
+}
+
+#[inline(always)]
+pub fn sys_fsck(repair: bool) {
+    syscall!(Syscall::Fsck, repair as u64);
+}
+
 #[inline(always)]
 pub fn sys_spawn(path: &str) -> u16 {
     syscall!(Syscall::Spawn, path.as_ptr() as u64, path.len() as u64) as u16
@@ -84,6 +112,21 @@ pub fn sys_open(path: &str, mode: crate::FileMode) -> u8 {
     ) as u8
 }
 
+#[inline(always)]
+pub fn sys_seek(fd: u8, pos: crate::SeekFrom) -> Option<usize> {
+    let (offset, whence) = match pos {
+        crate::SeekFrom::Start(offset) => (offset as u64, 0),
+        crate::SeekFrom::Current(offset) => (offset as u64, 1),
+        crate::SeekFrom::End(offset) => (offset as u64, 2),
+    };
+    let ret = syscall!(Syscall::Seek, fd as u64, offset, whence as u64) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
 #[inline(always)]
 pub fn sys_close(fd: u8) -> bool {
     syscall!(Syscall::Close, fd as u64) != 0
@@ -0,0 +1,238 @@
+//! Adapters between devices with 512 and 4096 byte blocks
+//!
+//! [`PageDevice`] groups eight sectors of a [`Block512`] device into one
+//! [`Block4096`], so a page-sized cache can sit on top of a sector driver.
+//! [`SectorDevice`] goes the other way and lets filesystems that only speak
+//! [`Block512`] use a page-sized device, writing a sector is a
+//! read-modify-write of the page that holds it.
+
+use spin::Mutex;
+
+use super::*;
+
+const SECTOR_SIZE: usize = Block512::BLOCK_SIZE;
+
+/// Sectors in a page
+const SECTORS_PER_PAGE: usize = Block4096::BLOCK_SIZE / SECTOR_SIZE;
+
+/// A [`Block512`] device seen as [`Block4096`] blocks
+///
+/// Sectors after the last whole page are not reachable.
+pub struct PageDevice<D> {
+    device: D,
+}
+
+impl<D: BlockDevice<Block512>> PageDevice<D> {
+    pub fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+}
+
+impl<D: BlockDevice<Block512>> BlockDevice<Block4096> for PageDevice<D> {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.device.block_count()? / SECTORS_PER_PAGE)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block4096) -> FsResult {
+        self.read_blocks(offset, core::slice::from_mut(block))
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block4096]) -> FsResult {
+        // the sectors of a partial page exist below but are not ours
+        if offset + blocks.len() > self.block_count()? {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let mut sectors = vec![Block512::default(); blocks.len() * SECTORS_PER_PAGE];
+        self.device
+            .read_blocks(offset * SECTORS_PER_PAGE, &mut sectors)?;
+
+        let chunks = blocks
+            .iter_mut()
+            .flat_map(|block| block.as_mut().chunks_mut(SECTOR_SIZE));
+        for (chunk, sector) in chunks.zip(&sectors) {
+            chunk.copy_from_slice(sector.as_ref());
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &Block4096) -> FsResult {
+        // checked up front, a page past the end must not be half written
+        if offset >= self.block_count()? {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let mut sector = Block512::default();
+
+        for (index, chunk) in block.as_ref().chunks(SECTOR_SIZE).enumerate() {
+            sector.as_mut().copy_from_slice(chunk);
+            self.device
+                .write_block(offset * SECTORS_PER_PAGE + index, &sector)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> FsResult {
+        self.device.flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        self.device.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.device.end_transaction()
+    }
+}
+
+/// A [`Block4096`] device seen as [`Block512`] sectors
+pub struct SectorDevice<D> {
+    device: D,
+    /// Held across read-modify-write, so writes to two sectors of the same
+    /// page cannot undo each other
+    lock: Mutex<()>,
+}
+
+impl<D: BlockDevice<Block4096>> SectorDevice<D> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+
+    /// The page holding sector `offset` and the position of the sector in it
+    fn locate(offset: usize) -> (usize, core::ops::Range<usize>) {
+        let start = offset % SECTORS_PER_PAGE * SECTOR_SIZE;
+        (offset / SECTORS_PER_PAGE, start..start + SECTOR_SIZE)
+    }
+}
+
+impl<D: BlockDevice<Block4096>> BlockDevice<Block512> for SectorDevice<D> {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.device.block_count()? * SECTORS_PER_PAGE)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+        let (page, range) = Self::locate(offset);
+        let mut buf = Block4096::default();
+
+        self.device.read_block(page, &mut buf)?;
+        block.as_mut().copy_from_slice(&buf[range]);
+
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+        let (page, range) = Self::locate(offset);
+        let mut buf = Block4096::default();
+
+        let _guard = self.lock.lock();
+        self.device.read_block(page, &mut buf)?;
+        buf.as_mut()[range].copy_from_slice(block.as_ref());
+        self.device.write_block(page, &buf)
+    }
+
+    fn flush(&self) -> FsResult {
+        self.device.flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        self.device.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.device.end_transaction()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i / SECTOR_SIZE) as u8 ^ i as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_page_device() {
+        // a tail of three sectors that does not fill a page
+        let disk = RamDisk::from_vec(pattern(19 * SECTOR_SIZE));
+        let device = PageDevice::new(disk.clone());
+
+        assert_eq!(device.block_count().unwrap(), 2);
+        assert_eq!(device.block_size(), 4096);
+
+        let mut page = Block4096::default();
+        device.read_block(1, &mut page).unwrap();
+        assert_eq!(page.as_ref(), &disk.to_vec()[4096..8192]);
+        assert_eq!(device.read_block(2, &mut page), Err(FsError::InvalidOffset));
+
+        // the partial page stays untouched
+        let tail = Block4096::new(&[0xff; 4096]);
+        assert_eq!(device.write_block(2, &tail), Err(FsError::InvalidOffset));
+        assert_eq!(disk.to_vec(), pattern(19 * SECTOR_SIZE));
+
+        device
+            .write_block(0, &Block4096::new(&[0x5a; 4096]))
+            .unwrap();
+        let data = disk.to_vec();
+        assert!(data[..4096].iter().all(|&b| b == 0x5a));
+        assert_eq!(data[4096..], pattern(19 * SECTOR_SIZE)[4096..]);
+    }
+
+    #[test]
+    fn test_sector_device() {
+        let disk = RamDisk::from_vec(pattern(8192));
+        let device = SectorDevice::new(disk.clone());
+
+        assert_eq!(device.block_count().unwrap(), 16);
+        assert_eq!(device.block_size(), 512);
+
+        let mut sector = Block512::default();
+        device.read_block(9, &mut sector).unwrap();
+        assert_eq!(sector.as_ref(), &pattern(8192)[9 * 512..10 * 512]);
+
+        // only the written sector of the page changes
+        device
+            .write_block(10, &Block512::new(&[0xa5; 512]))
+            .unwrap();
+        let mut expected = pattern(8192);
+        expected[10 * 512..11 * 512].fill(0xa5);
+        assert_eq!(disk.to_vec(), expected);
+
+        assert!(device.write_block(16, &sector).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let disk = RamDisk::from_vec(pattern(16 * 1024 * 1024));
+        let device = SectorDevice::new(PageDevice::new(disk.clone()));
+
+        let fs = crate::fat16::Fat16::format(device, &Default::default()).unwrap();
+        fs.create_file("/PAGES.TXT")
+            .unwrap()
+            .write_all(b"through both adapters")
+            .unwrap();
+
+        let mut buf = Vec::new();
+        crate::fat16::Fat16::new(disk)
+            .open_file("/PAGES.TXT")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"through both adapters");
+    }
+}
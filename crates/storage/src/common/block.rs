@@ -14,6 +14,7 @@ pub trait SizedBlock {
 }
 
 pub type Block512 = Block<512>;
+pub type Block2048 = Block<2048>;
 pub type Block4096 = Block<4096>;
 
 /// A block of data.
@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use spin::RwLock;
 
 use super::*;
@@ -39,18 +41,22 @@ impl<B: BlockTrait> BlockCache<B> {
         data.as_mut().copy_from_slice(self.inner.as_ref());
         Ok(())
     }
+
+    /// Write the block back to the device if it has been modified
+    pub fn flush(&mut self) -> FsResult {
+        if self.modified {
+            self.device.write_block(self.offset, &self.inner)?;
+            self.modified = false;
+        }
+        Ok(())
+    }
 }
 
 impl<B: BlockTrait> Drop for BlockCache<B> {
     fn drop(&mut self) {
         // This can be implemented as kernel async task
-        if self.modified {
-            match self.device.write_block(self.offset, &self.inner) {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("Failed to write block to device: {:?}", e);
-                }
-            }
+        if let Err(e) = self.flush() {
+            log::error!("Failed to write block to device: {:?}", e);
         }
     }
 }
@@ -64,6 +70,55 @@ where
 
     /// Put a block into the cache
     fn put(&self, key: usize, value: BlockCache<B>);
+
+    /// Put a block into the cache unless the key is already cached, checked
+    /// and inserted under one lock
+    fn put_if_absent(&self, key: usize, value: BlockCache<B>);
+
+    /// Returns true if the block is cached, without counting as a use
+    fn contains(&self, key: &usize) -> bool;
+
+    /// Collect the cached blocks that have not been written back yet
+    fn dirty(&self) -> Vec<Arc<RwLock<BlockCache<B>>>>;
+
+    /// Write every modified block back to the device, the blocks stay cached
+    fn flush(&self) -> FsResult {
+        for cache in self.dirty() {
+            cache.write().flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Counters of a [`CachedDevice`], shared with whoever wants to report them
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    reads: AtomicUsize,
+    prefetched: AtomicUsize,
+}
+
+impl CacheStats {
+    /// Reads served from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Reads that had to go to the device
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Read operations issued to the device, one per miss or read-ahead
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Blocks loaded by read-ahead beyond the one that missed
+    pub fn prefetched(&self) -> usize {
+        self.prefetched.load(Ordering::Relaxed)
+    }
 }
 
 pub struct CachedDevice<B, C>
@@ -73,6 +128,12 @@ where
 {
     cache: C,
     device: Arc<dyn BlockDevice<B>>,
+    /// Blocks fetched together on a sequential miss, 0 disables read-ahead
+    read_ahead: usize,
+    /// The last block that missed, read-ahead starts when the next miss
+    /// directly follows it
+    last_miss: AtomicUsize,
+    stats: Arc<CacheStats>,
 }
 
 impl<B, C> CachedDevice<B, C>
@@ -84,13 +145,81 @@ where
         Self {
             device: Arc::new(device),
             cache,
+            read_ahead: 0,
+            last_miss: AtomicUsize::new(usize::MAX),
+            stats: Arc::new(CacheStats::default()),
         }
     }
 
+    /// Fetch up to `blocks` blocks in one read once misses become sequential
+    pub fn with_read_ahead(mut self, blocks: usize) -> Self {
+        self.read_ahead = blocks;
+        self
+    }
+
+    pub fn stats(&self) -> Arc<CacheStats> {
+        self.stats.clone()
+    }
+
     fn save_cache(&self, offset: usize, block: B, modified: bool) {
         let cache = BlockCache::new(offset, self.device.clone(), block, modified);
         self.cache.put(offset, cache);
     }
+
+    /// Cache a block read from the device, a write that got there first is
+    /// newer and stays
+    fn fill_cache(&self, offset: usize, block: B) {
+        let cache = BlockCache::new(offset, self.device.clone(), block, false);
+        self.cache.put_if_absent(offset, cache);
+    }
+
+    /// Write back all modified blocks and flush the underlying device
+    pub fn sync(&self) -> FsResult {
+        self.cache.flush()?;
+        self.device.flush()
+    }
+
+    /// Number of blocks to read for a miss at `offset`
+    ///
+    /// The window stops before the first block that is already cached, a
+    /// cached block may be newer than the device.
+    fn window(&self, offset: usize) -> FsResult<usize> {
+        let sequential = offset.checked_sub(1) == Some(self.last_miss.load(Ordering::Relaxed));
+        if self.read_ahead <= 1 || !sequential {
+            return Ok(1);
+        }
+
+        let end = self.device.block_count()?.min(offset + self.read_ahead);
+        Ok((offset + 1..end)
+            .position(|next| self.cache.contains(&next))
+            .map_or(end.saturating_sub(offset).max(1), |cached| cached + 1))
+    }
+
+    fn read_miss(&self, offset: usize, block: &mut B) -> FsResult {
+        let count = self.window(offset)?;
+
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        self.stats.reads.fetch_add(1, Ordering::Relaxed);
+
+        if count == 1 {
+            self.device.read_block(offset, block)?;
+            self.fill_cache(offset, block.clone());
+        } else {
+            let mut blocks = vec![B::default(); count];
+            self.device.read_blocks(offset, &mut blocks)?;
+            block.as_mut().copy_from_slice(blocks[0].as_ref());
+
+            for (index, block) in blocks.into_iter().enumerate() {
+                self.fill_cache(offset + index, block);
+            }
+            self.stats
+                .prefetched
+                .fetch_add(count - 1, Ordering::Relaxed);
+        }
+
+        self.last_miss.store(offset + count - 1, Ordering::Relaxed);
+        Ok(())
+    }
 }
 
 impl<B, C> BlockDevice<B> for CachedDevice<B, C>
@@ -106,12 +235,12 @@ where
         match self.cache.get(&offset) {
             Some(cache) => {
                 // log::trace!("Cache hit for block {}", offset);
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
                 cache.read().load(block)?;
             }
             None => {
                 // log::trace!("Cache missed for block {}", offset);
-                self.device.read_block(offset, block)?;
-                self.save_cache(offset, block.clone(), false);
+                self.read_miss(offset, block)?;
             }
         };
 
@@ -129,4 +258,153 @@ where
         };
         Ok(())
     }
+
+    fn flush(&self) -> FsResult {
+        self.sync()
+    }
+
+    /// Cached writes reach the device below when they are written back, not
+    /// when the operation ends, so a journal belongs below the cache
+    fn begin_transaction(&self) -> FsResult {
+        self.device.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.device.end_transaction()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    use super::*;
+
+    type Entry = Arc<RwLock<BlockCache<Block512>>>;
+
+    #[derive(Default)]
+    struct MapCache(Mutex<BTreeMap<usize, Entry>>);
+
+    impl CacheManager<Block512> for MapCache {
+        fn get(&self, key: &usize) -> Option<Entry> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn put(&self, key: usize, value: BlockCache<Block512>) {
+            let entry = Arc::new(RwLock::new(value));
+            self.0.lock().unwrap().insert(key, entry);
+        }
+
+        fn put_if_absent(&self, key: usize, value: BlockCache<Block512>) {
+            let mut map = self.0.lock().unwrap();
+            map.entry(key)
+                .or_insert_with(|| Arc::new(RwLock::new(value)));
+        }
+
+        fn contains(&self, key: &usize) -> bool {
+            self.0.lock().unwrap().contains_key(key)
+        }
+
+        fn dirty(&self) -> Vec<Entry> {
+            let map = self.0.lock().unwrap();
+            map.values()
+                .filter(|entry| entry.read().is_modified())
+                .cloned()
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let disk = RamDisk::new(4 * 512);
+        let device = CachedDevice::<Block512, _>::new(disk.clone(), MapCache::default());
+
+        let block = Block512::new(&[0xaa; 512]);
+        device.write_block(1, &block).unwrap();
+        device.write_block(3, &block).unwrap();
+
+        // writes stay in the cache until flushed
+        assert!(disk.to_vec().iter().all(|&b| b == 0));
+        assert_eq!(device.cache.dirty().len(), 2);
+
+        device.sync().unwrap();
+
+        let data = disk.to_vec();
+        assert!(data[512..1024].iter().all(|&b| b == 0xaa));
+        assert!(data[1536..].iter().all(|&b| b == 0xaa));
+        assert!(data[..512].iter().all(|&b| b == 0));
+        assert!(device.cache.dirty().is_empty());
+
+        // flushed blocks are still cached
+        let mut read = Block512::default();
+        device.read_block(3, &mut read).unwrap();
+        assert_eq!(read.as_ref(), block.as_ref());
+    }
+
+    /// Counts the operations that reach the disk
+    struct Counting {
+        disk: RamDisk,
+        ops: AtomicUsize,
+    }
+
+    impl BlockDevice<Block512> for Counting {
+        fn block_count(&self) -> FsResult<usize> {
+            BlockDevice::<Block512>::block_count(&self.disk)
+        }
+
+        fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+            self.ops.fetch_add(1, Ordering::Relaxed);
+            self.disk.read_block(offset, block)
+        }
+
+        fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+            self.disk.write_block(offset, block)
+        }
+
+        fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> FsResult {
+            self.ops.fetch_add(1, Ordering::Relaxed);
+            for (index, block) in blocks.iter_mut().enumerate() {
+                self.disk.read_block(offset + index, block)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let data: Vec<u8> = (0..64 * 512).map(|i| (i / 512) as u8).collect();
+        let disk = RamDisk::from_vec(data);
+        let counting = Counting {
+            disk: disk.clone(),
+            ops: AtomicUsize::new(0),
+        };
+        let device = CachedDevice::new(counting, MapCache::default()).with_read_ahead(16);
+        let stats = device.stats();
+
+        // a cached block is newer than the disk and must not be replaced
+        device
+            .write_block(12, &Block512::new(&[0xee; 512]))
+            .unwrap();
+
+        let mut block = Block512::default();
+        for offset in 0..64 {
+            device.read_block(offset, &mut block).unwrap();
+            let expected = if offset == 12 { 0xee } else { offset as u8 };
+            assert!(block.iter().all(|&b| b == expected), "block {}", offset);
+        }
+
+        // 0 and 13 are single misses, 1..12 stops at the cached block, then
+        // 14..30, 30..46, 46..62 and 62..64
+        assert_eq!(stats.misses(), 7);
+        assert_eq!(stats.reads(), 7);
+        assert_eq!(stats.prefetched(), 63 - 7);
+        assert_eq!(stats.hits(), 64 - 7);
+
+        // random access does not trigger it
+        let device = CachedDevice::new(disk, MapCache::default()).with_read_ahead(16);
+        for offset in [5, 40, 7, 20] {
+            device.read_block(offset, &mut block).unwrap();
+        }
+        assert_eq!(device.stats().prefetched(), 0);
+    }
 }
@@ -0,0 +1,415 @@
+//! Encrypted block device, XTS-AES-256
+//!
+//! reference:
+//! - IEEE 1619-2007, XTS mode
+//! - <https://gitlab.com/cryptsetup/LUKS2-docs>, the same idea in practice
+//!
+//! The first [`HEADER_SIZE`] bytes of the device hold the header, the data
+//! after it is encrypted in 512 byte sectors with the sector number as the
+//! tweak, so the volume reads the same whatever block size it is opened
+//! with. The volume key is random, the header stores it XORed with a key
+//! derived from the passphrase (PBKDF2-HMAC-SHA256) and a digest to tell
+//! whether the passphrase was right. Changing the passphrase only rewrites
+//! the header.
+//!
+//! | offset | size | field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0x00   | 8    | magic, `YSCRYPT\0`                         |
+//! | 0x08   | 4    | version                                    |
+//! | 0x0c   | 4    | PBKDF2 iterations                          |
+//! | 0x10   | 32   | salt                                       |
+//! | 0x30   | 64   | volume key XOR the derived key             |
+//! | 0x70   | 32   | SHA-256 of the salt and the volume key     |
+
+use core::marker::PhantomData;
+
+use aes::{
+    Aes256,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use sha2::{Digest, Sha256};
+
+use super::*;
+use crate::random::Random;
+
+/// Bytes reserved for the header, a whole page so both block sizes align
+pub const HEADER_SIZE: usize = 4096;
+
+/// Iterations of PBKDF2 for a new volume
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+
+const MAGIC: &[u8; 8] = b"YSCRYPT\0";
+const VERSION: u32 = 1;
+const KEY_LEN: usize = 64;
+const SALT_LEN: usize = 32;
+/// Bytes in one XTS data unit
+const SECTOR_SIZE: usize = 512;
+
+/// XTS with two AES-256 keys, one for the data and one for the tweak
+struct Xts {
+    data: Aes256,
+    tweak: Aes256,
+}
+
+impl Xts {
+    fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            data: Aes256::new(GenericArray::from_slice(&key[..32])),
+            tweak: Aes256::new(GenericArray::from_slice(&key[32..])),
+        }
+    }
+
+    /// Encrypt or decrypt one data unit in place, its length must be a
+    /// multiple of the AES block
+    fn apply(&self, unit: usize, data: &mut [u8], encrypt: bool) {
+        let mut tweak = GenericArray::from((unit as u128).to_le_bytes());
+        self.tweak.encrypt_block(&mut tweak);
+        let mut tweak = u128::from_le_bytes(tweak.into());
+
+        for chunk in data.chunks_exact_mut(16) {
+            let mask = tweak.to_le_bytes();
+            chunk.iter_mut().zip(mask).for_each(|(b, m)| *b ^= m);
+
+            let block = GenericArray::from_mut_slice(chunk);
+            if encrypt {
+                self.data.encrypt_block(block);
+            } else {
+                self.data.decrypt_block(block);
+            }
+
+            chunk.iter_mut().zip(mask).for_each(|(b, m)| *b ^= m);
+
+            // multiply by x in GF(2^128), x^128 = x^7 + x^2 + x + 1
+            tweak = (tweak << 1) ^ ((tweak >> 127) * 0x87);
+        }
+    }
+}
+
+/// The key derived from the passphrase, used to hide the volume key
+fn derive(passphrase: &[u8], salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, iterations, &mut key);
+    key
+}
+
+fn digest(salt: &[u8], key: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(salt)
+        .chain_update(key)
+        .finalize()
+        .into()
+}
+
+fn xor(a: &[u8; KEY_LEN], b: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    core::array::from_fn(|i| a[i] ^ b[i])
+}
+
+struct Header {
+    data: [u8; 512],
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> FsResult<Self> {
+        let header = Self {
+            data: data[..512].try_into().unwrap(),
+        };
+
+        if &header.data[..8] != MAGIC || header.version() != VERSION || header.iterations() == 0 {
+            return Err(FsError::InvalidCryptHeader);
+        }
+
+        Ok(header)
+    }
+
+    define_field!(u32, 0x08, version);
+    define_field!(u32, 0x0c, iterations);
+
+    fn salt(&self) -> &[u8] {
+        &self.data[0x10..0x30]
+    }
+
+    fn key_slot(&self) -> [u8; KEY_LEN] {
+        self.data[0x30..0x70].try_into().unwrap()
+    }
+
+    fn digest(&self) -> &[u8] {
+        &self.data[0x70..0x90]
+    }
+
+    fn build(
+        passphrase: &[u8],
+        iterations: u32,
+        salt: &[u8; SALT_LEN],
+        key: &[u8; KEY_LEN],
+    ) -> Self {
+        let mut data = [0u8; 512];
+
+        data[..8].copy_from_slice(MAGIC);
+        data[0x08..0x0c].copy_from_slice(&VERSION.to_le_bytes());
+        data[0x0c..0x10].copy_from_slice(&iterations.to_le_bytes());
+        data[0x10..0x30].copy_from_slice(salt);
+        data[0x30..0x70].copy_from_slice(&xor(key, &derive(passphrase, salt, iterations)));
+        data[0x70..0x90].copy_from_slice(&digest(salt, key));
+
+        Self { data }
+    }
+
+    /// The volume key, if the passphrase is the right one
+    fn unlock(&self, passphrase: &[u8]) -> FsResult<[u8; KEY_LEN]> {
+        let key = xor(
+            &self.key_slot(),
+            &derive(passphrase, self.salt(), self.iterations()),
+        );
+
+        if digest(self.salt(), &key) != self.digest() {
+            return Err(FsError::WrongPassphrase);
+        }
+
+        Ok(key)
+    }
+}
+
+/// A block device whose blocks are stored encrypted on `D`
+pub struct CryptDevice<B, D> {
+    device: D,
+    xts: Xts,
+    _block: PhantomData<B>,
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> CryptDevice<B, D> {
+    /// The header must fit in a block and end on a block boundary
+    fn supported() -> bool {
+        B::size().is_multiple_of(SECTOR_SIZE) && HEADER_SIZE.is_multiple_of(B::size())
+    }
+
+    /// Blocks taken by the header
+    fn header_blocks() -> usize {
+        HEADER_SIZE / B::size()
+    }
+
+    fn read_header(device: &D) -> FsResult<Header> {
+        if !Self::supported() {
+            return Err(FsError::NotSupported);
+        }
+
+        let mut block = B::default();
+        device.read_block(0, &mut block)?;
+        Header::parse(block.as_ref())
+    }
+
+    fn write_header(device: &D, header: &Header) -> FsResult {
+        let zero = B::default();
+        for offset in 0..Self::header_blocks() {
+            device.write_block(offset, &zero)?;
+        }
+
+        let mut block = B::default();
+        block.as_mut()[..512].copy_from_slice(&header.data);
+        device.write_block(0, &block)?;
+        device.flush()
+    }
+
+    /// Write a header with a new random volume key to `device`
+    ///
+    /// The data blocks are not touched, whatever they held reads as noise.
+    /// The salt and the key come from RDRAND, a CPU without it gets
+    /// `NotSupported`.
+    pub fn format(device: &D, passphrase: &[u8], iterations: u32) -> FsResult {
+        if !Self::supported() || iterations == 0 {
+            return Err(FsError::NotSupported);
+        }
+
+        if device.block_count()? <= Self::header_blocks() {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let mut random = [0u8; SALT_LEN + KEY_LEN];
+        Random::fill_from_hardware(&mut random)?;
+        let (salt, key) = random.split_at(SALT_LEN);
+
+        let header = Header::build(
+            passphrase,
+            iterations,
+            salt.try_into().unwrap(),
+            key.try_into().unwrap(),
+        );
+        Self::write_header(device, &header)
+    }
+
+    /// Whether `device` starts with an encryption header
+    pub fn detect(device: &D) -> bool {
+        Self::read_header(device).is_ok()
+    }
+
+    /// Unlock `device` with `passphrase`
+    pub fn open(device: D, passphrase: &[u8]) -> FsResult<Self> {
+        let key = Self::read_header(&device)?.unlock(passphrase)?;
+
+        Ok(Self {
+            device,
+            xts: Xts::new(&key),
+            _block: PhantomData,
+        })
+    }
+
+    /// Protect the same volume key with another passphrase, the data stays
+    /// as it is
+    pub fn change_passphrase(&self, old: &[u8], new: &[u8], iterations: u32) -> FsResult {
+        if iterations == 0 {
+            return Err(FsError::NotSupported);
+        }
+
+        let key = Self::read_header(&self.device)?.unlock(old)?;
+
+        let mut salt = [0u8; SALT_LEN];
+        Random::fill_from_hardware(&mut salt)?;
+
+        let header = Header::build(new, iterations, &salt, &key);
+        Self::write_header(&self.device, &header)
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+
+    /// Encrypt or decrypt block `offset` in place, sector by sector
+    fn apply(&self, offset: usize, block: &mut B, encrypt: bool) {
+        let first = offset * (B::size() / SECTOR_SIZE);
+        for (index, sector) in block.as_mut().chunks_exact_mut(SECTOR_SIZE).enumerate() {
+            self.xts.apply(first + index, sector, encrypt);
+        }
+    }
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> BlockDevice<B> for CryptDevice<B, D> {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.device.block_count()? - Self::header_blocks())
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        self.device
+            .read_block(offset + Self::header_blocks(), block)?;
+        self.apply(offset, block, false);
+        Ok(())
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        self.device
+            .read_blocks(offset + Self::header_blocks(), blocks)?;
+        for (index, block) in blocks.iter_mut().enumerate() {
+            self.apply(offset + index, block, false);
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        let mut encrypted = block.clone();
+        self.apply(offset, &mut encrypted, true);
+        self.device
+            .write_block(offset + Self::header_blocks(), &encrypted)
+    }
+
+    fn flush(&self) -> FsResult {
+        self.device.flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        self.device.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.device.end_transaction()
+    }
+}
+
+impl<B, D> core::fmt::Debug for CryptDevice<B, D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CryptDevice").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Few iterations, the tests are not about PBKDF2
+    const ITERATIONS: u32 = 16;
+
+    #[test]
+    fn test_xts_vector() {
+        // IEEE 1619-2007 vector 10, data unit 0xff
+        let key = hex_literal::hex!(
+            "2718281828459045235360287471352662497757247093699959574966967627"
+            "3141592653589793238462643383279502884197169399375105820974944592"
+        );
+        let plain: Vec<u8> = (0..512).map(|i| i as u8).collect();
+
+        let xts = Xts::new(&key);
+        let mut data = plain.clone();
+        xts.apply(0xff, &mut data, true);
+
+        assert_eq!(
+            data[..32],
+            hex_literal::hex!("1c3b3a102f770386e4836c99e370cf9bea00803f5e482357a4ae12d414a3e63b")
+        );
+        assert_eq!(
+            data[496..],
+            hex_literal::hex!("c4f36ffda9fcea70b9c6e693e148c151")
+        );
+
+        xts.apply(0xff, &mut data, false);
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn test_crypt() {
+        let disk = RamDisk::new(64 * 512);
+        CryptDevice::<Block512, _>::format(&disk, b"secret", ITERATIONS).unwrap();
+        assert!(CryptDevice::<Block512, _>::detect(&disk));
+
+        assert_eq!(
+            CryptDevice::<Block512, _>::open(disk.clone(), b"guess").unwrap_err(),
+            FsError::WrongPassphrase
+        );
+
+        let device = CryptDevice::open(disk.clone(), b"secret").unwrap();
+        assert_eq!(device.block_count().unwrap(), 56);
+
+        let block = Block512::new(&[0x42; 512]);
+        device.write_block(0, &block).unwrap();
+        device.write_block(1, &block).unwrap();
+
+        // not on the disk in clear, and the same data differs per block
+        let raw = disk.to_vec();
+        assert_ne!(raw[4096..4608], block[..]);
+        assert_ne!(raw[4096..4608], raw[4608..5120]);
+
+        let mut read = Block512::default();
+        device.read_block(1, &mut read).unwrap();
+        assert_eq!(read.as_ref(), block.as_ref());
+
+        device
+            .change_passphrase(b"secret", b"new secret", ITERATIONS)
+            .unwrap();
+        assert!(CryptDevice::<Block512, _>::open(disk.clone(), b"secret").is_err());
+
+        // the same volume seen with pages
+        let pages = CryptDevice::<Block4096, _>::open(disk.clone(), b"new secret").unwrap();
+        assert_eq!(pages.block_count().unwrap(), 7);
+
+        let mut page = Block4096::default();
+        pages.read_block(0, &mut page).unwrap();
+        assert_eq!(page[..1024], [0x42; 1024]);
+
+        let data: [u8; 4096] = core::array::from_fn(|i| (i / 512) as u8);
+        pages.write_block(1, &Block4096::new(&data)).unwrap();
+        let device = CryptDevice::<Block512, _>::open(disk, b"new secret").unwrap();
+        device.read_block(8 + 3, &mut read).unwrap();
+        assert_eq!(read.as_ref(), [3; 512]);
+
+        assert_eq!(
+            CryptDevice::<Block512, _>::open(RamDisk::new(64 * 512), b"secret").unwrap_err(),
+            FsError::InvalidCryptHeader
+        );
+    }
+}
@@ -21,8 +21,100 @@ where
     /// Writes a block to the device from the provided buffer
     fn write_block(&self, offset: usize, block: &B) -> FsResult;
 
+    /// Reads consecutive blocks starting at `offset`
+    ///
+    /// Devices that can transfer several blocks in one operation should
+    /// override this, the default reads them one by one.
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        for (index, block) in blocks.iter_mut().enumerate() {
+            self.read_block(offset + index, block)?;
+        }
+        Ok(())
+    }
+
     /// Returns the block size of the device
     fn block_size(&self) -> usize {
         B::size()
     }
+
+    /// Makes sure all written blocks have reached the underlying storage
+    fn flush(&self) -> FsResult {
+        Ok(())
+    }
+
+    /// Starts an operation whose writes must reach the storage together
+    ///
+    /// Calls nest, only the outermost pair delimits the operation. Devices
+    /// without transactions ignore them.
+    fn begin_transaction(&self) -> FsResult {
+        Ok(())
+    }
+
+    /// Ends the operation started by the matching `begin_transaction`
+    fn end_transaction(&self) -> FsResult {
+        Ok(())
+    }
+}
+
+/// A device shared with whoever needs to reach the wrapper below a
+/// filesystem, e.g. to commit an overlay
+impl<B: BlockTrait, T: BlockDevice<B> + ?Sized> BlockDevice<B> for Arc<T> {
+    fn block_count(&self) -> FsResult<usize> {
+        (**self).block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        (**self).read_block(offset, block)
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        (**self).write_block(offset, block)
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        (**self).read_blocks(offset, blocks)
+    }
+
+    fn flush(&self) -> FsResult {
+        (**self).flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        (**self).begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        (**self).end_transaction()
+    }
+}
+
+/// A device picked at runtime among several stacks of wrappers
+impl<B: BlockTrait, T: BlockDevice<B> + ?Sized> BlockDevice<B> for Box<T> {
+    fn block_count(&self) -> FsResult<usize> {
+        (**self).block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        (**self).read_block(offset, block)
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        (**self).write_block(offset, block)
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        (**self).read_blocks(offset, blocks)
+    }
+
+    fn flush(&self) -> FsResult {
+        (**self).flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        (**self).begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        (**self).end_transaction()
+    }
 }
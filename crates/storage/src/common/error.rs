@@ -16,8 +16,14 @@ pub enum FsError {
     NotADirectory,
     /// The entry is not a file.
     NotAFile,
+    /// The entry already exists.
+    AlreadyExists,
+    /// The directory is not empty.
+    DirectoryNotEmpty,
     /// The file is read-only.
     ReadOnly,
+    /// The file or the volume is in use.
+    Busy,
     /// Invalid operation.
     InvalidOperation,
     /// Not supported.
@@ -26,12 +32,28 @@ pub enum FsError {
     BadCluster,
     /// Invalid offset.
     InvalidOffset,
+    /// The partition table is missing or corrupted.
+    InvalidPartitionTable,
+    /// The journal is missing or corrupted.
+    InvalidJournal,
+    /// The operation does not fit in one journal transaction.
+    TransactionTooLarge,
+    /// The encryption header is missing or corrupted.
+    InvalidCryptHeader,
+    /// The passphrase does not unlock the volume.
+    WrongPassphrase,
+    /// The volume descriptor is missing or corrupted.
+    InvalidVolume,
     /// The file name is invalid.
     FileNameError(FilenameError),
     /// Encountered an error while reading from the device.
     DeviceError(DeviceError),
     /// Invalid path.
     InvalidPath(String),
+    /// No filesystem is mounted at the path.
+    NotMounted,
+    /// The source and destination are on different filesystems.
+    CrossDevice,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -59,7 +81,7 @@ pub enum FilenameError {
     InvalidCharacter,
     /// Tried to create a file with no file name.
     FilenameEmpty,
-    /// Given name was too long (we are limited to 8.3).
+    /// Given name was too long.
     NameTooLong,
     /// Can't start a file with a period, or after 8 characters.
     MisplacedPeriod,
@@ -17,6 +17,16 @@ pub trait FileSystem: Debug + Sync + Send {
     /// Returns true if a file or directory at path exists, false otherwise
     fn exists(&self, path: &str) -> FsResult<bool>;
 
+    /// Writes all pending changes back to the underlying device
+    fn sync(&self) -> FsResult {
+        Ok(())
+    }
+
+    /// Returns the size and usage of the filesystem holding this path
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        Err(FsError::NotSupported)
+    }
+
     // ----------------------------------------------------
     // NOTE: following functions are not implemented (optional)
     // ----------------------------------------------------
@@ -31,13 +41,23 @@ pub trait FileSystem: Debug + Sync + Send {
         Err(FsError::NotSupported)
     }
 
+    /// Opens the file at this path for writing, with its content dropped
+    fn truncate_file(&self, _path: &str) -> FsResult<FileHandle> {
+        Err(FsError::NotSupported)
+    }
+
     /// Removes the file at this path
-    fn remove_file(&self, _path: &str) -> FsResult<FileHandle> {
+    fn remove_file(&self, _path: &str) -> FsResult {
+        Err(FsError::NotSupported)
+    }
+
+    /// Creates an empty directory at this path
+    fn create_dir(&self, _path: &str) -> FsResult {
         Err(FsError::NotSupported)
     }
 
-    /// Removes the directory at this path
-    fn remove_dir(&self, _path: &str) -> FsResult<FileHandle> {
+    /// Removes the empty directory at this path
+    fn remove_dir(&self, _path: &str) -> FsResult {
         Err(FsError::NotSupported)
     }
 
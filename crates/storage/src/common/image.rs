@@ -0,0 +1,112 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use super::*;
+
+/// A block device backed by a disk image on the host
+///
+/// Clones share the same open file.
+#[derive(Clone)]
+pub struct ImageFile {
+    file: Arc<Mutex<File>>,
+    readonly: bool,
+}
+
+impl ImageFile {
+    /// Open an existing image for reading and writing
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self::new(file, false))
+    }
+
+    /// Open an existing image, writes fail with `ReadOnly`
+    pub fn open_readonly(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(file, true))
+    }
+
+    /// Create (or truncate) an image of `size` bytes filled with zeros
+    pub fn create(path: impl AsRef<Path>, size: u64) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(size)?;
+        Ok(Self::new(file, false))
+    }
+
+    fn new(file: File, readonly: bool) -> Self {
+        Self {
+            file: Arc::new(Mutex::new(file)),
+            readonly,
+        }
+    }
+}
+
+impl<B: BlockTrait> BlockDevice<B> for ImageFile {
+    fn block_count(&self) -> FsResult<usize> {
+        let file = self.file.lock().unwrap();
+        let len = file
+            .metadata()
+            .map_err(|_| FsError::DeviceError(DeviceError::Unknown))?
+            .len();
+        Ok(len as usize / B::size())
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        let mut file = self.file.lock().unwrap();
+
+        file.seek(SeekFrom::Start((offset * B::size()) as u64))
+            .and_then(|_| file.read_exact(block.as_mut()))
+            .map_err(|_| FsError::DeviceError(DeviceError::ReadError))
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        let mut buf = vec![0u8; blocks.len() * B::size()];
+        let mut file = self.file.lock().unwrap();
+
+        file.seek(SeekFrom::Start((offset * B::size()) as u64))
+            .and_then(|_| file.read_exact(&mut buf))
+            .map_err(|_| FsError::DeviceError(DeviceError::ReadError))?;
+
+        for (block, chunk) in blocks.iter_mut().zip(buf.chunks(B::size())) {
+            block.as_mut().copy_from_slice(chunk);
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        if self.readonly {
+            return Err(FsError::ReadOnly);
+        }
+
+        let mut file = self.file.lock().unwrap();
+
+        file.seek(SeekFrom::Start((offset * B::size()) as u64))
+            .and_then(|_| file.write_all(block.as_ref()))
+            .map_err(|_| FsError::DeviceError(DeviceError::WriteError))
+    }
+
+    fn flush(&self) -> FsResult {
+        self.file
+            .lock()
+            .unwrap()
+            .sync_data()
+            .map_err(|_| FsError::DeviceError(DeviceError::WriteError))
+    }
+}
+
+impl core::fmt::Debug for ImageFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ImageFile")
+            .field("readonly", &self.readonly)
+            .finish()
+    }
+}
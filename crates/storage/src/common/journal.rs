@@ -0,0 +1,663 @@
+//! Write-ahead journal below a filesystem
+//!
+//! The last blocks of the device are reserved for the log, the rest is handed
+//! to the filesystem. Writes are kept in memory until the transaction is
+//! committed, by [`JournalDevice::commit`] or `flush`.
+//!
+//! The filesystem wraps each metadata operation in `begin_transaction` and
+//! `end_transaction`, the writes in between are one transaction committed by
+//! the end. An operation that does not fit in the log fails with
+//! `TransactionTooLarge` and its writes are dropped. Writes outside of an
+//! operation are committed when the log is full.
+//!
+//! A commit goes through these steps, with a flush after each one:
+//!
+//!   1. the descriptor (the home of every block) and the blocks go to the log
+//!   2. the commit block, with a CRC32 of the descriptor and the blocks
+//!   3. the blocks are written to their home (the checkpoint)
+//!   4. the superblock records the sequence number of the transaction
+//!
+//! A crash before 2 loses the transaction, a crash after it is repaired when
+//! the device is opened again by writing the logged blocks home once more.
+//!
+//! ```text
+//! [ data ... ] [ descriptor | blocks ... | commit ] [ superblock ]
+//!              ^ log start                          ^ last block
+//! ```
+
+use alloc::collections::BTreeMap;
+
+use spin::Mutex;
+
+use super::*;
+
+const SUPERBLOCK_MAGIC: &[u8; 8] = b"YSJOURNL";
+const DESCRIPTOR_MAGIC: &[u8; 8] = b"YSJDESC\0";
+const COMMIT_MAGIC: &[u8; 8] = b"YSJCMIT\0";
+const VERSION: u32 = 1;
+
+/// Offset of the first block number in the descriptor
+const TAGS_OFFSET: usize = 0x18;
+/// Block numbers that fit in a descriptor
+const MAX_TAGS: usize = (Block512::BLOCK_SIZE - TAGS_OFFSET) / 4;
+
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn get_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn put(data: &mut [u8], offset: usize, value: &[u8]) {
+    data[offset..offset + value.len()].copy_from_slice(value);
+}
+
+/// The last block of the device
+///
+/// | offset | size | field                                    |
+/// |--------|------|------------------------------------------|
+/// | 0x00   | 8    | magic, `YSJOURNL`                        |
+/// | 0x08   | 4    | version                                  |
+/// | 0x0c   | 4    | blocks in the log, the superblock too    |
+/// | 0x10   | 8    | sequence of the last checkpoint          |
+/// | 0x18   | 4    | CRC32 of the bytes before it             |
+struct Superblock {
+    log_blocks: usize,
+    sequence: u64,
+}
+
+impl Superblock {
+    fn parse(block: &Block512) -> FsResult<Self> {
+        if &block[..8] != SUPERBLOCK_MAGIC
+            || get_u32(block.as_ref(), 0x08) != VERSION
+            || crc32fast::hash(&block[..0x18]) != get_u32(block.as_ref(), 0x18)
+        {
+            return Err(FsError::InvalidJournal);
+        }
+
+        Ok(Self {
+            log_blocks: get_u32(block.as_ref(), 0x0c) as usize,
+            sequence: get_u64(block.as_ref(), 0x10),
+        })
+    }
+
+    fn to_block(&self) -> Block512 {
+        let mut block = Block512::default();
+        let data = block.as_mut();
+
+        put(data, 0x00, SUPERBLOCK_MAGIC);
+        put(data, 0x08, &VERSION.to_le_bytes());
+        put(data, 0x0c, &(self.log_blocks as u32).to_le_bytes());
+        put(data, 0x10, &self.sequence.to_le_bytes());
+        let crc = crc32fast::hash(&data[..0x18]);
+        put(data, 0x18, &crc.to_le_bytes());
+
+        block
+    }
+}
+
+struct State {
+    /// Sequence of the last transaction written home
+    sequence: u64,
+    /// Blocks of the open transaction, by their home
+    pending: BTreeMap<usize, Block512>,
+    /// Operations begun and not ended yet
+    depth: usize,
+    /// Whether the operation outgrew the log, its writes are dropped at the
+    /// end
+    overflowed: bool,
+}
+
+/// A [`Block512`] device whose writes reach the disk in atomic transactions
+pub struct JournalDevice<D> {
+    device: D,
+    /// First block of the log, also the number of blocks for data
+    log_start: usize,
+    log_blocks: usize,
+    state: Mutex<State>,
+}
+
+impl<D: BlockDevice<Block512>> JournalDevice<D> {
+    /// Reserve the last `log_blocks` blocks of `device` for an empty journal
+    ///
+    /// The data blocks are left as they are.
+    pub fn format(device: &D, log_blocks: usize) -> FsResult {
+        let total = device.block_count()?;
+        if log_blocks < 4 || log_blocks >= total {
+            return Err(FsError::InvalidOperation);
+        }
+
+        // no stale descriptor can look like the next transaction
+        device.write_block(total - log_blocks, &Block512::default())?;
+
+        let superblock = Superblock {
+            log_blocks,
+            sequence: 0,
+        };
+        device.write_block(total - 1, &superblock.to_block())?;
+        device.flush()
+    }
+
+    /// Whether `device` ends with a journal superblock
+    pub fn detect(device: &D) -> bool {
+        let mut block = Block512::default();
+        device
+            .block_count()
+            .and_then(|total| device.read_block(total.wrapping_sub(1), &mut block))
+            .and_then(|_| Superblock::parse(&block))
+            .is_ok()
+    }
+
+    /// Open the journal of `device` and finish the transaction a crash may
+    /// have interrupted
+    pub fn open(device: D) -> FsResult<Self> {
+        let total = device.block_count()?;
+        if total == 0 {
+            return Err(FsError::InvalidJournal);
+        }
+
+        let mut block = Block512::default();
+        device.read_block(total - 1, &mut block)?;
+        let superblock = Superblock::parse(&block)?;
+
+        if superblock.log_blocks < 4 || superblock.log_blocks >= total {
+            return Err(FsError::InvalidJournal);
+        }
+
+        let journal = Self {
+            device,
+            log_start: total - superblock.log_blocks,
+            log_blocks: superblock.log_blocks,
+            state: Mutex::new(State {
+                sequence: superblock.sequence,
+                pending: BTreeMap::new(),
+                depth: 0,
+                overflowed: false,
+            }),
+        };
+
+        journal.replay()?;
+        Ok(journal)
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+
+    /// Blocks a single transaction can hold
+    fn capacity(&self) -> usize {
+        // the descriptor, the commit block and the superblock
+        (self.log_blocks - 3).min(MAX_TAGS)
+    }
+
+    /// Read the transaction following the last checkpoint from the log, the
+    /// homes and the blocks if it was committed
+    fn logged(&self, sequence: u64) -> FsResult<Option<Vec<(usize, Block512)>>> {
+        let mut descriptor = Block512::default();
+        self.device.read_block(self.log_start, &mut descriptor)?;
+
+        let count = get_u32(descriptor.as_ref(), 0x10) as usize;
+        if &descriptor[..8] != DESCRIPTOR_MAGIC
+            || get_u64(descriptor.as_ref(), 0x08) != sequence
+            || count > self.capacity()
+        {
+            return Ok(None);
+        }
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(descriptor.as_ref());
+
+        let mut blocks = vec![Block512::default(); count];
+        self.device.read_blocks(self.log_start + 1, &mut blocks)?;
+        for block in blocks.iter() {
+            hasher.update(block.as_ref());
+        }
+
+        let mut commit = Block512::default();
+        self.device
+            .read_block(self.log_start + 1 + count, &mut commit)?;
+        if &commit[..8] != COMMIT_MAGIC
+            || get_u64(commit.as_ref(), 0x08) != sequence
+            || get_u32(commit.as_ref(), 0x10) != hasher.finalize()
+        {
+            return Ok(None);
+        }
+
+        let mut logged = Vec::with_capacity(count);
+        for (index, block) in blocks.into_iter().enumerate() {
+            let home = get_u32(descriptor.as_ref(), TAGS_OFFSET + index * 4) as usize;
+            if home >= self.log_start {
+                return Err(FsError::InvalidJournal);
+            }
+            logged.push((home, block));
+        }
+
+        Ok(Some(logged))
+    }
+
+    fn replay(&self) -> FsResult {
+        let mut state = self.state.lock();
+        let sequence = state.sequence + 1;
+
+        if let Some(blocks) = self.logged(sequence)? {
+            info!(
+                "Journal: replaying transaction {} of {} blocks",
+                sequence,
+                blocks.len()
+            );
+            self.checkpoint(sequence, blocks.iter().map(|(home, block)| (*home, block)))?;
+            state.sequence = sequence;
+        }
+
+        Ok(())
+    }
+
+    /// Write the blocks home, then record that the transaction is done
+    fn checkpoint<'a>(
+        &self,
+        sequence: u64,
+        blocks: impl Iterator<Item = (usize, &'a Block512)>,
+    ) -> FsResult {
+        for (home, block) in blocks {
+            self.device.write_block(home, block)?;
+        }
+        self.device.flush()?;
+
+        let superblock = Superblock {
+            log_blocks: self.log_blocks,
+            sequence,
+        };
+        self.device
+            .write_block(self.log_start + self.log_blocks - 1, &superblock.to_block())?;
+        self.device.flush()
+    }
+
+    fn commit_locked(&self, state: &mut State) -> FsResult {
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+
+        let sequence = state.sequence + 1;
+        trace!(
+            "Journal: committing transaction {} of {} blocks",
+            sequence,
+            state.pending.len()
+        );
+
+        let mut descriptor = Block512::default();
+        let data = descriptor.as_mut();
+        put(data, 0x00, DESCRIPTOR_MAGIC);
+        put(data, 0x08, &sequence.to_le_bytes());
+        put(data, 0x10, &(state.pending.len() as u32).to_le_bytes());
+        for (index, home) in state.pending.keys().enumerate() {
+            put(data, TAGS_OFFSET + index * 4, &(*home as u32).to_le_bytes());
+        }
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(descriptor.as_ref());
+        self.device.write_block(self.log_start, &descriptor)?;
+
+        for (index, block) in state.pending.values().enumerate() {
+            hasher.update(block.as_ref());
+            self.device.write_block(self.log_start + 1 + index, block)?;
+        }
+        self.device.flush()?;
+
+        let mut commit = Block512::default();
+        let data = commit.as_mut();
+        put(data, 0x00, COMMIT_MAGIC);
+        put(data, 0x08, &sequence.to_le_bytes());
+        put(data, 0x10, &hasher.finalize().to_le_bytes());
+        self.device
+            .write_block(self.log_start + 1 + state.pending.len(), &commit)?;
+        self.device.flush()?;
+
+        // the transaction is durable from here, replay finishes it on a crash
+        self.checkpoint(
+            sequence,
+            state.pending.iter().map(|(home, block)| (*home, block)),
+        )?;
+
+        state.sequence = sequence;
+        state.pending.clear();
+        Ok(())
+    }
+
+    /// Make the writes since the last commit durable as one transaction
+    ///
+    /// During an operation, everything before it is already committed and
+    /// its end commits the rest, so this waits for it.
+    pub fn commit(&self) -> FsResult {
+        let mut state = self.state.lock();
+        if state.depth > 0 {
+            return Ok(());
+        }
+
+        self.commit_locked(&mut state)
+    }
+}
+
+impl<D: BlockDevice<Block512>> BlockDevice<Block512> for JournalDevice<D> {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.log_start)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+        if offset >= self.log_start {
+            return Err(FsError::InvalidOffset);
+        }
+
+        if let Some(pending) = self.state.lock().pending.get(&offset) {
+            block.as_mut().copy_from_slice(pending.as_ref());
+            return Ok(());
+        }
+
+        self.device.read_block(offset, block)
+    }
+
+    /// The block joins the open transaction. When it is full, it is
+    /// committed first outside of an operation, an operation fails.
+    fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+        if offset >= self.log_start {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let mut state = self.state.lock();
+        if state.overflowed {
+            return Err(FsError::TransactionTooLarge);
+        }
+
+        if !state.pending.contains_key(&offset) && state.pending.len() >= self.capacity() {
+            if state.depth > 0 {
+                state.overflowed = true;
+                return Err(FsError::TransactionTooLarge);
+            }
+            self.commit_locked(&mut state)?;
+        }
+
+        state.pending.insert(offset, block.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> FsResult {
+        self.commit()
+    }
+
+    /// The writes before the operation are committed, so that it starts
+    /// with an empty transaction
+    fn begin_transaction(&self) -> FsResult {
+        let mut state = self.state.lock();
+        if state.depth == 0 {
+            self.commit_locked(&mut state)?;
+        }
+
+        state.depth += 1;
+        Ok(())
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        let mut state = self.state.lock();
+        state.depth = state
+            .depth
+            .checked_sub(1)
+            .ok_or(FsError::InvalidOperation)?;
+
+        if state.depth > 0 {
+            return Ok(());
+        }
+
+        if state.overflowed {
+            warn!(
+                "Journal: dropping an operation larger than {} blocks",
+                self.capacity()
+            );
+            state.pending.clear();
+            state.overflowed = false;
+            return Err(FsError::TransactionTooLarge);
+        }
+
+        self.commit_locked(&mut state)
+    }
+}
+
+impl<D> core::fmt::Debug for JournalDevice<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("JournalDevice")
+            .field("log_start", &self.log_start)
+            .field("log_blocks", &self.log_blocks)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Loses every write after the first `budget` ones, like a power cut
+    struct Crashing {
+        disk: RamDisk,
+        budget: AtomicUsize,
+    }
+
+    impl BlockDevice<Block512> for Crashing {
+        fn block_count(&self) -> FsResult<usize> {
+            BlockDevice::<Block512>::block_count(&self.disk)
+        }
+
+        fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+            self.disk.read_block(offset, block)
+        }
+
+        fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+            self.budget
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .map_err(|_| FsError::DeviceError(DeviceError::WriteError))?;
+            self.disk.write_block(offset, block)
+        }
+    }
+
+    fn filled(byte: u8) -> Block512 {
+        Block512::new(&[byte; 512])
+    }
+
+    fn read(device: &impl BlockDevice<Block512>, offset: usize) -> u8 {
+        let mut block = Block512::default();
+        device.read_block(offset, &mut block).unwrap();
+        block[0]
+    }
+
+    #[test]
+    fn test_journal() {
+        let disk = RamDisk::new(64 * 512);
+        JournalDevice::format(&disk, 16).unwrap();
+
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        assert_eq!(journal.block_count().unwrap(), 48);
+        assert_eq!(journal.capacity(), 13);
+        assert!(journal.write_block(48, &filled(1)).is_err());
+
+        // not on the disk before the commit, but visible through the journal
+        journal.write_block(3, &filled(1)).unwrap();
+        assert_eq!(read(&journal, 3), 1);
+        assert_eq!(read(&disk, 3), 0);
+
+        journal.flush().unwrap();
+        assert_eq!(read(&disk, 3), 1);
+
+        // a full transaction is committed by the next write
+        for offset in 0..14 {
+            journal.write_block(offset, &filled(2)).unwrap();
+        }
+        assert_eq!(read(&disk, 12), 2);
+        assert_eq!(read(&disk, 13), 0);
+        drop(journal);
+
+        // what was not committed is lost
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        assert_eq!(journal.state.lock().sequence, 2);
+        assert_eq!(read(&journal, 13), 0);
+
+        assert!(JournalDevice::detect(&disk));
+        assert!(!JournalDevice::detect(&RamDisk::new(64 * 512)));
+        assert_eq!(
+            JournalDevice::open(RamDisk::new(64 * 512)).unwrap_err(),
+            FsError::InvalidJournal
+        );
+    }
+
+    #[test]
+    fn test_crash() {
+        let disk = RamDisk::new(64 * 512);
+        JournalDevice::format(&disk, 16).unwrap();
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        for offset in 0..8 {
+            journal.write_block(offset, &filled(1)).unwrap();
+        }
+        journal.flush().unwrap();
+        drop(journal);
+
+        // descriptor, 8 blocks, commit, 8 blocks home and the superblock
+        for budget in 0..=19 {
+            let crashing = Crashing {
+                disk: RamDisk::from_vec(disk.to_vec()),
+                budget: AtomicUsize::new(budget),
+            };
+            let copy = crashing.disk.clone();
+
+            let journal = JournalDevice::open(crashing).unwrap();
+            for offset in 0..8 {
+                journal.write_block(offset, &filled(2)).unwrap();
+            }
+            assert_eq!(journal.flush().is_ok(), budget == 19);
+            drop(journal);
+
+            // either every block is new or none of them is
+            let journal = JournalDevice::open(copy).unwrap();
+            let expected = if budget >= 10 { 2 } else { 1 };
+            for offset in 0..8 {
+                assert_eq!(read(&journal, offset), expected, "budget {}", budget);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transaction() {
+        let disk = RamDisk::new(64 * 512);
+        JournalDevice::format(&disk, 8).unwrap();
+
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        assert_eq!(journal.capacity(), 5);
+
+        // nested operations are committed by the outermost end only
+        journal.begin_transaction().unwrap();
+        for offset in 0..3 {
+            journal.write_block(offset, &filled(1)).unwrap();
+        }
+        journal.begin_transaction().unwrap();
+        journal.write_block(3, &filled(1)).unwrap();
+        journal.end_transaction().unwrap();
+        journal.flush().unwrap();
+        assert_eq!(read(&disk, 0), 0);
+        journal.end_transaction().unwrap();
+        assert_eq!(read(&disk, 3), 1);
+
+        // writes before an operation are not part of it
+        journal.write_block(10, &filled(2)).unwrap();
+        journal.begin_transaction().unwrap();
+        assert_eq!(read(&disk, 10), 2);
+
+        // an operation larger than the log is dropped as a whole
+        for offset in 0..5 {
+            journal.write_block(offset, &filled(3)).unwrap();
+        }
+        assert_eq!(
+            journal.write_block(5, &filled(3)),
+            Err(FsError::TransactionTooLarge)
+        );
+        assert_eq!(
+            journal.write_block(0, &filled(3)),
+            Err(FsError::TransactionTooLarge)
+        );
+        assert_eq!(journal.end_transaction(), Err(FsError::TransactionTooLarge));
+        assert_eq!(read(&journal, 0), 1);
+        assert_eq!(read(&journal, 4), 0);
+        assert_eq!(read(&disk, 0), 1);
+
+        // the next one starts afresh
+        journal.begin_transaction().unwrap();
+        journal.write_block(4, &filled(4)).unwrap();
+        journal.end_transaction().unwrap();
+        assert_eq!(read(&disk, 4), 4);
+
+        assert_eq!(journal.end_transaction(), Err(FsError::InvalidOperation));
+    }
+
+    #[test]
+    fn test_wrapped() {
+        let disk = RamDisk::new(64 * 512);
+        JournalDevice::format(&disk, 16).unwrap();
+
+        // the adapters hand the operation down to the journal
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        let device = SectorDevice::new(PageDevice::new(journal));
+        device.begin_transaction().unwrap();
+        device.write_block(1, &filled(1)).unwrap();
+        device.flush().unwrap();
+        assert_eq!(read(&disk, 1), 0);
+        device.end_transaction().unwrap();
+        assert_eq!(read(&disk, 1), 1);
+    }
+
+    #[test]
+    fn test_fat16() {
+        let disk = RamDisk::new(16 * 1024 * 1024);
+        JournalDevice::format(&disk, 64).unwrap();
+
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        let fs = crate::fat16::Fat16::format(journal, &Default::default()).unwrap();
+        fs.create_dir("/LOG").unwrap();
+        fs.create_file("/LOG/A.TXT")
+            .unwrap()
+            .write_all(b"journaled")
+            .unwrap();
+        fs.sync().unwrap();
+
+        let fs = crate::fat16::Fat16::new(JournalDevice::open(disk).unwrap());
+        assert!(fs.check(false).unwrap().is_clean());
+
+        let mut buf = Vec::new();
+        fs.open_file("/LOG/A.TXT")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"journaled");
+
+        // a directory takes its FAT sectors, its cluster and its entry
+        let disk = RamDisk::new(16 * 1024 * 1024);
+        JournalDevice::format(&disk, 8).unwrap();
+
+        let journal = JournalDevice::open(disk.clone()).unwrap();
+        let fs = crate::fat16::Fat16::format(journal, &Default::default()).unwrap();
+        let free = fs.statfs("/").unwrap().free_blocks;
+        assert_eq!(
+            fs.create_dir("/LOG").unwrap_err(),
+            FsError::TransactionTooLarge
+        );
+        assert!(!fs.exists("/LOG").unwrap());
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, free);
+
+        fs.create_file("/A.TXT")
+            .unwrap()
+            .write_all(b"small")
+            .unwrap();
+        fs.sync().unwrap();
+
+        let fs = crate::fat16::Fat16::new(JournalDevice::open(disk).unwrap());
+        assert!(!fs.exists("/LOG").unwrap());
+        assert!(fs.exists("/A.TXT").unwrap());
+        assert!(fs.check(false).unwrap().is_clean());
+    }
+}
@@ -0,0 +1,91 @@
+//! Loop device, a file seen as a disk
+//!
+//! Block `n` is bytes `n * 512 .. (n + 1) * 512` of the file, a trailing part
+//! shorter than a block is not reachable. The file is never grown, so a disk
+//! image keeps its size whatever the filesystem inside it does.
+
+use spin::Mutex;
+
+use super::*;
+
+const SECTOR_SIZE: usize = Block512::BLOCK_SIZE;
+
+pub struct LoopDevice {
+    file: Mutex<FileHandle>,
+    blocks: usize,
+}
+
+impl LoopDevice {
+    /// Wrap `file`, its size is taken once by seeking to the end
+    pub fn new(mut file: FileHandle) -> FsResult<Self> {
+        let size = file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            blocks: size / SECTOR_SIZE,
+        })
+    }
+
+    /// Give the file back, after writing what it still buffers
+    pub fn into_inner(self) -> FsResult<FileHandle> {
+        let mut file = self.file.into_inner();
+        file.flush()?;
+        Ok(file)
+    }
+
+    /// Lock the file and move it to block `offset`
+    fn seek(&self, offset: usize) -> FsResult<spin::MutexGuard<'_, FileHandle>> {
+        if offset >= self.blocks {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+        Ok(file)
+    }
+}
+
+impl BlockDevice<Block512> for LoopDevice {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.blocks)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+        self.seek(offset)?.read_exact(block.as_mut())
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> FsResult {
+        if offset + blocks.len() > self.blocks {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let mut file = self.seek(offset)?;
+        for block in blocks {
+            file.read_exact(block.as_mut())?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+        self.seek(offset)?.write_all(block.as_ref())
+    }
+
+    fn flush(&self) -> FsResult {
+        self.file.lock().flush()
+    }
+
+    /// A file has no transactions, the best is to hand the whole operation
+    /// to the backing filesystem once it is done
+    fn end_transaction(&self) -> FsResult {
+        self.flush()
+    }
+}
+
+impl core::fmt::Debug for LoopDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LoopDevice")
+            .field("file", &self.file.lock().meta.name)
+            .field("blocks", &self.blocks)
+            .finish()
+    }
+}
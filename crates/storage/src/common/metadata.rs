@@ -11,6 +11,8 @@ pub enum FileType {
     File,
     /// A Directory
     Directory,
+    /// A symbolic link, its length is the length of the target
+    Symlink,
 }
 
 #[derive(Debug)]
@@ -28,6 +30,14 @@ pub struct Metadata {
     pub modified: Option<FsTime>,
     /// Access time of the file
     pub accessed: Option<FsTime>,
+    /// File type and permission bits in the `st_mode` layout
+    pub mode: Option<u16>,
+    /// Owner user id
+    pub uid: Option<u32>,
+    /// Owner group id
+    pub gid: Option<u32>,
+    /// Inode number, the same for every hard link to a file
+    pub inode: Option<usize>,
 }
 
 impl Metadata {
@@ -47,9 +57,22 @@ impl Metadata {
             modified,
             accessed,
             entry_type,
+            mode: None,
+            uid: None,
+            gid: None,
+            inode: None,
         }
     }
 
+    /// Add what an inode records about the entry
+    pub fn with_inode(mut self, inode: usize, mode: u16, uid: u32, gid: u32) -> Self {
+        self.inode = Some(inode);
+        self.mode = Some(mode);
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
     /// Return `true` if the entry is a file
     pub fn is_file(&self) -> bool {
         self.entry_type == FileType::File
@@ -59,4 +82,24 @@ impl Metadata {
     pub fn is_dir(&self) -> bool {
         self.entry_type == FileType::Directory
     }
+
+    /// Return `true` if the entry is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.entry_type == FileType::Symlink
+    }
+}
+
+/// Size and usage of a filesystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStat {
+    /// Allocation unit in bytes, the cluster size for FAT
+    pub block_size: usize,
+    /// Blocks available for data
+    pub total_blocks: usize,
+    /// Blocks not in use
+    pub free_blocks: usize,
+    /// Longest file name accepted
+    pub max_name_len: usize,
+    /// Nothing can be written
+    pub read_only: bool,
 }
@@ -1,25 +1,41 @@
 #[macro_use]
 mod macros;
 
+mod adapter;
 mod block;
 mod cache;
+mod crypt;
 mod device;
 mod error;
 mod filehandle;
 mod filesystem;
+#[cfg(feature = "std")]
+mod image;
 mod io;
+mod journal;
+mod loopdev;
 mod metadata;
 mod mount;
+mod overlay;
+mod ramdisk;
 
+pub use adapter::*;
 pub use block::*;
 pub use cache::*;
+pub use crypt::*;
 pub use device::*;
 pub use error::*;
 pub use filehandle::*;
 pub use filesystem::*;
+#[cfg(feature = "std")]
+pub use image::*;
 pub use io::*;
+pub use journal::*;
+pub use loopdev::*;
 pub use metadata::*;
 pub use mount::*;
+pub use overlay::*;
+pub use ramdisk::*;
 
 use super::*;
 
@@ -1,3 +1,5 @@
+use spin::RwLock;
+
 use super::*;
 
 pub struct Mount {
@@ -8,12 +10,38 @@ pub struct Mount {
 impl Mount {
     #[inline]
     pub fn new(fs: Box<dyn FileSystem>, mount_point: Box<str>) -> Self {
+        let mount_point = normalize(&mount_point).into();
         Self { fs, mount_point }
     }
 
+    /// Returns true if `path` is the mount point or lies below it
+    pub fn contains(&self, path: &str) -> bool {
+        if self.is_root() {
+            return true;
+        }
+
+        match path.strip_prefix(self.mount_point.as_ref()) {
+            Some(rest) => rest.is_empty() || rest.starts_with(PATH_SEPARATOR),
+            None => false,
+        }
+    }
+
+    #[inline]
+    fn is_root(&self) -> bool {
+        self.mount_point.as_ref() == "/"
+    }
+
     #[inline]
     fn trim_mount_point<'a>(&self, path: &'a str) -> &'a str {
-        path.trim_start_matches(self.mount_point.as_ref())
+        if self.is_root() {
+            return path.trim_start_matches(PATH_SEPARATOR);
+        }
+
+        match path.strip_prefix(self.mount_point.as_ref()) {
+            Some("") => "/",
+            Some(rest) => rest,
+            None => path,
+        }
     }
 }
 
@@ -37,6 +65,64 @@ impl FileSystem for Mount {
     fn exists(&self, path: &str) -> FsResult<bool> {
         self.fs.exists(self.trim_mount_point(path))
     }
+
+    #[inline]
+    fn sync(&self) -> FsResult {
+        self.fs.sync()
+    }
+
+    #[inline]
+    fn statfs(&self, path: &str) -> FsResult<FsStat> {
+        self.fs.statfs(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.fs.create_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.fs.append_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn truncate_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.fs.truncate_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.fs.remove_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.fs.create_dir(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn remove_dir(&self, path: &str) -> FsResult {
+        self.fs.remove_dir(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        self.fs
+            .copy_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    #[inline]
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.fs
+            .move_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    #[inline]
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        self.fs
+            .move_dir(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
 }
 
 impl core::fmt::Debug for Mount {
@@ -47,3 +133,238 @@ impl core::fmt::Debug for Mount {
             .finish()
     }
 }
+
+/// All mounted filesystems, a path is served by the deepest mount point that
+/// contains it.
+///
+/// Mount points that have no directory in their parent filesystem are still
+/// listed by `read_dir` and reported by `metadata`.
+#[derive(Default)]
+pub struct MountTable {
+    /// Sorted by mount point length, the deepest mount comes first
+    mounts: RwLock<Vec<Arc<Mount>>>,
+}
+
+impl MountTable {
+    pub const fn new() -> Self {
+        Self {
+            mounts: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Mounts `fs` at `mount_point`, which must be an absolute path
+    pub fn mount(&self, mount_point: &str, fs: Box<dyn FileSystem>) -> FsResult {
+        if !mount_point.starts_with(PATH_SEPARATOR) {
+            return Err(FsError::InvalidPath(mount_point.into()));
+        }
+
+        let mount = Mount::new(fs, mount_point.into());
+        let mut mounts = self.mounts.write();
+
+        if mounts.iter().any(|m| m.mount_point == mount.mount_point) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        mounts.push(Arc::new(mount));
+        mounts.sort_by_key(|m| core::cmp::Reverse(m.mount_point.len()));
+
+        Ok(())
+    }
+
+    /// Syncs and removes the filesystem mounted at `mount_point`
+    ///
+    /// Files that are still open keep the filesystem alive until closed.
+    pub fn unmount(&self, mount_point: &str) -> FsResult {
+        let mount_point = normalize(mount_point);
+
+        let mount = {
+            let mut mounts = self.mounts.write();
+            let index = mounts
+                .iter()
+                .position(|m| m.mount_point.as_ref() == mount_point)
+                .ok_or(FsError::NotMounted)?;
+            mounts.remove(index)
+        };
+
+        mount.sync()
+    }
+
+    /// Returns the mount points, deepest first
+    pub fn mount_points(&self) -> Vec<Box<str>> {
+        self.mounts
+            .read()
+            .iter()
+            .map(|m| m.mount_point.clone())
+            .collect()
+    }
+
+    /// Finds the mount that serves `path`
+    pub fn resolve(&self, path: &str) -> FsResult<Arc<Mount>> {
+        self.mounts
+            .read()
+            .iter()
+            .find(|m| m.contains(path))
+            .cloned()
+            .ok_or(FsError::NotMounted)
+    }
+
+    /// Resolves both paths of a copy or move, they must be on the same mount
+    fn resolve_pair(&self, src: &str, dst: &str) -> FsResult<Arc<Mount>> {
+        let mount = self.resolve(src)?;
+        if !Arc::ptr_eq(&mount, &self.resolve(dst)?) {
+            return Err(FsError::CrossDevice);
+        }
+        Ok(mount)
+    }
+
+    /// Names of the mount points directly below the directory `path`
+    fn child_mounts(&self, path: &str) -> Vec<String> {
+        let path = path.trim_end_matches(PATH_SEPARATOR);
+
+        self.mounts
+            .read()
+            .iter()
+            .filter_map(|m| {
+                let (parent, name) = m.mount_point.rsplit_once(PATH_SEPARATOR)?;
+                (!name.is_empty() && parent == path).then(|| name.to_owned())
+            })
+            .collect()
+    }
+
+    /// Returns true if `path` is exactly a mount point
+    fn is_mount_point(&self, path: &str) -> bool {
+        let path = normalize(path);
+
+        self.mounts
+            .read()
+            .iter()
+            .any(|m| m.mount_point.as_ref() == path)
+    }
+}
+
+impl FileSystem for MountTable {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let children = self.child_mounts(path);
+
+        let entries: Box<dyn Iterator<Item = Metadata> + Send> =
+            match self.resolve(path)?.read_dir(path) {
+                Ok(iter) => {
+                    let hidden = children.clone();
+                    Box::new(iter.filter(move |meta| !hidden.contains(&meta.name)))
+                }
+                // the mount point may not exist in the parent filesystem
+                Err(_) if !children.is_empty() => Box::new(core::iter::empty()),
+                Err(err) => return Err(err),
+            };
+
+        let mounts = children
+            .into_iter()
+            .map(|name| Metadata::new(name, FileType::Directory, 0, None, None, None));
+
+        Ok(Box::new(entries.chain(mounts)))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.resolve(path)?.open_file(path)
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        match self.resolve(path)?.metadata(path) {
+            Err(_) if self.is_mount_point(path) => {
+                let name = path.trim_end_matches(PATH_SEPARATOR);
+                let name = name.rsplit(PATH_SEPARATOR).next().unwrap_or_default();
+                Ok(Metadata::new(
+                    name.to_owned(),
+                    FileType::Directory,
+                    0,
+                    None,
+                    None,
+                    None,
+                ))
+            }
+            res => res,
+        }
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        if self.is_mount_point(path) {
+            return Ok(true);
+        }
+        self.resolve(path)?.exists(path)
+    }
+
+    fn sync(&self) -> FsResult {
+        let mounts = self.mounts.read().clone();
+        let mut result = Ok(());
+
+        // keep going so one failing mount does not stop the others
+        for mount in mounts {
+            if let Err(err) = mount.sync() {
+                warn!("Failed to sync {}: {:?}", mount.mount_point, err);
+                result = Err(err);
+            }
+        }
+
+        result
+    }
+
+    fn statfs(&self, path: &str) -> FsResult<FsStat> {
+        self.resolve(path)?.statfs(path)
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.resolve(path)?.create_file(path)
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.resolve(path)?.append_file(path)
+    }
+
+    fn truncate_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.resolve(path)?.truncate_file(path)
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.resolve(path)?.remove_file(path)
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.resolve(path)?.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        if self.is_mount_point(path) {
+            return Err(FsError::InvalidOperation);
+        }
+        self.resolve(path)?.remove_dir(path)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        self.resolve_pair(src, dst)?.copy_file(src, dst)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.resolve_pair(src, dst)?.move_file(src, dst)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        if self.is_mount_point(src) {
+            return Err(FsError::InvalidOperation);
+        }
+        self.resolve_pair(src, dst)?.move_dir(src, dst)
+    }
+}
+
+/// Strips trailing separators, keeping "/" for the root
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches(PATH_SEPARATOR) {
+        "" => "/",
+        trimmed => trimmed,
+    }
+}
+
+impl core::fmt::Debug for MountTable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.mounts.read().iter()).finish()
+    }
+}
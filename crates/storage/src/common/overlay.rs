@@ -0,0 +1,323 @@
+//! Copy-on-write overlay over a read-only device
+//!
+//! Written blocks go to a [`DeltaStore`], reads are served from it when the
+//! block has been written and from the base device otherwise. The base is
+//! never written until [`OverlayDevice::commit`], [`OverlayDevice::discard`]
+//! forgets every write since the last commit.
+
+use alloc::collections::BTreeMap;
+
+use spin::Mutex;
+
+use super::*;
+
+/// Where an [`OverlayDevice`] keeps the blocks written to it
+pub trait DeltaStore<B: BlockTrait>: Send + Sync + 'static {
+    /// Read block `offset` into `block`, false if it was never written
+    fn load(&self, offset: usize, block: &mut B) -> FsResult<bool>;
+
+    /// Keep `block` as the new content of block `offset`
+    fn store(&self, offset: usize, block: &B) -> FsResult;
+
+    /// Whether block `offset` has been written
+    fn contains(&self, offset: usize) -> bool;
+
+    /// The written blocks, in order
+    fn offsets(&self) -> Vec<usize>;
+
+    /// Forget every written block
+    fn clear(&self) -> FsResult;
+}
+
+/// Written blocks kept in memory
+pub struct RamDelta<B> {
+    blocks: Mutex<BTreeMap<usize, B>>,
+}
+
+impl<B> RamDelta<B> {
+    pub fn new() -> Self {
+        Self {
+            blocks: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<B> Default for RamDelta<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: BlockTrait> DeltaStore<B> for RamDelta<B> {
+    fn load(&self, offset: usize, block: &mut B) -> FsResult<bool> {
+        match self.blocks.lock().get(&offset) {
+            Some(stored) => {
+                block.as_mut().copy_from_slice(stored.as_ref());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn store(&self, offset: usize, block: &B) -> FsResult {
+        self.blocks.lock().insert(offset, block.clone());
+        Ok(())
+    }
+
+    fn contains(&self, offset: usize) -> bool {
+        self.blocks.lock().contains_key(&offset)
+    }
+
+    fn offsets(&self) -> Vec<usize> {
+        self.blocks.lock().keys().copied().collect()
+    }
+
+    fn clear(&self) -> FsResult {
+        self.blocks.lock().clear();
+        Ok(())
+    }
+}
+
+/// Written blocks stored one after the other on a scratch device
+///
+/// Only the map from block to slot is kept in memory. The scratch device
+/// runs out once as many distinct blocks as it holds have been written.
+pub struct DeviceDelta<B, D> {
+    device: D,
+    slots: Mutex<BTreeMap<usize, usize>>,
+    _block: core::marker::PhantomData<B>,
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> DeviceDelta<B, D> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            slots: Mutex::new(BTreeMap::new()),
+            _block: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> DeltaStore<B> for DeviceDelta<B, D> {
+    fn load(&self, offset: usize, block: &mut B) -> FsResult<bool> {
+        let slot = self.slots.lock().get(&offset).copied();
+        match slot {
+            Some(slot) => self.device.read_block(slot, block).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    fn store(&self, offset: usize, block: &B) -> FsResult {
+        let mut slots = self.slots.lock();
+
+        let slot = match slots.get(&offset) {
+            Some(&slot) => slot,
+            None if slots.len() < self.device.block_count()? => slots.len(),
+            None => return Err(FsError::WriteZero),
+        };
+
+        self.device.write_block(slot, block)?;
+        slots.insert(offset, slot);
+        Ok(())
+    }
+
+    fn contains(&self, offset: usize) -> bool {
+        self.slots.lock().contains_key(&offset)
+    }
+
+    fn offsets(&self) -> Vec<usize> {
+        self.slots.lock().keys().copied().collect()
+    }
+
+    fn clear(&self) -> FsResult {
+        self.slots.lock().clear();
+        Ok(())
+    }
+}
+
+/// A device that leaves its base untouched, writes go to the delta `S`
+pub struct OverlayDevice<B, D, S> {
+    base: D,
+    delta: S,
+    _block: core::marker::PhantomData<B>,
+}
+
+impl<B, D, S> OverlayDevice<B, D, S>
+where
+    B: BlockTrait,
+    D: BlockDevice<B>,
+    S: DeltaStore<B>,
+{
+    pub fn new(base: D, delta: S) -> Self {
+        Self {
+            base,
+            delta,
+            _block: core::marker::PhantomData,
+        }
+    }
+
+    pub fn base(&self) -> &D {
+        &self.base
+    }
+
+    /// Number of blocks that differ from the base
+    pub fn dirty_blocks(&self) -> usize {
+        self.delta.offsets().len()
+    }
+
+    /// Write the delta to the base and start over from the result
+    pub fn commit(&self) -> FsResult {
+        let mut block = B::default();
+
+        for offset in self.delta.offsets() {
+            self.delta.load(offset, &mut block)?;
+            self.base.write_block(offset, &block)?;
+        }
+
+        self.base.flush()?;
+        self.delta.clear()
+    }
+
+    /// Drop the delta, the device reads as the base again
+    pub fn discard(&self) -> FsResult {
+        self.delta.clear()
+    }
+}
+
+impl<B, D, S> BlockDevice<B> for OverlayDevice<B, D, S>
+where
+    B: BlockTrait,
+    D: BlockDevice<B>,
+    S: DeltaStore<B>,
+{
+    fn block_count(&self) -> FsResult<usize> {
+        self.base.block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        if self.delta.load(offset, block)? {
+            return Ok(());
+        }
+        self.base.read_block(offset, block)
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        // one read from the base, then the written blocks on top
+        self.base.read_blocks(offset, blocks)?;
+        for (index, block) in blocks.iter_mut().enumerate() {
+            self.delta.load(offset + index, block)?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        if offset >= self.base.block_count()? {
+            return Err(FsError::InvalidOffset);
+        }
+        self.delta.store(offset, block)
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        self.base.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.base.end_transaction()
+    }
+}
+
+impl<B, D, S> core::fmt::Debug for OverlayDevice<B, D, S>
+where
+    B: BlockTrait,
+    D: BlockDevice<B>,
+    S: DeltaStore<B>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OverlayDevice")
+            .field("dirty_blocks", &self.dirty_blocks())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(device: &impl BlockDevice<Block512>, offset: usize) -> u8 {
+        let mut block = Block512::default();
+        device.read_block(offset, &mut block).unwrap();
+        block[0]
+    }
+
+    #[test]
+    fn test_overlay() {
+        let base = RamDisk::from_vec(vec![1; 16 * 512]);
+        let overlay = OverlayDevice::new(base.clone(), RamDelta::new());
+
+        overlay.write_block(3, &Block512::new(&[2; 512])).unwrap();
+        assert_eq!(read(&overlay, 3), 2);
+        assert_eq!(read(&base, 3), 1);
+        assert_eq!(overlay.dirty_blocks(), 1);
+        assert!(overlay.write_block(16, &Block512::default()).is_err());
+
+        let mut blocks = vec![Block512::default(); 4];
+        overlay.read_blocks(2, &mut blocks).unwrap();
+        let firsts: Vec<u8> = blocks.iter().map(|block| block[0]).collect();
+        assert_eq!(firsts, [1, 2, 1, 1]);
+
+        overlay.discard().unwrap();
+        assert_eq!(read(&overlay, 3), 1);
+
+        overlay.write_block(5, &Block512::new(&[3; 512])).unwrap();
+        overlay.commit().unwrap();
+        assert_eq!(read(&base, 5), 3);
+        assert_eq!(overlay.dirty_blocks(), 0);
+    }
+
+    #[test]
+    fn test_device_delta() {
+        let base = RamDisk::from_vec(vec![1; 16 * 512]);
+        let scratch = RamDisk::new(2 * 512);
+        let overlay = OverlayDevice::new(base.clone(), DeviceDelta::new(scratch.clone()));
+
+        overlay.write_block(9, &Block512::new(&[4; 512])).unwrap();
+        overlay.write_block(2, &Block512::new(&[5; 512])).unwrap();
+        overlay.write_block(9, &Block512::new(&[6; 512])).unwrap();
+
+        assert_eq!(read(&overlay, 9), 6);
+        assert_eq!(read(&overlay, 2), 5);
+        assert_eq!(read(&scratch, 0), 6);
+        assert!(base.to_vec().iter().all(|&b| b == 1));
+
+        // the scratch device is full
+        assert_eq!(
+            overlay.write_block(0, &Block512::default()),
+            Err(FsError::WriteZero)
+        );
+
+        overlay.discard().unwrap();
+        assert_eq!(read(&overlay, 9), 1);
+    }
+
+    #[test]
+    fn test_pristine_fat16() {
+        let base = RamDisk::new(16 * 1024 * 1024);
+        crate::fat16::mkfs::format(&base, &Default::default()).unwrap();
+        let pristine = base.to_vec();
+
+        let overlay = Arc::new(OverlayDevice::new(base.clone(), RamDelta::new()));
+        let fs = crate::fat16::Fat16::new(overlay.clone());
+        fs.create_file("/RUN.TXT")
+            .unwrap()
+            .write_all(b"student output")
+            .unwrap();
+        fs.sync().unwrap();
+
+        assert!(fs.exists("/RUN.TXT").unwrap());
+        assert!(base.to_vec() == pristine);
+
+        overlay.discard().unwrap();
+        let fs = crate::fat16::Fat16::new(overlay);
+        assert!(!fs.exists("/RUN.TXT").unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+use spin::RwLock;
+
+use super::*;
+
+/// A block device kept in memory
+///
+/// Clones share the same storage, so a disk can be handed to a partition
+/// table and inspected afterwards.
+#[derive(Clone, Default)]
+pub struct RamDisk {
+    data: Arc<RwLock<Vec<u8>>>,
+}
+
+impl RamDisk {
+    /// Create a zeroed disk of `size` bytes
+    pub fn new(size: usize) -> Self {
+        Self::from_vec(vec![0; size])
+    }
+
+    /// Create a disk holding an existing image
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self {
+            data: Arc::new(RwLock::new(data)),
+        }
+    }
+
+    /// Size of the disk in bytes
+    pub fn len(&self) -> usize {
+        self.data.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy the current content of the disk
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.data.read().clone()
+    }
+}
+
+impl<B: BlockTrait> BlockDevice<B> for RamDisk {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.len() / B::size())
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        let size = B::size();
+        let data = self.data.read();
+        let range = offset * size..(offset + 1) * size;
+
+        block.as_mut().copy_from_slice(
+            data.get(range)
+                .ok_or(FsError::DeviceError(DeviceError::ReadError))?,
+        );
+
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        let size = B::size();
+        let mut data = self.data.write();
+        let range = offset * size..(offset + 1) * size;
+
+        data.get_mut(range)
+            .ok_or(FsError::DeviceError(DeviceError::WriteError))?
+            .copy_from_slice(block.as_ref());
+
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for RamDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RamDisk").field("len", &self.len()).finish()
+    }
+}
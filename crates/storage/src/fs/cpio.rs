@@ -0,0 +1,408 @@
+//! Read-only cpio archive in the "newc" format, as used for initramfs
+//!
+//! reference:
+//! - <https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html>
+//! - <https://man.archlinux.org/man/cpio.5#New_ASCII_Format>
+//!
+//! Every member is a 110 byte ASCII header, the name and the data, the name
+//! and the data both padded to 4 bytes. The archive ends with a member called
+//! `TRAILER!!!`. Directories that only appear as part of a path are added as
+//! if they were in the archive.
+
+use alloc::collections::BTreeMap;
+
+use chrono::DateTime;
+
+use crate::*;
+
+const HEADER_LEN: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// The fixed part of a newc member
+struct Header<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    fn new(data: &'a [u8]) -> FsResult<Self> {
+        let data = data.get(..HEADER_LEN).ok_or(FsError::EndOfFile)?;
+
+        // 070702 is the same layout with a checksum
+        if &data[..6] != b"070701" && &data[..6] != b"070702" {
+            return Err(FsError::InvalidOperation);
+        }
+
+        Ok(Self { data })
+    }
+
+    /// Parse the `index`-th 8 digit hex field after the magic
+    fn field(&self, index: usize) -> FsResult<u32> {
+        let start = 6 + index * 8;
+        core::str::from_utf8(&self.data[start..start + 8])
+            .ok()
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or(FsError::InvalidOperation)
+    }
+
+    fn mode(&self) -> FsResult<u32> {
+        self.field(1)
+    }
+
+    fn mtime(&self) -> FsResult<u32> {
+        self.field(5)
+    }
+
+    fn file_size(&self) -> FsResult<usize> {
+        self.field(6).map(|size| size as usize)
+    }
+
+    fn name_size(&self) -> FsResult<usize> {
+        self.field(11).map(|size| size as usize)
+    }
+}
+
+#[derive(Clone)]
+struct Entry {
+    kind: FileType,
+    data: &'static [u8],
+    mtime: Option<FsTime>,
+}
+
+/// A cpio archive kept in memory, mounted without copying the files
+pub struct CpioFs {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl CpioFs {
+    pub fn new(archive: &'static [u8]) -> FsResult<Self> {
+        let mut entries = BTreeMap::new();
+        let mut offset = 0;
+
+        loop {
+            // the padding after the last member may be missing too
+            let header = Header::new(archive.get(offset..).ok_or(FsError::EndOfFile)?)?;
+            let name_size = header.name_size()?;
+            let file_size = header.file_size()?;
+
+            let name_start = offset + HEADER_LEN;
+            let data_start = (name_start + name_size).next_multiple_of(4);
+            let data_end = data_start + file_size;
+
+            if data_end > archive.len() || name_size == 0 {
+                return Err(FsError::EndOfFile);
+            }
+
+            // the name includes the trailing NUL
+            let name = core::str::from_utf8(&archive[name_start..name_start + name_size - 1])
+                .map_err(|_| FsError::FileNameError(FilenameError::Utf8Error))?;
+
+            if name == TRAILER {
+                break;
+            }
+
+            let kind = match header.mode()? & S_IFMT {
+                S_IFDIR => Some(FileType::Directory),
+                S_IFREG => Some(FileType::File),
+                _ => None,
+            };
+
+            let path = normalize(name);
+            add_parents(&mut entries, path);
+
+            // device nodes and symlinks are skipped, their directories are kept
+            if let Some(kind) = kind
+                && !path.is_empty()
+            {
+                let mtime = DateTime::from_timestamp(header.mtime()? as i64, 0);
+                entries.insert(
+                    path.to_owned(),
+                    Entry {
+                        kind,
+                        data: &archive[data_start..data_end],
+                        mtime,
+                    },
+                );
+            }
+
+            offset = data_end.next_multiple_of(4);
+        }
+
+        Ok(Self { entries })
+    }
+
+    fn entry(&self, path: &str) -> FsResult<&Entry> {
+        self.entries
+            .get(normalize(path))
+            .ok_or(FsError::FileNotFound)
+    }
+}
+
+/// Strips leading "./" and "/" and the trailing separator
+fn normalize(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_matches(PATH_SEPARATOR);
+    if path == "." { "" } else { path }
+}
+
+fn name_of(path: &str) -> &str {
+    path.rsplit(PATH_SEPARATOR).next().unwrap_or(path)
+}
+
+fn parent_of(path: &str) -> &str {
+    path.rsplit_once(PATH_SEPARATOR)
+        .map(|(parent, _)| parent)
+        .unwrap_or("")
+}
+
+/// Adds the directories leading to `path` that are not in the archive
+fn add_parents(entries: &mut BTreeMap<String, Entry>, path: &str) {
+    let mut parent = parent_of(path);
+
+    while !parent.is_empty() && !entries.contains_key(parent) {
+        entries.insert(
+            parent.to_owned(),
+            Entry {
+                kind: FileType::Directory,
+                data: &[],
+                mtime: None,
+            },
+        );
+        parent = parent_of(parent);
+    }
+}
+
+fn as_meta(name: &str, entry: &Entry) -> Metadata {
+    Metadata::new(
+        name.to_owned(),
+        entry.kind,
+        entry.data.len(),
+        entry.mtime,
+        entry.mtime,
+        None,
+    )
+}
+
+impl FileSystem for CpioFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let dir = normalize(path);
+
+        if !dir.is_empty() && self.entry(dir)?.kind != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(path, _)| parent_of(path) == dir)
+            .map(|(path, entry)| as_meta(name_of(path), entry))
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let entry = self.entry(path)?;
+
+        if entry.kind != FileType::File {
+            return Err(FsError::NotAFile);
+        }
+
+        let meta = as_meta(name_of(normalize(path)), entry);
+        let file = File {
+            data: entry.data,
+            offset: 0,
+        };
+
+        Ok(FileHandle::new(meta, Box::new(file)))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let path = normalize(path);
+
+        if path.is_empty() {
+            return Ok(Metadata::new(
+                String::new(),
+                FileType::Directory,
+                0,
+                None,
+                None,
+                None,
+            ));
+        }
+
+        Ok(as_meta(name_of(path), self.entry(path)?))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        let path = normalize(path);
+        Ok(path.is_empty() || self.entries.contains_key(path))
+    }
+
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        let used: usize = self.entries.values().map(|entry| entry.data.len()).sum();
+
+        Ok(FsStat {
+            block_size: 1,
+            total_blocks: used,
+            free_blocks: 0,
+            // newc stores the length of the name in 8 hex digits
+            max_name_len: u32::MAX as usize,
+            read_only: true,
+        })
+    }
+}
+
+impl core::fmt::Debug for CpioFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CpioFs")
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+/// A file inside the archive
+pub struct File {
+    data: &'static [u8],
+    offset: usize,
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let rest = &self.data[self.offset.min(self.data.len())..];
+        let len = rest.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.offset += len;
+
+        Ok(len)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.data.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        self.offset = offset.ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Append a newc member the way `cpio -o -H newc` writes it
+    fn member(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        let fields = [
+            1,
+            mode,
+            0,
+            0,
+            1,
+            1_600_000_000,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+
+        archive.extend_from_slice(b"070701");
+        for field in fields {
+            archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+
+    fn archive() -> &'static [u8] {
+        let mut archive = Vec::new();
+        member(&mut archive, ".", S_IFDIR | 0o755, &[]);
+        member(&mut archive, "APP", S_IFDIR | 0o755, &[]);
+        member(&mut archive, "APP/SH", S_IFREG | 0o755, b"\x7fELF shell");
+        member(&mut archive, "./etc/motd", S_IFREG | 0o644, b"hello\n");
+        member(&mut archive, "dev/console", 0o020000 | 0o600, &[]);
+        member(&mut archive, TRAILER, 0, &[]);
+        Vec::leak(archive)
+    }
+
+    fn names(fs: &CpioFs, path: &str) -> Vec<String> {
+        fs.read_dir(path).unwrap().map(|meta| meta.name).collect()
+    }
+
+    #[test]
+    fn test_cpio() {
+        let fs = CpioFs::new(archive()).unwrap();
+
+        // "etc" and "dev" only appear as parents
+        assert_eq!(names(&fs, "/"), ["APP", "dev", "etc"]);
+        assert_eq!(names(&fs, "/APP/"), ["SH"]);
+        assert!(names(&fs, "/dev").is_empty());
+        assert!(fs.metadata("/etc").unwrap().is_dir());
+
+        let mut buf = Vec::new();
+        fs.open_file("/etc/motd")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"hello\n");
+
+        let mut file = fs.open_file("APP/SH").unwrap();
+        assert_eq!(file.meta.len, 10);
+        assert_eq!(file.meta.modified.unwrap().timestamp(), 1_600_000_000);
+        file.seek(SeekFrom::Start(5)).unwrap();
+        let mut buf = [0u8; 5];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"shell");
+        assert_eq!(file.write(b"x"), Err(FsError::ReadOnly));
+
+        assert_eq!(fs.open_file("/APP").unwrap_err(), FsError::NotAFile);
+        assert_eq!(
+            fs.open_file("/dev/console").unwrap_err(),
+            FsError::FileNotFound
+        );
+        assert!(fs.read_dir("/etc/motd").is_err());
+        assert_eq!(fs.create_file("/new").unwrap_err(), FsError::NotSupported);
+    }
+
+    #[test]
+    fn test_bad_archive() {
+        assert!(CpioFs::new(b"070707 not newc").is_err());
+
+        // cut off in the middle of a member
+        let archive = archive();
+        assert!(CpioFs::new(&archive[..200]).is_err());
+
+        // no trailer, with and without the padding of the last member
+        let mut archive = Vec::new();
+        member(&mut archive, "APP/SH", S_IFREG | 0o755, b"\x7fELF shell");
+        let archive = Vec::leak(archive);
+        assert_eq!(CpioFs::new(archive).err(), Some(FsError::EndOfFile));
+        assert_eq!(
+            CpioFs::new(&archive[..archive.len() - 2]).err(),
+            Some(FsError::EndOfFile)
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Device filesystem
+//!
+//! A flat directory of device nodes. Drivers register their nodes when they
+//! initialize, every `open_file` asks the node for a fresh file.
+
+use alloc::collections::BTreeMap;
+
+use spin::RwLock;
+
+use crate::*;
+
+/// A device that can be opened through devfs
+pub trait DeviceNode: Send + Sync {
+    /// Opens a new file on the device
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>>;
+
+    /// Size of the device in bytes, 0 for character devices
+    fn size(&self) -> usize {
+        0
+    }
+}
+
+/// Handle to the registered device nodes, clones share the same nodes
+#[derive(Clone, Default)]
+pub struct DevFs {
+    nodes: Arc<RwLock<BTreeMap<String, Arc<dyn DeviceNode>>>>,
+}
+
+impl DevFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device node called `name`
+    pub fn register(&self, name: &str, node: impl DeviceNode + 'static) -> FsResult {
+        if name.is_empty() || name.contains(PATH_SEPARATOR) {
+            return Err(FsError::InvalidPath(name.to_owned()));
+        }
+
+        let mut nodes = self.nodes.write();
+        if nodes.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        nodes.insert(name.to_owned(), Arc::new(node));
+        Ok(())
+    }
+
+    /// Removes a device node, files already opened on it stay usable
+    pub fn unregister(&self, name: &str) -> FsResult {
+        self.nodes
+            .write()
+            .remove(name)
+            .map(|_| ())
+            .ok_or(FsError::FileNotFound)
+    }
+
+    fn node<'a>(&self, path: &'a str) -> FsResult<(&'a str, Arc<dyn DeviceNode>)> {
+        let name = path.trim_start_matches(PATH_SEPARATOR);
+
+        if name.contains(PATH_SEPARATOR) {
+            return Err(FsError::FileNotFound);
+        }
+
+        self.nodes
+            .read()
+            .get(name)
+            .cloned()
+            .map(|node| (name, node))
+            .ok_or(FsError::FileNotFound)
+    }
+}
+
+fn node_meta(name: &str, node: &dyn DeviceNode) -> Metadata {
+    Metadata::new(
+        name.to_owned(),
+        FileType::File,
+        node.size(),
+        None,
+        None,
+        None,
+    )
+}
+
+fn is_root(path: &str) -> bool {
+    path.trim_matches(PATH_SEPARATOR).is_empty()
+}
+
+impl FileSystem for DevFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        if !is_root(path) {
+            return match self.node(path) {
+                Ok(_) => Err(FsError::NotADirectory),
+                Err(e) => Err(e),
+            };
+        }
+
+        let entries: Vec<_> = self
+            .nodes
+            .read()
+            .iter()
+            .map(|(name, node)| node_meta(name, node.as_ref()))
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        if is_root(path) {
+            return Err(FsError::NotAFile);
+        }
+
+        let (name, node) = self.node(path)?;
+        Ok(FileHandle::new(
+            node_meta(name, node.as_ref()),
+            node.open()?,
+        ))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        if is_root(path) {
+            return Ok(Metadata::new(
+                String::new(),
+                FileType::Directory,
+                0,
+                None,
+                None,
+                None,
+            ));
+        }
+
+        let (name, node) = self.node(path)?;
+        Ok(node_meta(name, node.as_ref()))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        Ok(is_root(path) || self.node(path).is_ok())
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        match self.node(path) {
+            Ok(_) => Err(FsError::AlreadyExists),
+            Err(_) => Err(FsError::NotSupported),
+        }
+    }
+
+    /// Devices have no end to append to, this opens them as usual
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.open_file(path)
+    }
+}
+
+impl core::fmt::Debug for DevFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.nodes.read().keys()).finish()
+    }
+}
+
+/// Reads nothing and discards everything written
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Null;
+
+impl Read for Null {
+    fn read(&mut self, _buf: &mut [u8]) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Null {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for Null {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl DeviceNode for Null {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        Ok(Box::new(Null))
+    }
+}
+
+/// Reads zeros and discards everything written
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zero;
+
+impl Read for Zero {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+}
+
+impl Write for Zero {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for Zero {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl DeviceNode for Zero {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        Ok(Box::new(Zero))
+    }
+}
+
+/// A block device accessed as a file of bytes
+///
+/// Writes that do not cover a whole block read the block first.
+pub struct BlockFile<B: BlockTrait> {
+    device: Arc<dyn BlockDevice<B>>,
+    offset: usize,
+    read_only: bool,
+}
+
+impl<B: BlockTrait> BlockFile<B> {
+    pub fn new(device: impl BlockDevice<B>) -> Self {
+        Self {
+            device: Arc::new(device),
+            offset: 0,
+            read_only: false,
+        }
+    }
+
+    /// A file that refuses writes, for a device a mounted filesystem owns
+    pub fn read_only(device: impl BlockDevice<B>) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(device)
+        }
+    }
+
+    fn len(&self) -> FsResult<usize> {
+        Ok(self.device.block_count()? * B::size())
+    }
+}
+
+impl<B: BlockTrait> Clone for BlockFile<B> {
+    fn clone(&self) -> Self {
+        Self {
+            device: self.device.clone(),
+            offset: self.offset,
+            read_only: self.read_only,
+        }
+    }
+}
+
+impl<B: BlockTrait> Read for BlockFile<B> {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let len = self.len()?;
+        let mut block = B::default();
+        let mut read = 0;
+
+        while read < buf.len() && self.offset < len {
+            let start = self.offset % B::size();
+            let count = (B::size() - start)
+                .min(buf.len() - read)
+                .min(len - self.offset);
+
+            self.device
+                .read_block(self.offset / B::size(), &mut block)?;
+            buf[read..read + count].copy_from_slice(&block.as_ref()[start..start + count]);
+
+            read += count;
+            self.offset += count;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<B: BlockTrait> Write for BlockFile<B> {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        if self.read_only {
+            return Err(FsError::ReadOnly);
+        }
+
+        let len = self.len()?;
+        let mut block = B::default();
+        let mut written = 0;
+
+        while written < buf.len() && self.offset < len {
+            let index = self.offset / B::size();
+            let start = self.offset % B::size();
+            let count = (B::size() - start).min(buf.len() - written);
+
+            if count != B::size() {
+                self.device.read_block(index, &mut block)?;
+            }
+
+            block.as_mut()[start..start + count].copy_from_slice(&buf[written..written + count]);
+            self.device.write_block(index, &block)?;
+
+            written += count;
+            self.offset += count;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        self.device.flush()
+    }
+}
+
+impl<B: BlockTrait> Seek for BlockFile<B> {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len()?.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        self.offset = offset.ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
+
+impl<B: BlockTrait> DeviceNode for BlockFile<B> {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        let mut file = self.clone();
+        file.offset = 0;
+        Ok(Box::new(file))
+    }
+
+    fn size(&self) -> usize {
+        self.len().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devfs() {
+        let devfs = DevFs::new();
+        let disk = RamDisk::new(4 * 512);
+
+        devfs.register("null", Null).unwrap();
+        devfs.register("zero", Zero).unwrap();
+        devfs
+            .register("disk", BlockFile::<Block512>::new(disk.clone()))
+            .unwrap();
+        assert_eq!(devfs.register("null", Null), Err(FsError::AlreadyExists));
+        assert!(devfs.register("a/b", Null).is_err());
+
+        let names: Vec<_> = devfs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, ["disk", "null", "zero"]);
+        assert_eq!(devfs.metadata("/disk").unwrap().len, 2048);
+        assert_eq!(devfs.open_file("/tty").unwrap_err(), FsError::FileNotFound);
+
+        let mut buf = [1u8; 8];
+        assert_eq!(devfs.open_file("/null").unwrap().read(&mut buf), Ok(0));
+        assert_eq!(devfs.open_file("/zero").unwrap().read(&mut buf), Ok(8));
+        assert_eq!(buf, [0; 8]);
+
+        // an unaligned write keeps the rest of the blocks
+        let mut file = devfs.open_file("/disk").unwrap();
+        file.seek(SeekFrom::Start(510)).unwrap();
+        file.write_all(&[0xaa; 4]).unwrap();
+        let data = disk.to_vec();
+        assert_eq!(&data[508..516], &[0, 0, 0xaa, 0xaa, 0xaa, 0xaa, 0, 0]);
+
+        file.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(file.write(&[0xbb; 4]), Ok(2));
+        let mut all = Vec::new();
+        devfs
+            .open_file("/disk")
+            .unwrap()
+            .read_all(&mut all)
+            .unwrap();
+        assert_eq!(all, disk.to_vec());
+        assert_eq!(&all[2046..], &[0xbb, 0xbb]);
+
+        devfs.unregister("disk").unwrap();
+        assert!(!devfs.exists("/disk").unwrap());
+
+        devfs
+            .register("disk", BlockFile::<Block512>::read_only(disk.clone()))
+            .unwrap();
+        let mut file = devfs.open_file("/disk").unwrap();
+        assert_eq!(file.write(&[0xcc; 4]), Err(FsError::ReadOnly));
+        assert_eq!(file.read(&mut buf), Ok(8));
+        assert_eq!(disk.to_vec()[..8], buf);
+    }
+}
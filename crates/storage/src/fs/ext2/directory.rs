@@ -0,0 +1,115 @@
+//! Ext2 linked list directories
+//!
+//! reference:
+//! - <https://www.nongnu.org/ext2-doc/ext2.html#linked-directories>
+//!
+//! A directory block is a chain of entries, each giving the length of the
+//! record to skip to reach the next one. The last record of a block spans
+//! to its end, a removed entry is merged into the one before it or has its
+//! inode set to 0.
+
+/// The fixed part of an entry: inode, record length and name length
+pub const HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry<'a> {
+    pub inode: u32,
+    /// Bytes to the next entry
+    pub rec_len: usize,
+    pub name: &'a [u8],
+}
+
+impl<'a> DirEntry<'a> {
+    /// Parse the entry at the start of `data`, `filetype` tells whether the
+    /// name length is one byte followed by the file type
+    pub fn parse(data: &'a [u8], filetype: bool) -> Option<DirEntry<'a>> {
+        let header = data.get(..HEADER_LEN)?;
+        let rec_len = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let name_len = if filetype {
+            header[6] as usize
+        } else {
+            u16::from_le_bytes([header[6], header[7]]) as usize
+        };
+
+        if rec_len < HEADER_LEN + name_len || rec_len > data.len() {
+            return None;
+        }
+
+        Some(DirEntry {
+            inode: u32::from_le_bytes(header[..4].try_into().unwrap()),
+            rec_len,
+            name: &data[HEADER_LEN..HEADER_LEN + name_len],
+        })
+    }
+}
+
+/// The entries of one directory block, in order, removed ones skipped
+pub struct DirEntries<'a> {
+    data: &'a [u8],
+    filetype: bool,
+}
+
+impl<'a> DirEntries<'a> {
+    pub fn new(data: &'a [u8], filetype: bool) -> Self {
+        Self { data, filetype }
+    }
+}
+
+impl<'a> Iterator for DirEntries<'a> {
+    type Item = DirEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // a broken record ends the block
+            let entry = DirEntry::parse(self.data, self.filetype)?;
+            self.data = &self.data[entry.rec_len..];
+
+            if entry.inode != 0 {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(inode: u32, rec_len: u16, name: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&inode.to_le_bytes());
+        data.extend_from_slice(&rec_len.to_le_bytes());
+        data.push(name.len() as u8);
+        data.push(1);
+        data.extend_from_slice(name);
+        data.resize(rec_len as usize, 0);
+        data
+    }
+
+    #[test]
+    fn test_dir_entries() {
+        let mut block = Vec::new();
+        block.extend(entry(2, 12, b"."));
+        block.extend(entry(2, 12, b".."));
+        block.extend(entry(0, 16, b"gone"));
+        block.extend(entry(12, 24, b"hello.txt"));
+        block.extend(entry(13, 1024 - 64, b"last"));
+
+        let names: Vec<_> = DirEntries::new(&block, true)
+            .map(|entry| (entry.inode, entry.name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (2, &b"."[..]),
+                (2, b".."),
+                (12, b"hello.txt"),
+                (13, b"last")
+            ]
+        );
+
+        // the record claims more than the block holds
+        let broken = entry(12, 24, b"hello.txt");
+        assert_eq!(DirEntries::new(&broken[..16], true).count(), 0);
+    }
+}
@@ -0,0 +1,65 @@
+//! File
+//!
+//! The blocks of a file are found through its inode, see
+//! [`Ext2Impl::block_of`].
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct File {
+    /// The current offset in the file.
+    offset: usize,
+    /// Inode of this file
+    inode: Inode,
+    /// The file system handle that contains this file.
+    handle: Ext2Handle,
+}
+
+impl File {
+    pub fn new(handle: Ext2Handle, inode: Inode) -> Self {
+        Self {
+            offset: 0,
+            inode,
+            handle,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.inode.size()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let to_read = buf.len().min(self.length().saturating_sub(self.offset));
+
+        self.handle
+            .read_data(&self.inode, self.offset, &mut buf[..to_read])?;
+        self.offset += to_read;
+
+        Ok(to_read)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        self.offset = offset.ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
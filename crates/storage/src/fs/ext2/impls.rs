@@ -0,0 +1,304 @@
+use core::ops::ControlFlow;
+
+use super::{directory::DirEntries, inode::DIRECT_BLOCKS, *};
+
+/// Symlinks followed while resolving one path, against loops
+const MAX_SYMLINKS: usize = 8;
+
+impl Ext2Impl {
+    pub fn new(inner: impl BlockDevice<Block512>) -> FsResult<Self> {
+        let superblock = Superblock::read(&inner)?;
+
+        trace!("Loading Ext2 Volume: {:#?}", superblock);
+
+        let unsupported = superblock.unsupported_features();
+        if unsupported != 0 {
+            warn!("Unsupported ext2 features: {:#x}", unsupported);
+            return Err(FsError::NotSupported);
+        }
+
+        let block_size = superblock.block_size();
+
+        // the descriptors start in the block after the superblock
+        let table = (superblock.first_data_block() as usize + 1) * block_size;
+        let mut data = vec![0u8; superblock.group_count() * GroupDesc::LEN];
+        read_at(&inner, table, &mut data)?;
+
+        let groups = data.chunks(GroupDesc::LEN).map(GroupDesc::new).collect();
+
+        Ok(Self {
+            inner: Box::new(inner),
+            superblock,
+            groups,
+            block_size,
+        })
+    }
+
+    /// Read bytes of the volume, `offset` bytes into block `block`
+    fn read_block_at(&self, block: u32, offset: usize, buf: &mut [u8]) -> FsResult {
+        read_at(
+            self.inner.as_ref(),
+            block as usize * self.block_size + offset,
+            buf,
+        )
+    }
+
+    pub fn read_inode(&self, ino: u32) -> FsResult<Inode> {
+        if ino == 0 || ino > self.superblock.inodes_count() {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let per_group = self.superblock.inodes_per_group();
+        let group = &self.groups[((ino - 1) / per_group) as usize];
+        let index = ((ino - 1) % per_group) as usize;
+
+        let mut data = [0u8; Inode::LEN];
+        self.read_block_at(
+            group.inode_table(),
+            index * self.superblock.inode_size(),
+            &mut data,
+        )?;
+
+        Ok(Inode::new(&data))
+    }
+
+    /// The volume block holding block `index` of the inode, 0 for a hole
+    pub fn block_of(&self, inode: &Inode, index: usize) -> FsResult<u32> {
+        if index < DIRECT_BLOCKS {
+            return Ok(inode.block(index));
+        }
+
+        let per_block = self.block_size / 4;
+        let mut index = index - DIRECT_BLOCKS;
+        let mut span = 1;
+
+        // single, double and triple indirect, each covers `per_block` times
+        // as many blocks as the one before
+        for level in 0..3 {
+            span *= per_block;
+            if index < span {
+                return self.walk_indirect(inode.block(DIRECT_BLOCKS + level), index, span);
+            }
+            index -= span;
+        }
+
+        Err(FsError::EndOfFile)
+    }
+
+    /// Follow an indirect block that covers `span` blocks down to block
+    /// `index` under it
+    fn walk_indirect(&self, mut block: u32, mut index: usize, mut span: usize) -> FsResult<u32> {
+        let per_block = self.block_size / 4;
+
+        while span > 1 && block != 0 {
+            span /= per_block;
+
+            let mut pointer = [0u8; 4];
+            self.read_block_at(block, index / span * 4, &mut pointer)?;
+
+            block = u32::from_le_bytes(pointer);
+            index %= span;
+        }
+
+        Ok(block)
+    }
+
+    /// Read from the data of an inode, holes read as zeros
+    pub fn read_data(&self, inode: &Inode, offset: usize, buf: &mut [u8]) -> FsResult {
+        let mut done = 0;
+
+        while done < buf.len() {
+            let position = offset + done;
+            let in_block = position % self.block_size;
+            let len = (self.block_size - in_block).min(buf.len() - done);
+            let chunk = &mut buf[done..done + len];
+
+            match self.block_of(inode, position / self.block_size)? {
+                0 => chunk.fill(0),
+                block => self.read_block_at(block, in_block, chunk)?,
+            }
+
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Walk the entries of a directory, "." and ".." included, until `func`
+    /// breaks
+    pub(super) fn walk_dir<T, F>(&self, dir: &Inode, mut func: F) -> FsResult<Option<T>>
+    where
+        F: FnMut(&[u8], u32) -> ControlFlow<T>,
+    {
+        let filetype = self.superblock.has_filetype();
+        let mut block = vec![0u8; self.block_size];
+
+        for index in 0..dir.size().div_ceil(self.block_size) {
+            self.read_data(dir, index * self.block_size, &mut block)?;
+
+            for entry in DirEntries::new(&block, filetype) {
+                if let ControlFlow::Break(ret) = func(entry.name, entry.inode) {
+                    return Ok(Some(ret));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Get the inode number of an entry from the given directory
+    fn find_entry(&self, dir: &Inode, name: &str) -> FsResult<u32> {
+        if !dir.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+
+        self.walk_dir(dir, |entry, ino| {
+            if entry == name.as_bytes() {
+                ControlFlow::Break(ino)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?
+        .ok_or(FsError::FileNotFound)
+    }
+
+    /// Target of a symlink
+    pub fn read_link(&self, inode: &Inode) -> FsResult<String> {
+        if !inode.is_symlink() {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let target = if inode.is_fast_symlink(self.block_size) {
+            inode.inline_target().to_vec()
+        } else {
+            let mut target = vec![0u8; inode.size()];
+            self.read_data(inode, 0, &mut target)?;
+            target
+        };
+
+        String::from_utf8(target).map_err(|_| FilenameError::Utf8Error.into())
+    }
+
+    /// Resolve a path to its inode number and inode, symlinks are followed
+    /// on the way and, if `follow` is set, at the end
+    ///
+    /// Absolute targets start over from the root of this volume.
+    pub(super) fn lookup(&self, path: &str, follow: bool) -> FsResult<(u32, Inode)> {
+        let components = |path: &str| -> Vec<String> {
+            path.split(PATH_SEPARATOR)
+                .filter(|name| !name.is_empty())
+                .rev()
+                .map(String::from)
+                .collect()
+        };
+
+        let root = (ROOT_INODE, self.read_inode(ROOT_INODE)?);
+        let mut pending = components(path);
+        let mut current = root.clone();
+        let mut links = 0;
+
+        while let Some(name) = pending.pop() {
+            let ino = self.find_entry(&current.1, &name)?;
+            let inode = self.read_inode(ino)?;
+
+            if inode.is_symlink() && (follow || !pending.is_empty()) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return Err(FsError::InvalidPath(path.into()));
+                }
+
+                let target = self.read_link(&inode)?;
+                if target.starts_with(PATH_SEPARATOR) {
+                    current = root.clone();
+                }
+
+                // the target is resolved from the directory of the link
+                pending.extend(components(&target));
+                continue;
+            }
+
+            current = (ino, inode);
+        }
+
+        Ok(current)
+    }
+}
+
+/// The last component of a path, empty for the root
+fn name_of(path: &str) -> &str {
+    path.trim_end_matches(PATH_SEPARATOR)
+        .rsplit(PATH_SEPARATOR)
+        .next()
+        .unwrap_or_default()
+}
+
+impl Ext2 {
+    /// Target of the symlink at `path`
+    pub fn read_link(&self, path: &str) -> FsResult<String> {
+        let (_, inode) = self.handle.lookup(path, false)?;
+        self.handle.read_link(&inode)
+    }
+}
+
+impl FileSystem for Ext2 {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let (_, dir) = self.handle.lookup(path, true)?;
+
+        if !dir.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+
+        let mut entries = Vec::new();
+        self.handle.walk_dir(&dir, |name, ino| {
+            entries.push((String::from_utf8_lossy(name).into_owned(), ino));
+            ControlFlow::<()>::Continue(())
+        })?;
+
+        let metas: Vec<Metadata> = entries
+            .into_iter()
+            .filter_map(|(name, ino)| match self.handle.read_inode(ino) {
+                Ok(inode) => Some(inode.as_meta(name, ino)),
+                Err(e) => {
+                    warn!("Failed to read inode {}: {:?}", ino, e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Box::new(metas.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (ino, inode) = self.handle.lookup(path, true)?;
+
+        if inode.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        let meta = inode.as_meta(name_of(path).into(), ino);
+        let file = Box::new(File::new(self.handle.clone(), inode));
+
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let (ino, inode) = self.handle.lookup(path, true)?;
+        Ok(inode.as_meta(name_of(path).into(), ino))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        Ok(self.handle.lookup(path, true).is_ok())
+    }
+
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        let superblock = &self.handle.superblock;
+
+        Ok(FsStat {
+            block_size: self.handle.block_size,
+            total_blocks: (superblock.blocks_count() - superblock.first_data_block()) as usize,
+            free_blocks: superblock.free_blocks_count() as usize,
+            max_name_len: 255,
+            read_only: true,
+        })
+    }
+}
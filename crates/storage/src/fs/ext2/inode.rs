@@ -0,0 +1,137 @@
+//! Ext2 inode
+//!
+//! reference:
+//! - <https://www.nongnu.org/ext2-doc/ext2.html#inode-table>
+//! - <https://wiki.osdev.org/Ext2#Inodes>
+
+use chrono::DateTime;
+
+use super::*;
+
+/// Direct block pointers, followed by the single, double and triple
+/// indirect ones
+pub const DIRECT_BLOCKS: usize = 12;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFLNK: u16 = 0o120000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+
+#[derive(Clone)]
+pub struct Inode {
+    data: Vec<u8>,
+}
+
+impl Inode {
+    /// Fields past the first 128 bytes are not used
+    pub const LEN: usize = 128;
+
+    pub fn new(data: &[u8]) -> Inode {
+        Inode {
+            data: data[..Self::LEN].to_vec(),
+        }
+    }
+
+    pub fn kind(&self) -> FileType {
+        match self.mode() & S_IFMT {
+            S_IFDIR => FileType::Directory,
+            S_IFLNK => FileType::Symlink,
+            // devices, pipes and sockets read as empty files
+            _ => FileType::File,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind() == FileType::Directory
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind() == FileType::Symlink
+    }
+
+    /// Length in bytes, regular files keep the upper half in what used to
+    /// be the directory ACL
+    pub fn size(&self) -> usize {
+        let high = if self.mode() & S_IFMT == S_IFREG {
+            self.size_high() as usize
+        } else {
+            0
+        };
+        (high << 32) | self.size_low() as usize
+    }
+
+    pub fn uid(&self) -> u32 {
+        (self.uid_high() as u32) << 16 | self.uid_low() as u32
+    }
+
+    pub fn gid(&self) -> u32 {
+        (self.gid_high() as u32) << 16 | self.gid_low() as u32
+    }
+
+    /// The `index`-th of the 15 block pointers
+    pub fn block(&self, index: usize) -> u32 {
+        let offset = 0x28 + index * 4;
+        u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// A symlink whose target is kept in place of the block pointers
+    pub fn is_fast_symlink(&self, block_size: usize) -> bool {
+        // the extended attribute block is counted in `sectors`
+        let xattr = if self.file_acl() != 0 {
+            block_size / Block512::BLOCK_SIZE
+        } else {
+            0
+        };
+        self.is_symlink() && self.sectors() as usize == xattr
+    }
+
+    /// The target of a fast symlink
+    pub fn inline_target(&self) -> &[u8] {
+        let len = self.size().min(60);
+        &self.data[0x28..0x28 + len]
+    }
+
+    pub fn as_meta(&self, name: String, ino: u32) -> Metadata {
+        let time = |secs: u32| DateTime::from_timestamp(secs as i64, 0);
+        let len = if self.is_dir() { 0 } else { self.size() };
+
+        Metadata::new(
+            name,
+            self.kind(),
+            len,
+            None,
+            time(self.mtime()),
+            time(self.atime()),
+        )
+        .with_inode(ino as usize, self.mode(), self.uid(), self.gid())
+    }
+
+    define_field!(u16, 0x00, mode);
+    define_field!(u16, 0x02, uid_low);
+    define_field!(u32, 0x04, size_low);
+    define_field!(u32, 0x08, atime);
+    define_field!(u32, 0x0c, ctime);
+    define_field!(u32, 0x10, mtime);
+    define_field!(u32, 0x14, dtime);
+    define_field!(u16, 0x18, gid_low);
+    define_field!(u16, 0x1a, links_count);
+    define_field!(u32, 0x1c, sectors);
+    define_field!(u32, 0x20, flags);
+    define_field!(u32, 0x68, file_acl);
+    define_field!(u32, 0x6c, size_high);
+    define_field!(u16, 0x78, uid_high);
+    define_field!(u16, 0x7a, gid_high);
+}
+
+impl core::fmt::Debug for Inode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Inode")
+            .field("Mode", &format_args!("{:o}", self.mode()))
+            .field("Uid", &self.uid())
+            .field("Gid", &self.gid())
+            .field("Size", &self.size())
+            .field("Links Count", &self.links_count())
+            .field("Sectors", &self.sectors())
+            .finish()
+    }
+}
@@ -0,0 +1,102 @@
+//! Ext2 splits the volume into block groups, each with its own bitmaps and
+//! slice of the inode table. Files are inodes, directories map names to
+//! inode numbers, so one inode can have several names (hard links).
+//!
+//! ```text
+//! [ Boot | Superblock ] [ Group 0 ] [ Group 1 ] ... [ Group N ]
+//!
+//! Group: [ (Superblock) | (Descriptors) | Block Bitmap | Inode Bitmap |
+//!          Inode Table | Data ]
+//! ```
+//!
+//!   - The superblock is always at byte 1024, the group descriptors follow it
+//!     in the next block.
+//!   - An inode points to its data through 12 direct blocks, then a single, a
+//!     double and a triple indirect block.
+//!   - The root directory is inode 2.
+//!
+//! This driver only reads. Volumes using features that change the layout,
+//! like extents or 64 bit block numbers, are refused.
+
+pub mod directory;
+pub mod file;
+pub mod impls;
+pub mod inode;
+pub mod superblock;
+
+use file::File;
+use inode::Inode;
+use superblock::{GroupDesc, Superblock};
+
+use crate::*;
+
+/// Inode of the root directory
+const ROOT_INODE: u32 = 2;
+
+/// Identifies an ext2 volume on the disk.
+#[derive(Clone)]
+pub struct Ext2 {
+    handle: Ext2Handle,
+}
+
+impl Ext2 {
+    pub fn new(inner: impl BlockDevice<Block512>) -> FsResult<Self> {
+        Ok(Self {
+            handle: Arc::new(Ext2Impl::new(inner)?),
+        })
+    }
+
+    /// Whether the device holds an ext2 volume this driver can read, ext3
+    /// and ext4 volumes using features it does not know are left out
+    pub fn detect(inner: &impl BlockDevice<Block512>) -> bool {
+        Superblock::read(inner).is_ok_and(|superblock| superblock.unsupported_features() == 0)
+    }
+}
+
+type Ext2Handle = Arc<Ext2Impl>;
+
+pub struct Ext2Impl {
+    pub(crate) inner: Box<dyn BlockDevice<Block512>>,
+    pub superblock: Superblock,
+    pub groups: Vec<GroupDesc>,
+    /// Bytes in one block
+    pub block_size: usize,
+}
+
+impl core::fmt::Debug for Ext2 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Ext2")
+            .field("superblock", &self.handle.superblock)
+            .finish()
+    }
+}
+
+impl core::fmt::Debug for Ext2Impl {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Ext2Impl")
+            .field("superblock", &self.superblock)
+            .finish()
+    }
+}
+
+/// Read `buf.len()` bytes from byte `offset` of the volume
+fn read_at<D>(inner: &D, offset: usize, buf: &mut [u8]) -> FsResult
+where
+    D: BlockDevice<Block512> + ?Sized,
+{
+    let size = Block512::BLOCK_SIZE;
+    let first = offset / size;
+    let mut sectors = vec![Block512::default(); (offset + buf.len()).div_ceil(size) - first];
+    inner.read_blocks(first, &mut sectors)?;
+
+    let skip = offset % size;
+    let mut copied = 0;
+    for (index, sector) in sectors.iter().enumerate() {
+        let data = &sector[if index == 0 { skip } else { 0 }..];
+        let len = data.len().min(buf.len() - copied);
+        buf[copied..copied + len].copy_from_slice(&data[..len]);
+        copied += len;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Ext2 superblock and block group descriptors
+//!
+//! reference:
+//! - <https://www.nongnu.org/ext2-doc/ext2.html#superblock>
+//! - <https://wiki.osdev.org/Ext2#Superblock>
+
+use super::*;
+
+/// The superblock, 1024 bytes at byte 1024 of the volume
+pub struct Superblock {
+    data: [u8; 1024],
+}
+
+impl Superblock {
+    pub const OFFSET: usize = 1024;
+    pub const MAGIC: u16 = 0xEF53;
+
+    /// Directory entries record the file type
+    pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+    /// The journal has to be replayed first
+    pub const INCOMPAT_RECOVER: u32 = 0x0004;
+    /// Groups share their metadata, which only moves it around
+    pub const INCOMPAT_FLEX_BG: u32 = 0x0200;
+    /// What this driver reads, any other incompatible feature is refused
+    pub const INCOMPAT_SUPPORTED: u32 = Self::INCOMPAT_FILETYPE | Self::INCOMPAT_FLEX_BG;
+
+    /// Attempt to parse a superblock from its 1024 bytes.
+    pub fn new(data: &[u8]) -> Result<Superblock, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad superblock size")?;
+        let superblock = Superblock { data };
+
+        if superblock.magic() != Self::MAGIC {
+            return Err("Bad superblock magic");
+        }
+
+        if superblock.log_block_size() > 6 || superblock.inodes_per_group() == 0 {
+            return Err("Bad superblock geometry");
+        }
+
+        Ok(superblock)
+    }
+
+    /// Read and parse the superblock of a volume
+    pub fn read(inner: &(impl BlockDevice<Block512> + ?Sized)) -> FsResult<Superblock> {
+        let mut data = [0u8; 1024];
+        read_at(inner, Self::OFFSET, &mut data)?;
+        Superblock::new(&data).map_err(|_| FsError::InvalidVolume)
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << self.log_block_size()
+    }
+
+    /// Revision 0 has fixed 128 byte inodes
+    pub fn inode_size(&self) -> usize {
+        match self.rev_level() {
+            0 => 128,
+            _ => self.inode_size_field() as usize,
+        }
+    }
+
+    pub fn group_count(&self) -> usize {
+        (self.inodes_count() as usize).div_ceil(self.inodes_per_group() as usize)
+    }
+
+    /// Incompatible features in use that this driver does not read, like
+    /// extents or a journal to replay
+    pub fn unsupported_features(&self) -> u32 {
+        self.feature_incompat() & !Self::INCOMPAT_SUPPORTED
+    }
+
+    /// Whether directory entries carry the file type instead of a 16 bit
+    /// name length
+    pub fn has_filetype(&self) -> bool {
+        self.feature_incompat() & Self::INCOMPAT_FILETYPE != 0
+    }
+
+    define_field!(u32, 0x00, inodes_count);
+    define_field!(u32, 0x04, blocks_count);
+    define_field!(u32, 0x0c, free_blocks_count);
+    define_field!(u32, 0x10, free_inodes_count);
+    define_field!(u32, 0x14, first_data_block);
+    define_field!(u32, 0x18, log_block_size);
+    define_field!(u32, 0x20, blocks_per_group);
+    define_field!(u32, 0x28, inodes_per_group);
+    define_field!(u32, 0x2c, mtime);
+    define_field!(u32, 0x30, wtime);
+    define_field!(u16, 0x38, magic);
+    define_field!(u16, 0x3a, state);
+    define_field!(u32, 0x4c, rev_level);
+    define_field!(u16, 0x58, inode_size_field);
+    define_field!(u32, 0x5c, feature_compat);
+    define_field!(u32, 0x60, feature_incompat);
+    define_field!(u32, 0x64, feature_ro_compat);
+    define_field!([u8; 16], 0x78, volume_name);
+}
+
+impl core::fmt::Debug for Superblock {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Ext2 Superblock")
+            .field(
+                "Volume Name",
+                &self.volume_name_str().trim_end_matches('\0'),
+            )
+            .field("Inodes Count", &self.inodes_count())
+            .field("Blocks Count", &self.blocks_count())
+            .field("Free Blocks Count", &self.free_blocks_count())
+            .field("Free Inodes Count", &self.free_inodes_count())
+            .field("First Data Block", &self.first_data_block())
+            .field("Block Size", &self.block_size())
+            .field("Blocks per Group", &self.blocks_per_group())
+            .field("Inodes per Group", &self.inodes_per_group())
+            .field("Revision", &self.rev_level())
+            .field("Inode Size", &self.inode_size())
+            .field("Compatible Features", &self.feature_compat())
+            .field("Incompatible Features", &self.feature_incompat())
+            .field("Read-only Features", &self.feature_ro_compat())
+            .finish()
+    }
+}
+
+/// A block group descriptor, 32 bytes each in the block after the
+/// superblock
+pub struct GroupDesc {
+    data: [u8; GroupDesc::LEN],
+}
+
+impl GroupDesc {
+    pub const LEN: usize = 32;
+
+    pub fn new(data: &[u8]) -> GroupDesc {
+        GroupDesc {
+            data: data.try_into().unwrap(),
+        }
+    }
+
+    define_field!(u32, 0x00, block_bitmap);
+    define_field!(u32, 0x04, inode_bitmap);
+    define_field!(u32, 0x08, inode_table);
+    define_field!(u16, 0x0c, free_blocks_count);
+    define_field!(u16, 0x0e, free_inodes_count);
+    define_field!(u16, 0x10, used_dirs_count);
+}
+
+impl core::fmt::Debug for GroupDesc {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GroupDesc")
+            .field("Block Bitmap", &self.block_bitmap())
+            .field("Inode Bitmap", &self.inode_bitmap())
+            .field("Inode Table", &self.inode_table())
+            .field("Free Blocks Count", &self.free_blocks_count())
+            .field("Free Inodes Count", &self.free_inodes_count())
+            .finish()
+    }
+}
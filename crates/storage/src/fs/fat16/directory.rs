@@ -4,7 +4,7 @@
 //! - <https://wiki.osdev.org/FAT#Directories_on_FAT12.2F16.2F32>
 //! - <https://github.com/rust-embedded-community/embedded-sdmmc-rs/blob/develop/src/filesystem.rs>
 
-use super::*;
+use super::{lfn::LfnBuilder, *};
 
 #[derive(Debug)]
 pub struct Directory {
@@ -32,7 +32,11 @@ impl Directory {
 
     pub fn from_entry(entry: DirEntry) -> Self {
         Directory {
-            cluster: entry.cluster,
+            // ".." of a first level directory points to cluster 0
+            cluster: match entry.cluster {
+                Cluster::EMPTY => Cluster::ROOT_DIR,
+                cluster => cluster,
+            },
             entry: Some(entry),
         }
     }
@@ -47,3 +51,116 @@ impl core::fmt::Display for Directory {
         )
     }
 }
+
+/// The entries of a directory, read as the iterator is consumed
+///
+/// Sectors are read one at a time and the cluster chain is followed when
+/// the end of a cluster is reached. An entry that cannot be parsed is
+/// reported and skipped, a failed read is reported and ends the iteration.
+pub struct DirIter<V: FatVariant> {
+    fs: FatHandle<V>,
+    /// Cluster being read, `None` once the directory is exhausted
+    cluster: Option<Cluster>,
+    /// Next sector to read, relative to the start of the cluster
+    sector: usize,
+    block: Block512,
+    /// Where `block` was read from
+    block_sector: usize,
+    /// Next entry in `block`
+    index: usize,
+    lfn: LfnBuilder,
+}
+
+impl<V: FatVariant> DirIter<V> {
+    const ENTRIES_PER_SECTOR: usize = BLOCK_SIZE / DirEntry::LEN;
+
+    pub(super) fn new(fs: FatHandle<V>, dir: &Directory) -> Self {
+        Self {
+            fs,
+            cluster: Some(dir.cluster),
+            sector: 0,
+            block: Block512::default(),
+            block_sector: 0,
+            index: Self::ENTRIES_PER_SECTOR,
+            lfn: LfnBuilder::new(),
+        }
+    }
+
+    /// Read the next sector of the directory into `block`, returns `false`
+    /// at the end of the chain
+    fn next_sector(&mut self) -> FsResult<bool> {
+        let Some(mut cluster) = self.cluster else {
+            return Ok(false);
+        };
+
+        if self.sector == self.fs.dir_sectors(&cluster) {
+            cluster = match self.fs.next_cluster(&cluster) {
+                Ok(next) => next,
+                Err(FsError::EndOfFile) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            self.cluster = Some(cluster);
+            self.sector = 0;
+        }
+
+        self.block_sector = self.fs.cluster_to_sector(&cluster) + self.sector;
+        self.fs
+            .inner
+            .read_block(self.block_sector, &mut self.block)?;
+        self.sector += 1;
+        self.index = 0;
+
+        Ok(true)
+    }
+}
+
+impl<V: FatVariant> Iterator for DirIter<V> {
+    type Item = FsResult<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.index == Self::ENTRIES_PER_SECTOR {
+                match self.next_sector() {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.cluster = None;
+                        return None;
+                    }
+                    Err(e) => {
+                        self.cluster = None;
+                        return Some(Err(e));
+                    }
+                }
+            }
+
+            let location = EntryLocation::new(self.block_sector, self.index);
+            let data = &self.block[location.range()];
+            self.index += 1;
+
+            let mut entry = match DirEntry::parse(data) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.lfn.reset();
+                    return Some(Err(e));
+                }
+            };
+
+            if entry.is_eod() {
+                self.cluster = None;
+                return None;
+            } else if !entry.is_valid() {
+                self.lfn.reset();
+                continue;
+            } else if entry.is_long_name() {
+                self.lfn.push(data, location);
+                continue;
+            }
+
+            if let Some((name, _)) = self.lfn.finish(&entry.filename) {
+                entry.long_name = Some(name);
+            }
+
+            return Some(Ok(entry));
+        }
+    }
+}
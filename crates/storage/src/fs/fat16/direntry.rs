@@ -24,11 +24,33 @@ pub struct DirEntry {
     pub cluster: Cluster,
     pub attributes: Attributes,
     pub size: u32,
+    /// VFAT long file name, assembled from the preceding LFN entries
+    pub long_name: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Cluster(pub u32);
 
+/// Where a raw directory entry lives on the volume
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EntryLocation {
+    /// The sector holding the entry
+    pub sector: usize,
+    /// The index of the entry within the sector
+    pub index: usize,
+}
+
+impl EntryLocation {
+    pub fn new(sector: usize, index: usize) -> Self {
+        Self { sector, index }
+    }
+
+    /// Byte range of the entry within its sector
+    pub fn range(&self) -> Range<usize> {
+        self.index * DirEntry::LEN..(self.index + 1) * DirEntry::LEN
+    }
+}
+
 bitflags! {
     /// File Attributes
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -39,13 +61,29 @@ bitflags! {
         const VOLUME_ID = 0x08;
         const DIRECTORY = 0x10;
         const ARCHIVE   = 0x20;
-        const LFN       = 0x0f; // Long File Name, Not Implemented
+        const LFN       = 0x0f; // VFAT Long File Name
     }
 }
 
 impl DirEntry {
     pub const LEN: usize = 0x20;
 
+    /// Create an empty entry, timestamps are left at zero as there is no
+    /// clock available to the filesystem
+    pub fn new(filename: ShortFileName, attributes: Attributes) -> Self {
+        let time = prase_datetime(0);
+        Self {
+            filename,
+            moditified_time: time,
+            created_time: time,
+            accessed_time: time,
+            cluster: Cluster::EMPTY,
+            attributes,
+            size: 0,
+            long_name: None,
+        }
+    }
+
     pub fn is_readonly(&self) -> bool {
         self.attributes.contains(Attributes::READ_ONLY)
     }
@@ -87,14 +125,26 @@ impl DirEntry {
     }
 
     pub fn filename(&self) -> String {
-        if self.is_valid() && !self.is_long_name() {
+        if let Some(name) = &self.long_name {
+            name.clone()
+        } else if self.is_valid() && !self.is_long_name() {
             format!("{}", self.filename)
         } else {
             String::from("unknown")
         }
     }
 
-    /// For Standard 8.3 format
+    /// Whether the entry answers to `name`, by its long name (case
+    /// insensitive) or by its short name
+    pub fn matches(&self, name: &str, sfn: Option<&ShortFileName>) -> bool {
+        self.long_name
+            .as_ref()
+            .is_some_and(|long_name| long_name.eq_ignore_ascii_case(name))
+            || sfn.is_some_and(|sfn| self.filename.matches(sfn))
+    }
+
+    /// For Standard 8.3 format, the long name is filled in by the directory
+    /// walker
     pub fn parse(data: &[u8]) -> FsResult<DirEntry> {
         // trace!(
         //     "Parsing file...\n    {:016x} {:016x} {:016x} {:016x}",
@@ -106,11 +156,6 @@ impl DirEntry {
 
         let filename = ShortFileName::new(&data[..11]);
 
-        // TODO: parse long file name
-        // if filename.is_eod() || filename.is_unused() {
-        //     return Err(FilenameError::UnableToParse);
-        // }
-
         let attributes = Attributes::from_bits_truncate(data[11]);
 
         // 12: Reserved. Must be set to zero
@@ -140,9 +185,26 @@ impl DirEntry {
             cluster: Cluster(cluster),
             attributes,
             size,
+            long_name: None,
         })
     }
 
+    /// Write this entry back into a raw 32-byte directory entry
+    ///
+    /// Bytes 12 and 13 (NT reserved & CrtTimeTenth) are left untouched.
+    pub fn write_to(&self, data: &mut [u8]) {
+        data[..8].copy_from_slice(&self.filename.name);
+        data[8..11].copy_from_slice(&self.filename.ext);
+        data[11] = self.attributes.bits();
+
+        data[14..18].copy_from_slice(&pack_datetime(&self.created_time).to_le_bytes());
+        data[18..20].copy_from_slice(&pack_datetime(&self.accessed_time).to_le_bytes()[2..]);
+        data[20..22].copy_from_slice(&((self.cluster.0 >> 16) as u16).to_le_bytes());
+        data[22..26].copy_from_slice(&pack_datetime(&self.moditified_time).to_le_bytes());
+        data[26..28].copy_from_slice(&(self.cluster.0 as u16).to_le_bytes());
+        data[28..32].copy_from_slice(&self.size.to_le_bytes());
+    }
+
     pub fn as_meta(&self) -> Metadata {
         self.into()
     }
@@ -163,6 +225,22 @@ fn prase_datetime(time: u32) -> FsTime {
     }
 }
 
+/// Inverse of `prase_datetime`, times before 1980 are stored as zero
+fn pack_datetime(time: &FsTime) -> u32 {
+    use chrono::{Datelike, Timelike};
+
+    if time.year() < 1980 {
+        return 0;
+    }
+
+    (((time.year() - 1980) as u32) << 25)
+        | (time.month() << 21)
+        | (time.day() << 16)
+        | (time.hour() << 11)
+        | (time.minute() << 5)
+        | (time.second() / 2)
+}
+
 #[derive(PartialEq, Eq, Clone)]
 pub struct ShortFileName {
     pub name: [u8; 8],
@@ -202,6 +280,13 @@ impl ShortFileName {
             name: [0x20; 8],
             ext: [0x20; 3],
         };
+
+        // "." and ".." entries are stored as they are
+        if name == "." || name == ".." {
+            sfn.name[..name.len()].copy_from_slice(name.as_bytes());
+            return Ok(sfn);
+        }
+
         let mut idx = 0;
         let mut seen_dot = false;
         for ch in name.bytes() {
@@ -335,6 +420,10 @@ impl From<&DirEntry> for Metadata {
             created: Some(entry.created_time),
             accessed: Some(entry.accessed_time),
             modified: Some(entry.moditified_time),
+            mode: None,
+            uid: None,
+            gid: None,
+            inode: None,
         }
     }
 }
@@ -384,4 +473,26 @@ mod tests {
 
         println!("{:#?}", res);
     }
+
+    #[test]
+    fn test_dir_entry_write_back() {
+        let data = hex_literal::hex!(
+            "4b 45 52 4e 45 4c 20 20 45 4c 46 20 00 00 0f be
+             d0 50 d0 50 00 00 0f be d0 50 02 00 f0 e4 0e 00"
+        );
+
+        let mut entry = DirEntry::parse(&data).unwrap();
+        let mut raw = [0u8; DirEntry::LEN];
+        entry.write_to(&mut raw);
+        assert_eq!(raw, data);
+
+        entry.size = 0x1234;
+        entry.cluster = Cluster(0x42);
+        entry.write_to(&mut raw);
+
+        let res = DirEntry::parse(&raw).unwrap();
+        assert_eq!(res.size, 0x1234);
+        assert_eq!(res.cluster, Cluster(0x42));
+        assert_eq!(res.created_time, entry.created_time);
+    }
 }
@@ -4,28 +4,44 @@
 //! - <https://wiki.osdev.org/FAT#Directories_on_FAT12.2F16.2F32>
 //! - <https://github.com/rust-embedded-community/embedded-sdmmc-rs/blob/develop/src/filesystem.rs>
 
-use x86_64::registers::debug;
-
 use super::*;
 
-#[derive(Debug, Clone)]
-pub struct File {
+/// The size in a directory entry is 32 bits
+const MAX_FILE_SIZE: usize = u32::MAX as usize;
+
+#[derive(Debug)]
+pub struct File<V: FatVariant> {
     /// The current offset in the file.
     offset: usize,
     /// DirEntry of this file
     entry: DirEntry,
-    /// The current cluster of this file
-    current_cluster: Cluster,
+    /// Where the DirEntry of this file is stored
+    location: EntryLocation,
+    /// The known prefix of the cluster chain, indexed by cluster number in
+    /// the file
+    clusters: Vec<Cluster>,
+    /// Whether the DirEntry needs to be written back
+    dirty: bool,
     /// The file system handle that contains this file.
-    handle: Fat16Handle,
+    handle: FatHandle<V>,
 }
 
-impl File {
-    pub fn new(handle: Fat16Handle, entry: DirEntry) -> Self {
+impl<V: FatVariant> File<V> {
+    pub fn new(handle: FatHandle<V>, entry: DirEntry, location: EntryLocation) -> Self {
+        let clusters = if entry.cluster == Cluster::EMPTY {
+            Vec::new()
+        } else {
+            vec![entry.cluster]
+        };
+
+        handle.open_file(location);
+
         Self {
             offset: 0,
-            current_cluster: entry.cluster,
+            clusters,
+            dirty: false,
             entry,
+            location,
             handle,
         }
     }
@@ -33,9 +49,108 @@ impl File {
     pub fn length(&self) -> usize {
         self.entry.size as usize
     }
+
+    /// Get the `index`-th cluster of this file.
+    ///
+    /// The chain is walked from the last cached cluster and every cluster seen
+    /// is remembered, so each link is only read from the FAT once. When
+    /// `alloc` is set, new clusters are appended to a chain that is too
+    /// short, otherwise `EndOfFile` is returned.
+    fn cluster_at(&mut self, index: usize, alloc: bool) -> FsResult<Cluster> {
+        if self.clusters.is_empty() {
+            if !alloc {
+                return Err(FsError::EndOfFile);
+            }
+
+            let cluster = self.handle.alloc_cluster(None)?;
+            self.entry.cluster = cluster;
+            self.clusters.push(cluster);
+            self.dirty = true;
+        }
+
+        while self.clusters.len() <= index {
+            let last = *self.clusters.last().unwrap();
+            let next = match self.handle.next_cluster(&last) {
+                Ok(next) => next,
+                Err(FsError::EndOfFile) if alloc => self.handle.alloc_cluster(Some(&last))?,
+                Err(e) => return Err(e),
+            };
+            self.clusters.push(next);
+        }
+
+        Ok(self.clusters[index])
+    }
+
+    /// Write `buf` at the current offset, returns the bytes written before
+    /// the volume ran out of space.
+    fn write_at_offset(&mut self, buf: &[u8]) -> FsResult<usize> {
+        let cluster_size = self.handle.cluster_size();
+
+        let mut block = Block::default();
+        let mut bytes_written = 0;
+
+        while bytes_written < buf.len() {
+            let cluster = match self.cluster_at(self.offset / cluster_size, true) {
+                Ok(cluster) => cluster,
+                // volume is full, report what has been written so far
+                Err(FsError::WriteZero) if bytes_written > 0 => break,
+                Err(e) => return Err(e),
+            };
+
+            let cluster_offset = self.offset % cluster_size;
+            let current_sector =
+                self.handle.cluster_to_sector(&cluster) + cluster_offset / BLOCK_SIZE;
+
+            let current_offset = self.offset % BLOCK_SIZE;
+            let block_remain = BLOCK_SIZE - current_offset;
+            let buf_remain = buf.len() - bytes_written;
+            let to_write = buf_remain.min(block_remain);
+
+            // partial block, keep the rest of the sector
+            if to_write < BLOCK_SIZE {
+                self.handle.inner.read_block(current_sector, &mut block)?;
+            }
+
+            block.as_mut()[current_offset..current_offset + to_write]
+                .copy_from_slice(&buf[bytes_written..bytes_written + to_write]);
+
+            self.handle.inner.write_block(current_sector, &block)?;
+
+            bytes_written += to_write;
+            self.offset += to_write;
+        }
+
+        if bytes_written > 0 {
+            self.entry.attributes |= Attributes::ARCHIVE;
+            self.dirty = true;
+        }
+
+        if self.offset > self.length() {
+            self.entry.size = self.offset as u32;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Fill the hole between the end of file and the current offset with
+    /// zeros, which is left behind by seeking past the end.
+    fn fill_hole(&mut self) -> FsResult {
+        let end = self.offset;
+        let zeros = [0u8; BLOCK_SIZE];
+
+        self.offset = self.length();
+        while self.offset < end {
+            let to_write = (end - self.offset).min(BLOCK_SIZE);
+            if self.write_at_offset(&zeros[..to_write])? < to_write {
+                return Err(FsError::WriteZero);
+            }
+        }
+
+        Ok(())
+    }
 }
 
-impl Read for File {
+impl<V: FatVariant> Read for File<V> {
     fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
         let length = self.length();
 
@@ -43,15 +158,20 @@ impl Read for File {
             return Ok(0);
         }
 
-        let sector_pre_cluster = self.handle.bpb.sectors_per_cluster() as usize;
-        let sector_size = self.handle.bpb.bytes_per_sector() as usize;
-        let cluster_size = sector_pre_cluster * sector_size;
+        let cluster_size = self.handle.cluster_size();
 
         let mut block = Block::default();
         let mut bytes_read = 0;
 
         while bytes_read < buf.len() && self.offset < length {
-            let cluster_sector = self.handle.cluster_to_sector(&self.current_cluster);
+            let cluster = match self.cluster_at(self.offset / cluster_size, false) {
+                Ok(cluster) => cluster,
+                // chain is shorter than the recorded size
+                Err(FsError::EndOfFile) => break,
+                Err(e) => return Err(e),
+            };
+
+            let cluster_sector = self.handle.cluster_to_sector(&cluster);
             let cluster_offset = self.offset % cluster_size;
             let current_sector = cluster_sector + cluster_offset / BLOCK_SIZE;
 
@@ -72,32 +192,105 @@ impl Read for File {
             if to_read < block_remain {
                 break;
             }
-
-            if self.offset.is_multiple_of(cluster_size) {
-                if let Ok(next_cluster) = self.handle.next_cluster(&self.current_cluster) {
-                    self.current_cluster = next_cluster;
-                } else {
-                    break;
-                }
-            }
         }
 
         Ok(bytes_read)
     }
 }
 
-impl Seek for File {
-    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
-        unimplemented!()
+impl<V: FatVariant> Seek for File<V> {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        // the cluster is looked up from the cache on the next access
+        self.offset = offset
+            .filter(|&offset| offset <= MAX_FILE_SIZE)
+            .ok_or(FsError::InvalidOffset)?;
+
+        Ok(self.offset)
     }
 }
 
-impl Write for File {
-    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
-        unimplemented!()
+impl<V: FatVariant> Write for File<V> {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.entry.is_readonly() {
+            return Err(FsError::ReadOnly);
+        }
+
+        // nothing past the largest size the entry can record
+        if self.offset >= MAX_FILE_SIZE {
+            return Err(FsError::WriteZero);
+        }
+        let buf = &buf[..buf.len().min(MAX_FILE_SIZE - self.offset)];
+
+        if self.offset > self.length() {
+            self.fill_hole()?;
+        }
+
+        self.write_at_offset(buf)
     }
 
     fn flush(&mut self) -> FsResult {
-        unimplemented!()
+        if self.dirty {
+            self.handle
+                .transaction(|| self.handle.update_dir_entry(&self.entry, &self.location))?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<V: FatVariant> Drop for File<V> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("Failed to flush file {}: {:?}", self.entry.filename(), e);
+        }
+
+        self.handle.close_file(&self.location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::ControlFlow;
+
+    use super::*;
+
+    #[test]
+    fn test_failed_write() {
+        let fs = Fat16::format(RamDisk::new(8 * 1024 * 1024), &Default::default()).unwrap();
+        fs.create_file("/A.TXT").unwrap();
+
+        let mut fill = fs.create_file("/FILL").unwrap();
+        while fill.write(&[0; 4096]).is_ok() {}
+        drop(fill);
+
+        let (mut entry, location) = fs
+            .handle
+            .walk_entries(&Directory::root(), |entry, location, _| {
+                match entry.filename() == "A.TXT" {
+                    true => ControlFlow::Break((entry, location)),
+                    false => ControlFlow::Continue(()),
+                }
+            })
+            .unwrap()
+            .unwrap();
+        entry.attributes.remove(Attributes::ARCHIVE);
+        fs.handle.update_dir_entry(&entry, &location).unwrap();
+
+        // nothing was written, so the entry is left as it is
+        let mut file = File::new(fs.handle.clone(), entry, location);
+        assert_eq!(file.write(b"x"), Err(FsError::WriteZero));
+        assert!(!file.dirty);
+        assert!(!file.entry.attributes.contains(Attributes::ARCHIVE));
     }
 }
@@ -0,0 +1,522 @@
+//! Check a FAT volume for inconsistencies
+//!
+//! Every directory is walked from the root, and every cluster chain is
+//! followed and marked with its owner. This finds:
+//!
+//!   - FAT copies that differ from the first active one
+//!   - chains that run into a free, bad or out of range cluster, or loop
+//!   - clusters used by two entries (cross-linked)
+//!   - used clusters that no entry refers to (lost chains)
+//!   - files whose size does not match the length of their chain
+//!
+//! When repairing, the first FAT is copied over the others, lost chains are
+//! freed, and chains longer than the file are cut while sizes beyond the
+//! chain are reduced. Bad and cross-linked chains are only reported.
+
+use core::ops::ControlFlow;
+
+use super::{impls::parse_fat_entry, *};
+
+/// A problem found by [`Fat::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// `sectors` sectors of FAT `copy` differ from the first active FAT
+    FatMismatch { copy: usize, sectors: usize },
+    /// The chain of `path` runs into `cluster`, which is not a valid link
+    BadChain { path: String, cluster: u32 },
+    /// `cluster` belongs to both `first` and `second`
+    CrossLinked {
+        cluster: u32,
+        first: String,
+        second: String,
+    },
+    /// A chain starting at `start` that no entry refers to
+    LostChain { start: u32, length: usize },
+    /// The size of `path` needs a different number of clusters than its chain
+    SizeMismatch {
+        path: String,
+        size: u32,
+        clusters: usize,
+    },
+}
+
+impl core::fmt::Display for Problem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Problem::FatMismatch { copy, sectors } => {
+                write!(f, "FAT #{} differs in {} sectors", copy, sectors)
+            }
+            Problem::BadChain { path, cluster } => {
+                write!(f, "{}: bad link at cluster {}", path, cluster)
+            }
+            Problem::CrossLinked {
+                cluster,
+                first,
+                second,
+            } => write!(
+                f,
+                "{}: cross-linked with {} at cluster {}",
+                second, first, cluster
+            ),
+            Problem::LostChain { start, length } => {
+                write!(f, "lost chain of {} clusters at {}", length, start)
+            }
+            Problem::SizeMismatch {
+                path,
+                size,
+                clusters,
+            } => write!(f, "{}: size {} but {} clusters", path, size, clusters),
+        }
+    }
+}
+
+/// Result of a [`Fat::check`]
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub problems: Vec<Problem>,
+    /// Files found, directories excluded
+    pub files: usize,
+    /// Directories found, the root excluded
+    pub dirs: usize,
+    /// Clusters owned by an entry
+    pub used_clusters: usize,
+    /// Problems that have been fixed
+    pub repaired: usize,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A file whose chain must be cut or whose size must be reduced
+struct SizeFix {
+    entry: DirEntry,
+    location: EntryLocation,
+    chain: Vec<u32>,
+}
+
+struct Checker<'a, V: FatVariant> {
+    fs: &'a FatImpl<V>,
+    fat: Vec<u32>,
+    /// Index into `paths` + 1 of the owner of each cluster, 0 if none
+    owner: Vec<usize>,
+    paths: Vec<String>,
+    report: FsckReport,
+    fixes: Vec<SizeFix>,
+}
+
+impl<'a, V: FatVariant> Checker<'a, V> {
+    fn new(fs: &'a FatImpl<V>) -> FsResult<Self> {
+        let end = fs.cluster_count() + 2;
+        let mut fat = Vec::with_capacity(end);
+        let mut block = Block::default();
+
+        for sector in 0..end.div_ceil(BLOCK_SIZE / V::ENTRY_SIZE) {
+            fs.inner.read_block(fs.fat_sector(sector), &mut block)?;
+            fat.extend(
+                block
+                    .as_ref()
+                    .chunks_exact(V::ENTRY_SIZE)
+                    .map(parse_fat_entry::<V>),
+            );
+        }
+        fat.truncate(end);
+
+        Ok(Self {
+            fs,
+            fat,
+            owner: vec![0; end],
+            paths: Vec::new(),
+            report: FsckReport::default(),
+            fixes: Vec::new(),
+        })
+    }
+
+    fn compare_fats(&mut self) -> FsResult {
+        let fat_size = self.fs.bpb.fat_sectors();
+        let first_copy = self.fs.bpb.active_fats().start;
+        let mut first = Block::default();
+        let mut other = Block::default();
+
+        for copy in self.fs.bpb.active_fats().skip(1) {
+            let mut sectors = 0;
+
+            for sector in 0..fat_size {
+                let sector = self.fs.fat_sector(sector);
+                self.fs.inner.read_block(sector, &mut first)?;
+                self.fs
+                    .inner
+                    .read_block(sector + (copy - first_copy) * fat_size, &mut other)?;
+
+                if first.as_ref() != other.as_ref() {
+                    sectors += 1;
+                }
+            }
+
+            if sectors > 0 {
+                self.report
+                    .problems
+                    .push(Problem::FatMismatch { copy, sectors });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follow the chain starting at `start` and mark it as owned by `path`,
+    /// returns the chain if it is sound
+    fn claim(&mut self, start: Cluster, path: &str) -> Option<Vec<u32>> {
+        let mut chain = Vec::new();
+
+        if start == Cluster::EMPTY {
+            return Some(chain);
+        }
+
+        self.paths.push(path.into());
+        let id = self.paths.len();
+        let mut current = start.0 as usize;
+
+        loop {
+            if !(2..self.fat.len()).contains(&current) {
+                self.report.problems.push(Problem::BadChain {
+                    path: path.into(),
+                    cluster: current as u32,
+                });
+                return None;
+            }
+
+            match self.owner[current] {
+                0 => self.owner[current] = id,
+                owner if owner == id => {
+                    // a loop
+                    self.report.problems.push(Problem::BadChain {
+                        path: path.into(),
+                        cluster: current as u32,
+                    });
+                    return None;
+                }
+                owner => {
+                    self.report.problems.push(Problem::CrossLinked {
+                        cluster: current as u32,
+                        first: self.paths[owner - 1].clone(),
+                        second: path.into(),
+                    });
+                    return None;
+                }
+            }
+
+            chain.push(current as u32);
+            self.report.used_clusters += 1;
+
+            match self.fat[current] {
+                next if next >= V::END_OF_CHAIN => return Some(chain),
+                next if next == 0 || next == V::BAD_CLUSTER => {
+                    self.report.problems.push(Problem::BadChain {
+                        path: path.into(),
+                        cluster: current as u32,
+                    });
+                    return None;
+                }
+                next => current = next as usize,
+            }
+        }
+    }
+
+    fn check_dir(&mut self, dir: Directory, path: &str) -> FsResult {
+        let mut children = Vec::new();
+
+        self.fs
+            .walk_entries(&dir, |entry, location, _| -> ControlFlow<()> {
+                let dot = entry.filename.name[0] == b'.';
+                if !dot && !entry.is_volume_id() {
+                    children.push((entry, location));
+                }
+                ControlFlow::Continue(())
+            })?;
+
+        for (entry, location) in children {
+            let path = format!("{}/{}", path, entry.filename());
+            let chain = self.claim(entry.cluster, &path);
+
+            if entry.is_directory() {
+                self.report.dirs += 1;
+
+                // a broken directory could lead anywhere, even to itself
+                if chain.is_some_and(|chain| !chain.is_empty()) {
+                    self.check_dir(Directory::from_entry(entry), &path)?;
+                }
+                continue;
+            }
+
+            self.report.files += 1;
+
+            let Some(chain) = chain else {
+                continue;
+            };
+
+            let expected = (entry.size as usize).div_ceil(self.fs.cluster_size());
+            if chain.len() != expected {
+                self.report.problems.push(Problem::SizeMismatch {
+                    path,
+                    size: entry.size,
+                    clusters: chain.len(),
+                });
+                self.fixes.push(SizeFix {
+                    entry,
+                    location,
+                    chain,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Chains of used clusters without an owner, as (start, length)
+    fn lost_chains(&self) -> Vec<(u32, Vec<u32>)> {
+        let lost =
+            |c: usize| self.owner[c] == 0 && self.fat[c] != 0 && self.fat[c] != V::BAD_CLUSTER;
+
+        // the heads are the lost clusters no other lost cluster links to
+        let mut is_head: Vec<bool> = (0..self.fat.len()).map(|c| c >= 2 && lost(c)).collect();
+        for c in (2..self.fat.len()).filter(|&c| lost(c)) {
+            if let Some(head) = is_head.get_mut(self.fat[c] as usize) {
+                *head = false;
+            }
+        }
+
+        let mut visited = vec![false; self.fat.len()];
+        let mut chains = Vec::new();
+
+        // heads first, then whatever is left only loops
+        let starts = (2..self.fat.len())
+            .filter(|&c| is_head[c])
+            .chain((2..self.fat.len()).filter(|&c| lost(c)));
+
+        for start in starts.collect::<Vec<_>>() {
+            if visited[start] {
+                continue;
+            }
+
+            let mut chain = Vec::new();
+            let mut current = start;
+            while current < self.fat.len() && lost(current) && !visited[current] {
+                visited[current] = true;
+                chain.push(current as u32);
+                current = self.fat[current] as usize;
+            }
+
+            chains.push((start as u32, chain));
+        }
+
+        chains
+    }
+
+    fn repair(&mut self, lost: &[(u32, Vec<u32>)]) -> FsResult {
+        let fs = self.fs;
+
+        // later writes go to every active copy, so the copies are synced first
+        let fat_size = fs.bpb.fat_sectors();
+        let first_copy = fs.bpb.active_fats().start;
+        let mut block = Block::default();
+        for problem in &self.report.problems {
+            if let Problem::FatMismatch { copy, .. } = problem {
+                for sector in 0..fat_size {
+                    let sector = fs.fat_sector(sector);
+                    fs.inner.read_block(sector, &mut block)?;
+                    fs.inner
+                        .write_block(sector + (copy - first_copy) * fat_size, &block)?;
+                }
+                self.report.repaired += 1;
+            }
+        }
+
+        for (_, chain) in lost {
+            for &cluster in chain {
+                fs.write_fat_entry(&Cluster(cluster), 0)?;
+            }
+            self.report.repaired += 1;
+        }
+
+        for SizeFix {
+            mut entry,
+            location,
+            chain,
+        } in self.fixes.drain(..)
+        {
+            let expected = (entry.size as usize).div_ceil(fs.cluster_size());
+
+            if chain.len() > expected {
+                match expected {
+                    0 => entry.cluster = Cluster::EMPTY,
+                    n => fs.write_fat_entry(&Cluster(chain[n - 1]), V::ENTRY_MASK)?,
+                }
+                for &cluster in &chain[expected..] {
+                    fs.write_fat_entry(&Cluster(cluster), 0)?;
+                }
+            } else {
+                entry.size = (chain.len() * fs.cluster_size()) as u32;
+            }
+
+            fs.update_dir_entry(&entry, &location)?;
+            self.report.repaired += 1;
+        }
+
+        // count again, so that a recorded count is right again as well
+        fs.reset_free_count();
+        fs.free_clusters()?;
+        fs.sync()
+    }
+}
+
+impl<V: FatVariant> Fat<V> {
+    /// Check the volume, and fix what can be fixed safely if `repair` is set
+    ///
+    /// Open files cache their cluster chain and size, so repairing fails
+    /// with `Busy` while any is open. Nothing else should use the volume
+    /// meanwhile.
+    pub fn check(&self, repair: bool) -> FsResult<FsckReport> {
+        if repair && self.handle.has_open_files() {
+            return Err(FsError::Busy);
+        }
+
+        let mut checker = Checker::new(&self.handle)?;
+
+        checker.compare_fats()?;
+        // a root directory that is a cluster chain owns it
+        if self.handle.root_cluster != Cluster::ROOT_DIR {
+            checker.claim(self.handle.root_cluster, "/");
+        }
+        checker.check_dir(Directory::root(), "")?;
+
+        let lost = checker.lost_chains();
+        for (start, chain) in &lost {
+            checker.report.problems.push(Problem::LostChain {
+                start: *start,
+                length: chain.len(),
+            });
+        }
+
+        if repair {
+            checker.repair(&lost)?;
+        }
+
+        Ok(checker.report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::mkfs::FormatOptions, *};
+
+    fn volume() -> (RamDisk, Fat16) {
+        let disk = RamDisk::new(8 * 1024 * 1024);
+        let options = FormatOptions {
+            sectors_per_cluster: Some(1),
+            ..Default::default()
+        };
+        let fs = Fat16::format(disk.clone(), &options).unwrap();
+
+        fs.create_dir("/DIR").unwrap();
+        fs.create_file("/DIR/BIG.BIN")
+            .unwrap()
+            .write_all(&[0x42; 5000])
+            .unwrap();
+        fs.create_file("/small file.txt")
+            .unwrap()
+            .write_all(b"small")
+            .unwrap();
+
+        (disk, fs)
+    }
+
+    /// Overwrite a FAT entry in the first FAT only
+    fn set_fat(disk: &RamDisk, fs: &Fat16, cluster: u32, value: u16) {
+        let sector = fs.handle.fat_start + cluster as usize * 2 / BLOCK_SIZE;
+        let offset = cluster as usize * 2 % BLOCK_SIZE;
+
+        let mut block = Block512::default();
+        disk.read_block(sector, &mut block).unwrap();
+        block.as_mut()[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        disk.write_block(sector, &block).unwrap();
+    }
+
+    fn first_cluster(fs: &Fat16, path: &str) -> u32 {
+        fs.handle.get_dir_entry(path).unwrap().cluster.0
+    }
+
+    #[test]
+    fn test_clean() {
+        let (_, fs) = volume();
+        let report = fs.check(false).unwrap();
+
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(report.files, 2);
+        assert_eq!(report.dirs, 1);
+        // one for the directory, 5000 bytes in 512 byte clusters, one more
+        assert_eq!(report.used_clusters, 1 + 10 + 1);
+    }
+
+    #[test]
+    fn test_busy() {
+        let (_, fs) = volume();
+        let file = fs.open_file("/small file.txt").unwrap();
+
+        // an open file may be checked but not repaired under its feet
+        assert!(fs.check(false).unwrap().is_clean());
+        assert_eq!(fs.check(true).unwrap_err(), FsError::Busy);
+
+        drop(file);
+        assert!(fs.check(true).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_problems() {
+        let (disk, fs) = volume();
+        let big = first_cluster(&fs, "/DIR/BIG.BIN");
+        let small = first_cluster(&fs, "/small file.txt");
+
+        // a lost cluster at the end of the volume, BIG.BIN ends early and its
+        // rest is lost, the small file runs into the directory
+        let last = fs.handle.cluster_count() as u32 + 1;
+        set_fat(&disk, &fs, last, 0xFFFF);
+        set_fat(&disk, &fs, big + 4, 0xFFFF);
+        set_fat(&disk, &fs, small, first_cluster(&fs, "/DIR") as u16);
+
+        let report = fs.check(true).unwrap();
+        let problems = &report.problems;
+
+        // the first and the last sector of the FAT were changed
+        assert!(problems.contains(&Problem::FatMismatch {
+            copy: 1,
+            sectors: 2
+        }));
+        assert!(problems.contains(&Problem::LostChain {
+            start: big + 5,
+            length: 5
+        }));
+        assert!(problems.contains(&Problem::LostChain {
+            start: last,
+            length: 1
+        }));
+        assert!(problems.contains(&Problem::SizeMismatch {
+            path: "/DIR/BIG.BIN".into(),
+            size: 5000,
+            clusters: 5
+        }));
+        assert!(problems.iter().any(|p| matches!(
+            p,
+            Problem::CrossLinked { second, .. } if second == "/small file.txt"
+        )));
+
+        // the cross link is left alone
+        assert_eq!(report.repaired, 4);
+        let report = fs.check(false).unwrap();
+        assert_eq!(report.problems.len(), 1, "{:?}", report);
+
+        let meta = fs.metadata("/DIR/BIG.BIN").unwrap();
+        assert_eq!(meta.len, 5 * 512);
+    }
+}
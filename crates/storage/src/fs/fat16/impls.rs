@@ -1,168 +1,398 @@
-use super::*;
+use core::{ops::ControlFlow, sync::atomic::Ordering};
 
-impl Fat16Impl {
+use super::{
+    directory::DirIter,
+    lfn::{self, LfnBuilder},
+    *,
+};
+
+/// A directory entry, where it is stored and the slots of its long name
+type Found = (DirEntry, EntryLocation, Vec<EntryLocation>);
+
+/// `free_count` before the FAT has been scanned
+const UNKNOWN: usize = usize::MAX;
+
+impl<V: FatVariant> FatImpl<V> {
     pub fn new(inner: impl BlockDevice<Block512>) -> Self {
         let mut block = Block::default();
-        let block_size = Block512::size();
 
         inner.read_block(0, &mut block).unwrap();
-        let bpb = Fat16Bpb::new(block.as_ref()).unwrap();
+        let bpb = V::parse(block.as_ref()).unwrap();
 
-        trace!("Loading Fat16 Volume: {:#?}", bpb);
+        trace!("Loading {} Volume: {:#?}", V::NAME, bpb);
 
-        // FirstDataSector = BPB_ResvdSecCnt + (BPB_NumFATs * FATSz) + RootDirSectors;
-        let root_dir_size =
-            (bpb.root_entries_count() as usize * DirEntry::LEN).div_ceil(block_size);
+        // FirstDataSector = BPB_ResvdSecCnt + (BPB_NumFATs * FATSz) +
+        // RootDirSectors;
+        let fat_start = bpb.reserved_sectors();
+        let first_root_dir_sector = fat_start + bpb.fats() * bpb.fat_sectors();
+        let first_data_sector = first_root_dir_sector + bpb.root_dir_sectors();
 
-        let fat_start = bpb.reserved_sector_count() as usize;
-        let first_root_dir_sector =
-            fat_start + (bpb.fat_count() as usize * bpb.sectors_per_fat() as usize);
-        let first_data_sector = first_root_dir_sector + root_dir_size;
+        let (free_count, free_hint) = bpb.load_free_info(&inner);
 
-        Self {
+        let fs = Self {
+            root_cluster: bpb.root_cluster(),
             bpb,
             inner: Box::new(inner),
             fat_start,
             first_data_sector,
             first_root_dir_sector,
+            // out of range hints are clamped on use
+            free_hint: AtomicUsize::new(free_hint),
+            free_count: AtomicUsize::new(UNKNOWN),
+            free_info_dirty: AtomicBool::new(false),
+            open_files: spin::Mutex::new(Vec::new()),
+        };
+
+        // a recorded count that cannot be right is counted again
+        if let Some(count) = free_count.filter(|&count| count <= fs.cluster_count()) {
+            fs.free_count.store(count, Ordering::Relaxed);
         }
+
+        fs
     }
 
-    pub fn cluster_to_sector(&self, cluster: &Cluster) -> usize {
+    /// Map the root directory marker (and 0 in "..") to the first cluster
+    /// of the root directory
+    fn resolve(&self, cluster: &Cluster) -> Cluster {
         match *cluster {
+            Cluster::ROOT_DIR | Cluster::EMPTY => self.root_cluster,
+            cluster => cluster,
+        }
+    }
+
+    pub fn cluster_to_sector(&self, cluster: &Cluster) -> usize {
+        match self.resolve(cluster) {
             Cluster::ROOT_DIR => self.first_root_dir_sector,
             Cluster(c) => {
-                // FirstSectorofCluster = ((N – 2) * BPB_SecPerClus) + FirstDataSector;
-                let first_sector_of_cluster = (c - 2) * self.bpb.sectors_per_cluster() as u32;
-                self.first_data_sector + first_sector_of_cluster as usize
+                // FirstSectorofCluster = ((N – 2) * BPB_SecPerClus) +
+                // FirstDataSector;
+                let first_sector_of_cluster = (c as usize - 2) * self.bpb.cluster_sectors();
+                self.first_data_sector + first_sector_of_cluster
             }
         }
     }
 
-    /// Finds an entry in a given sector
-    fn find_entry_in_sector(
-        &self,
-        match_name: &ShortFileName,
-        sector: usize,
-    ) -> FsResult<DirEntry> {
-        let mut block = Block::default();
+    /// Sectors in a cluster of a directory, the fixed root directory is one
+    /// area of its own
+    pub(super) fn dir_sectors(&self, cluster: &Cluster) -> usize {
+        match self.resolve(cluster) {
+            Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
+            _ => self.bpb.cluster_sectors(),
+        }
+    }
+
+    /// Bytes in one cluster
+    pub fn cluster_size(&self) -> usize {
+        self.bpb.cluster_sectors() * Block512::size()
+    }
+
+    /// Number of data clusters on the volume, valid clusters are `2..count + 2`
+    pub fn cluster_count(&self) -> usize {
+        let data_sectors = self.bpb.volume_sectors() - self.first_data_sector;
+        let fat_entries = self.bpb.fat_sectors() * Block512::size() / V::ENTRY_SIZE;
+        (data_sectors / self.bpb.cluster_sectors()).min(fat_entries - 2)
+    }
+
+    /// Sector and offset of the FAT entry of a cluster in the given FAT copy
+    fn fat_entry_position(&self, fat: usize, cluster: &Cluster) -> (usize, usize) {
+        let fat_offset = cluster.0 as usize * V::ENTRY_SIZE;
         let block_size = Block512::size();
-        self.inner.read_block(sector, &mut block).unwrap();
-
-        for entry in 0..block_size / DirEntry::LEN {
-            let start = entry * DirEntry::LEN;
-            let end = (entry + 1) * DirEntry::LEN;
-            let dir_entry =
-                DirEntry::parse(&block[start..end]).map_err(|_| FsError::InvalidOperation)?;
-            // trace!("Matching {} to {}...", dir_entry.filename(), match_name);
-            if dir_entry.is_eod() {
-                // Can quit early
-                return Err(FsError::FileNotFound);
-            } else if dir_entry.filename.matches(match_name) {
-                // Found it
-                return Ok(dir_entry);
-            };
+        let sector = self.fat_start + fat * self.bpb.fat_sectors() + fat_offset / block_size;
+        (sector, fat_offset % block_size)
+    }
+
+    /// The `index`-th sector of the first active FAT
+    pub(super) fn fat_sector(&self, index: usize) -> usize {
+        self.fat_start + self.bpb.active_fats().start * self.bpb.fat_sectors() + index
+    }
+
+    /// Read the FAT entry of a cluster from the first active FAT
+    fn read_fat_entry(&self, cluster: &Cluster) -> FsResult<u32> {
+        let mut block = Block::default();
+        let (sector, offset) = self.fat_entry_position(self.bpb.active_fats().start, cluster);
+
+        self.inner.read_block(sector, &mut block)?;
+
+        Ok(parse_fat_entry::<V>(&block[offset..]))
+    }
+
+    /// Write the FAT entry of a cluster into every active FAT copy, keeping
+    /// the reserved bits
+    pub(super) fn write_fat_entry(&self, cluster: &Cluster, value: u32) -> FsResult {
+        let mut block = Block::default();
+
+        for fat in self.bpb.active_fats() {
+            let (sector, offset) = self.fat_entry_position(fat, cluster);
+            self.inner.read_block(sector, &mut block)?;
+
+            let raw = &mut block.as_mut()[offset..offset + V::ENTRY_SIZE];
+            let mut old = [0u8; 4];
+            old[..V::ENTRY_SIZE].copy_from_slice(raw);
+            let new = (u32::from_le_bytes(old) & !V::ENTRY_MASK) | (value & V::ENTRY_MASK);
+            raw.copy_from_slice(&new.to_le_bytes()[..V::ENTRY_SIZE]);
+            self.inner.write_block(sector, &block)?;
         }
-        Err(FsError::NotInSector)
+
+        Ok(())
     }
 
     /// look for next cluster in FAT
     pub fn next_cluster(&self, cluster: &Cluster) -> FsResult<Cluster> {
-        let fat_offset = (cluster.0 * 2) as usize;
+        let cluster = self.resolve(cluster);
+
+        // the fixed root directory is not a chain
+        if cluster == Cluster::ROOT_DIR {
+            return Err(FsError::EndOfFile);
+        }
+
+        match self.read_fat_entry(&cluster)? {
+            f if f == V::BAD_CLUSTER => Err(FsError::BadCluster), // Bad cluster
+            f if f >= V::END_OF_CHAIN => Err(FsError::EndOfFile), // There is no next cluster
+            f => Ok(Cluster(f)),                                  // Seems legit
+        }
+    }
+
+    /// Allocate a free cluster and mark it as the end of a chain
+    ///
+    /// If `prev` is given, the new cluster is linked after it.
+    pub fn alloc_cluster(&self, prev: Option<&Cluster>) -> FsResult<Cluster> {
+        let count = self.cluster_count();
+        let hint = self.free_hint.load(Ordering::Relaxed).clamp(2, count + 1);
+
+        let cluster = (hint..count + 2)
+            .chain(2..hint)
+            .map(|c| Cluster(c as u32))
+            .find(|c| matches!(self.read_fat_entry(c), Ok(0)))
+            .ok_or(FsError::WriteZero)?;
+
+        // the end of the chain and the link to it, in every FAT copy
+        self.transaction(|| {
+            self.write_fat_entry(&cluster, V::ENTRY_MASK)?;
+            if let Some(prev) = prev {
+                self.write_fat_entry(&self.resolve(prev), cluster.0)?;
+            }
+            Ok(())
+        })?;
+
+        self.free_hint
+            .store(cluster.0 as usize + 1, Ordering::Relaxed);
+        self.adjust_free_count(-1);
+
+        trace!("Allocated cluster {} after {:?}", cluster, prev);
+
+        Ok(cluster)
+    }
+
+    /// Number of free clusters, the FAT is scanned the first time if the
+    /// volume does not record it
+    pub fn free_clusters(&self) -> FsResult<usize> {
+        match self.free_count.load(Ordering::Relaxed) {
+            UNKNOWN => {}
+            count => return Ok(count),
+        }
+
+        let end = self.cluster_count() + 2;
+        let entries_per_sector = Block512::size() / V::ENTRY_SIZE;
         let mut block = Block::default();
-        let block_size = Block512::size();
-        let cur_fat_sector = self.fat_start + fat_offset / block_size;
-        let offset = fat_offset % block_size;
+        let mut count = 0;
+
+        for sector in 0..end.div_ceil(entries_per_sector) {
+            self.inner.read_block(self.fat_sector(sector), &mut block)?;
+
+            let first = sector * entries_per_sector;
+            count += block
+                .as_ref()
+                .chunks_exact(V::ENTRY_SIZE)
+                .enumerate()
+                .filter(|(i, raw)| {
+                    (2..end).contains(&(first + i)) && parse_fat_entry::<V>(raw) == 0
+                })
+                .count();
+        }
+
+        self.free_count.store(count, Ordering::Relaxed);
+        self.free_info_dirty.store(true, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    /// Keep the free cluster count in step, if it has been counted
+    fn adjust_free_count(&self, delta: isize) {
+        let _ = self
+            .free_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count != UNKNOWN).then(|| count.saturating_add_signed(delta))
+            });
+        self.free_info_dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Forget the free cluster count, after the FAT was changed behind
+    /// `alloc_cluster` and `free_chain`
+    pub(super) fn reset_free_count(&self) {
+        self.free_count.store(UNKNOWN, Ordering::Relaxed);
+        self.free_info_dirty.store(true, Ordering::Relaxed);
+    }
 
-        self.inner.read_block(cur_fat_sector, &mut block).unwrap();
+    /// Store the free cluster count and the allocation hint if they changed,
+    /// they are only hints so this waits for a flush
+    fn store_free_info(&self) -> FsResult {
+        if !self.free_info_dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let free = match self.free_count.load(Ordering::Relaxed) {
+            UNKNOWN => None,
+            count => Some(count),
+        };
+
+        let hint = self.free_hint.load(Ordering::Relaxed);
+        self.bpb
+            .store_free_info(self.inner.as_ref(), free, hint)
+            .inspect_err(|_| self.free_info_dirty.store(true, Ordering::Relaxed))
+    }
 
-        let fat_entry = u16::from_le_bytes(block[offset..=offset + 1].try_into().unwrap_or([0; 2]));
-        match fat_entry {
-            0xFFF7 => Err(FsError::BadCluster),         // Bad cluster
-            0xFFF8..=0xFFFF => Err(FsError::EndOfFile), // There is no next cluster
-            f => Ok(Cluster(f as u32)),                 // Seems legit
+    /// Remember that a file whose entry is at `location` has been opened
+    pub(super) fn open_file(&self, location: EntryLocation) {
+        self.open_files.lock().push(location);
+    }
+
+    /// Forget one handle of the file whose entry is at `location`
+    pub(super) fn close_file(&self, location: &EntryLocation) {
+        let mut open_files = self.open_files.lock();
+        if let Some(index) = open_files.iter().position(|open| open == location) {
+            open_files.swap_remove(index);
         }
     }
 
-    pub fn iterate_dir<F>(&self, dir: &directory::Directory, mut func: F) -> FsResult
-    where
-        F: FnMut(&DirEntry),
-    {
-        if let Some(entry) = &dir.entry {
-            trace!("Iterating directory: {}", entry.filename());
+    /// Whether the file whose entry is at `location` is open
+    fn is_open(&self, location: &EntryLocation) -> bool {
+        self.open_files.lock().contains(location)
+    }
+
+    /// Whether any file on the volume is open
+    pub(super) fn has_open_files(&self) -> bool {
+        !self.open_files.lock().is_empty()
+    }
+
+    /// Run a metadata operation as one transaction of the device, a crash
+    /// leaves all of its writes on the disk or none
+    pub(super) fn transaction<T>(&self, op: impl FnOnce() -> FsResult<T>) -> FsResult<T> {
+        self.inner.begin_transaction()?;
+        let result = op();
+        let end = self.inner.end_transaction();
+
+        // the device dropped the writes, allocations included
+        if end == Err(FsError::TransactionTooLarge) {
+            self.reset_free_count();
         }
 
-        let mut current_cluster = Some(dir.cluster);
+        let value = result?;
+        end.map(|_| value)
+    }
+
+    /// Write back what is only kept in memory and flush the device
+    pub fn sync(&self) -> FsResult {
+        self.store_free_info()?;
+        self.inner.flush()
+    }
+
+    /// Write a directory entry back to where it was found
+    pub fn update_dir_entry(&self, entry: &DirEntry, location: &EntryLocation) -> FsResult {
+        let mut block = Block::default();
+        self.inner.read_block(location.sector, &mut block)?;
+        entry.write_to(&mut block.as_mut()[location.range()]);
+        self.inner.write_block(location.sector, &block)
+    }
+
+    /// Walk the raw slots of a directory in order, following the cluster
+    /// chain, until `func` breaks
+    fn walk_dir<T, F>(&self, dir: &Directory, mut func: F) -> FsResult<Option<T>>
+    where
+        F: FnMut(&[u8], EntryLocation) -> FsResult<ControlFlow<T>>,
+    {
+        let mut current_cluster = Some(self.resolve(&dir.cluster));
         let mut dir_sector_num = self.cluster_to_sector(&dir.cluster);
-        let dir_size = match dir.cluster {
-            Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
-            _ => self.bpb.sectors_per_cluster() as usize,
-        };
-        trace!("Directory size: {}", dir_size);
+        let dir_size = self.dir_sectors(&dir.cluster);
 
         let mut block = Block::default();
         let block_size = Block512::size();
         while let Some(cluster) = current_cluster {
             for sector in dir_sector_num..dir_sector_num + dir_size {
-                self.inner.read_block(sector, &mut block).unwrap();
+                self.inner.read_block(sector, &mut block)?;
                 for entry in 0..block_size / DirEntry::LEN {
-                    let start = entry * DirEntry::LEN;
-                    let end = (entry + 1) * DirEntry::LEN;
-
-                    let dir_entry = DirEntry::parse(&block[start..end])?;
-
-                    if dir_entry.is_eod() {
-                        return Ok(());
-                    } else if dir_entry.is_valid() && !dir_entry.is_long_name() {
-                        func(&dir_entry);
+                    let location = EntryLocation::new(sector, entry);
+                    if let ControlFlow::Break(ret) = func(&block[location.range()], location)? {
+                        return Ok(Some(ret));
                     }
                 }
             }
-            current_cluster = if cluster != Cluster::ROOT_DIR {
-                match self.next_cluster(&cluster) {
-                    Ok(n) => {
-                        dir_sector_num = self.cluster_to_sector(&n);
-                        Some(n)
-                    }
-                    _ => None,
+            current_cluster = match self.next_cluster(&cluster) {
+                Ok(n) => {
+                    dir_sector_num = self.cluster_to_sector(&n);
+                    Some(n)
                 }
-            } else {
-                None
-            }
+                _ => None,
+            };
         }
-        Ok(())
+        Ok(None)
+    }
+
+    /// Walk the valid entries of a directory with their long names
+    /// assembled, until the end of directory or `func` breaks
+    pub(super) fn walk_entries<T, F>(&self, dir: &Directory, mut func: F) -> FsResult<Option<T>>
+    where
+        F: FnMut(DirEntry, EntryLocation, Vec<EntryLocation>) -> ControlFlow<T>,
+    {
+        let mut lfn = LfnBuilder::new();
+
+        let ret = self.walk_dir(dir, |data, location| {
+            let mut entry = DirEntry::parse(data)?;
+
+            if entry.is_eod() {
+                return Ok(ControlFlow::Break(None));
+            } else if !entry.is_valid() {
+                lfn.reset();
+                return Ok(ControlFlow::Continue(()));
+            } else if entry.is_long_name() {
+                lfn.push(data, location);
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            let slots = match lfn.finish(&entry.filename) {
+                Some((name, slots)) => {
+                    entry.long_name = Some(name);
+                    slots
+                }
+                None => Vec::new(),
+            };
+
+            Ok(match func(entry, location, slots) {
+                ControlFlow::Break(ret) => ControlFlow::Break(Some(ret)),
+                ControlFlow::Continue(()) => ControlFlow::Continue(()),
+            })
+        })?;
+
+        Ok(ret.flatten())
     }
 
     /// Get an entry from the given directory
     fn find_directory_entry(&self, dir: &Directory, name: &str) -> FsResult<DirEntry> {
-        let match_name = ShortFileName::parse(name)?;
+        self.locate_directory_entry(dir, name)
+            .map(|(entry, ..)| entry)
+    }
 
-        let mut current_cluster = Some(dir.cluster);
-        let mut dir_sector_num = self.cluster_to_sector(&dir.cluster);
-        let dir_size = match dir.cluster {
-            Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
-            _ => self.bpb.sectors_per_cluster() as usize,
-        };
-        while let Some(cluster) = current_cluster {
-            for sector in dir_sector_num..dir_sector_num + dir_size {
-                match self.find_entry_in_sector(&match_name, sector) {
-                    Err(FsError::NotInSector) => continue,
-                    x => return x,
-                }
-            }
-            current_cluster = if cluster != Cluster::ROOT_DIR {
-                match self.next_cluster(&cluster) {
-                    Ok(n) => {
-                        dir_sector_num = self.cluster_to_sector(&n);
-                        Some(n)
-                    }
-                    _ => None,
-                }
+    /// Get an entry and its on-disk location from the given directory, by
+    /// long or short name
+    fn locate_directory_entry(&self, dir: &Directory, name: &str) -> FsResult<Found> {
+        let sfn = ShortFileName::parse(name).ok();
+
+        self.walk_entries(dir, |entry, location, lfn| {
+            if entry.matches(name, sfn.as_ref()) {
+                ControlFlow::Break((entry, location, lfn))
             } else {
-                None
+                ControlFlow::Continue(())
             }
-        }
-        Err(FsError::FileNotFound)
+        })?
+        .ok_or(FsError::FileNotFound)
     }
 
     fn get_parent_dir(&self, path: &str) -> FsResult<Directory> {
@@ -188,28 +418,454 @@ impl Fat16Impl {
         Ok(current)
     }
 
-    fn get_dir_entry(&self, path: &str) -> FsResult<DirEntry> {
-        let parent = self.get_parent_dir(path)?;
-        let name = path.rsplit(PATH_SEPARATOR).next().unwrap_or("");
+    pub(super) fn get_dir_entry(&self, path: &str) -> FsResult<DirEntry> {
+        self.locate_dir_entry(path).map(|(entry, ..)| entry)
+    }
+
+    fn locate_dir_entry(&self, path: &str) -> FsResult<Found> {
+        let (parent, name) = split_path(path);
+        let parent = self.get_dir(parent)?;
+
+        self.locate_directory_entry(&parent, name)
+    }
+
+    /// Resolve a path whose every component is a directory
+    fn get_dir(&self, path: &str) -> FsResult<Directory> {
+        let mut current = Directory::root();
+
+        for name in path.split(PATH_SEPARATOR).filter(|name| !name.is_empty()) {
+            let entry = self.find_directory_entry(&current, name)?;
+
+            if !entry.is_directory() {
+                return Err(FsError::NotADirectory);
+            }
+
+            current = Directory::from_entry(entry);
+        }
+
+        Ok(current)
+    }
+
+    /// Resolve the parent directory of a new entry, make sure the name is
+    /// valid and not taken yet, and pick its short name
+    fn prepare_new_entry(
+        &self,
+        path: &str,
+    ) -> FsResult<(Directory, ShortFileName, Option<String>)> {
+        let (parent, name) = split_path(path);
+        reject_dot_name(name)?;
+
+        let parent = self.get_dir(parent)?;
+
+        match self.find_directory_entry(&parent, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::FileNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        if !lfn::needs_lfn(name) {
+            return Ok((parent, ShortFileName::parse(name)?, None));
+        }
+
+        if name.is_empty() {
+            return Err(FilenameError::FilenameEmpty.into());
+        } else if name
+            .chars()
+            .any(|c| c.is_control() || "\"*:<>?\\|".contains(c))
+        {
+            return Err(FilenameError::InvalidCharacter.into());
+        } else if name.encode_utf16().count() > lfn::LFN_MAX_LEN {
+            return Err(FilenameError::NameTooLong.into());
+        }
+
+        let sfn = self.unique_short_name(&parent, name)?;
+        Ok((parent, sfn, Some(name.into())))
+    }
+
+    /// Generate a "~N" short name for a long name that is not used in the
+    /// directory yet
+    fn unique_short_name(&self, dir: &Directory, name: &str) -> FsResult<ShortFileName> {
+        let mut taken = Vec::new();
+        self.walk_entries(dir, |entry, _, _| -> ControlFlow<()> {
+            taken.push(entry.filename);
+            ControlFlow::Continue(())
+        })?;
+
+        let basis = lfn::basis_name(name);
+        (1..1_000_000)
+            .map(|n| lfn::with_tail(&basis, n))
+            .find(|sfn| !taken.contains(sfn))
+            .ok_or(FsError::AlreadyExists)
+    }
+
+    /// Fill a cluster with zeros
+    fn zero_cluster(&self, cluster: &Cluster) -> FsResult {
+        let block = Block::default();
+        let first_sector = self.cluster_to_sector(cluster);
+
+        for sector in first_sector..first_sector + self.bpb.cluster_sectors() {
+            self.inner.write_block(sector, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// Release every cluster of the chain starting at `start`
+    fn free_chain(&self, start: &Cluster) -> FsResult {
+        let end = self.cluster_count() + 2;
+        let mut current = *start;
+
+        while (2..end).contains(&(current.0 as usize)) {
+            let next = self.next_cluster(&current);
+
+            self.write_fat_entry(&current, 0)?;
+            self.free_hint
+                .fetch_min(current.0 as usize, Ordering::Relaxed);
+            self.adjust_free_count(1);
+
+            current = match next {
+                Ok(next) => next,
+                Err(FsError::EndOfFile) => break,
+                Err(e) => return Err(e),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Last cluster of a cluster chain
+    fn last_cluster(&self, start: &Cluster) -> FsResult<Cluster> {
+        let mut current = *start;
+        loop {
+            current = match self.next_cluster(&current) {
+                Ok(next) => next,
+                Err(FsError::EndOfFile) => return Ok(current),
+                Err(e) => return Err(e),
+            };
+        }
+    }
+
+    /// Find `count` consecutive unused slots in the directory, the directory
+    /// is extended if needed (except for a fixed-size root directory)
+    fn find_free_entries(&self, dir: &Directory, count: usize) -> FsResult<Vec<EntryLocation>> {
+        let mut run = Vec::with_capacity(count);
+
+        let found = self.walk_dir(dir, |data, location| {
+            // 0x00 for end of directory, 0xE5 for deleted entry
+            if matches!(data[0], 0x00 | 0xE5) {
+                run.push(location);
+                if run.len() == count {
+                    return Ok(ControlFlow::Break(()));
+                }
+            } else {
+                run.clear();
+            }
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        if found.is_some() {
+            return Ok(run);
+        }
+
+        if self.resolve(&dir.cluster) == Cluster::ROOT_DIR {
+            return Err(FsError::WriteZero);
+        }
+
+        // the run of free slots may continue into the new clusters
+        let mut last = self.last_cluster(&self.resolve(&dir.cluster))?;
+        let entries_per_sector = Block512::size() / DirEntry::LEN;
+        while run.len() < count {
+            last = self.alloc_cluster(Some(&last))?;
+            self.zero_cluster(&last)?;
+
+            let first_sector = self.cluster_to_sector(&last);
+            let slots =
+                (first_sector..first_sector + self.bpb.cluster_sectors()).flat_map(|sector| {
+                    (0..entries_per_sector).map(move |entry| EntryLocation::new(sector, entry))
+                });
+            run.extend(slots.take(count - run.len()));
+        }
+
+        Ok(run)
+    }
+
+    /// Store a new entry in the directory, preceded by its long name entries
+    fn insert_dir_entry(&self, dir: &Directory, entry: &DirEntry) -> FsResult<EntryLocation> {
+        let mut raws = match &entry.long_name {
+            Some(name) => lfn::lfn_entries(name, &entry.filename)?,
+            None => Vec::new(),
+        };
+
+        let mut raw = [0u8; DirEntry::LEN];
+        entry.write_to(&mut raw);
+        raws.push(raw);
+
+        let slots = self.find_free_entries(dir, raws.len())?;
+
+        let mut block = Block::default();
+        for (raw, slot) in raws.iter().zip(&slots) {
+            self.inner.read_block(slot.sector, &mut block)?;
+            block.as_mut()[slot.range()].copy_from_slice(raw);
+            self.inner.write_block(slot.sector, &block)?;
+        }
+
+        Ok(*slots.last().unwrap())
+    }
+
+    /// Mark the entry and its long name entries as deleted
+    fn delete_dir_entry(&self, location: &EntryLocation, lfn: &[EntryLocation]) -> FsResult {
+        let mut block = Block::default();
+        for slot in lfn.iter().chain(core::iter::once(location)) {
+            self.inner.read_block(slot.sector, &mut block)?;
+            block.as_mut()[slot.range().start] = 0xE5;
+            self.inner.write_block(slot.sector, &block)?;
+        }
+        Ok(())
+    }
+
+    /// Cluster of the parent directory, read from the ".." entry
+    fn parent_cluster(&self, dir: &Cluster) -> FsResult<Cluster> {
+        let mut block = Block::default();
+        let location = EntryLocation::new(self.cluster_to_sector(dir), 1);
+        self.inner.read_block(location.sector, &mut block)?;
+
+        let dotdot = DirEntry::parse(&block[location.range()])?;
+        Ok(self.resolve(&dotdot.cluster))
+    }
+
+    /// Cluster stored in ".." for a directory in `parent`, 0 for the root
+    fn dotdot_cluster(&self, parent: &Directory) -> Cluster {
+        match self.resolve(&parent.cluster) {
+            cluster if cluster == self.root_cluster => Cluster::EMPTY,
+            cluster => cluster,
+        }
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<(DirEntry, EntryLocation)> {
+        let (parent, filename, long_name) = self.prepare_new_entry(path)?;
+        let mut entry = DirEntry::new(filename, Attributes::ARCHIVE);
+        entry.long_name = long_name;
+        let location = self.insert_dir_entry(&parent, &entry)?;
+
+        Ok((entry, location))
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        let (parent, filename, long_name) = self.prepare_new_entry(path)?;
+
+        let cluster = self.alloc_cluster(None)?;
+        self.zero_cluster(&cluster)?;
+
+        let mut entry = DirEntry::new(filename, Attributes::DIRECTORY);
+        entry.long_name = long_name;
+        entry.cluster = cluster;
+
+        if let Err(e) = self.insert_dir_entry(&parent, &entry) {
+            self.free_chain(&cluster)?;
+            return Err(e);
+        }
+
+        // "." points to itself, ".." to the parent or 0 for the root
+        let mut dot = DirEntry::new(ShortFileName::new(b".          "), Attributes::DIRECTORY);
+        dot.cluster = cluster;
+
+        let mut dotdot = DirEntry::new(ShortFileName::new(b"..         "), Attributes::DIRECTORY);
+        dotdot.cluster = self.dotdot_cluster(&parent);
+
+        let mut block = Block::default();
+        dot.write_to(&mut block.as_mut()[EntryLocation::new(0, 0).range()]);
+        dotdot.write_to(&mut block.as_mut()[EntryLocation::new(0, 1).range()]);
+        self.inner
+            .write_block(self.cluster_to_sector(&cluster), &block)
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        let (entry, location, lfn) = self.locate_dir_entry(path)?;
+
+        if entry.is_directory() {
+            return Err(FsError::NotAFile);
+        }
+
+        if entry.is_readonly() {
+            return Err(FsError::ReadOnly);
+        }
+
+        // open handles would keep using the freed clusters
+        if self.is_open(&location) {
+            return Err(FsError::Busy);
+        }
+
+        // drop the entry first, a crash in between only leaks clusters
+        self.delete_dir_entry(&location, &lfn)?;
+        self.free_chain(&entry.cluster)
+    }
+
+    /// Free the cluster chain of a file and set its size to 0, the entry
+    /// and its attributes stay
+    fn truncate_file(&self, path: &str) -> FsResult<(DirEntry, EntryLocation)> {
+        let (mut entry, location, _) = self.locate_dir_entry(path)?;
+
+        if entry.is_directory() {
+            return Err(FsError::NotAFile);
+        }
+
+        if entry.is_readonly() {
+            return Err(FsError::ReadOnly);
+        }
+
+        // open handles cache the chain that is about to be freed
+        if self.is_open(&location) {
+            return Err(FsError::Busy);
+        }
+
+        let chain = entry.cluster;
+        entry.cluster = Cluster::EMPTY;
+        entry.size = 0;
+        entry.attributes |= Attributes::ARCHIVE;
+
+        // same order as removing, a crash in between only leaks clusters
+        self.update_dir_entry(&entry, &location)?;
+        self.free_chain(&chain)?;
+
+        Ok((entry, location))
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        reject_dot_name(split_path(path).1)?;
+        let (entry, location, lfn) = self.locate_dir_entry(path)?;
 
-        self.find_directory_entry(&parent, name)
+        if !entry.is_directory() {
+            return Err(FsError::NotADirectory);
+        }
+
+        // anything but "." and ".."
+        let child = self.walk_entries(&Directory::new(entry.cluster), |child, _, _| match child
+            .filename
+            .name[0]
+        {
+            b'.' => ControlFlow::Continue(()),
+            _ => ControlFlow::Break(()),
+        })?;
+
+        if child.is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        self.delete_dir_entry(&location, &lfn)?;
+        self.free_chain(&entry.cluster)
+    }
+
+    /// Move an entry to a new path, possibly in another directory
+    fn rename(&self, src: &str, dst: &str, is_dir: bool) -> FsResult {
+        reject_dot_name(split_path(src).1)?;
+        let (mut entry, location, lfn) = self.locate_dir_entry(src)?;
+
+        match (entry.is_directory(), is_dir) {
+            (true, false) => return Err(FsError::NotAFile),
+            (false, true) => return Err(FsError::NotADirectory),
+            _ => {}
+        }
+
+        // open handles would write their entry back to the old slot
+        if self.is_open(&location) {
+            return Err(FsError::Busy);
+        }
+
+        let (parent, filename, long_name) = self.prepare_new_entry(dst)?;
+
+        if is_dir {
+            // refuse to move a directory into itself
+            let mut current = self.resolve(&parent.cluster);
+            while current != self.root_cluster {
+                if current == entry.cluster {
+                    return Err(FsError::InvalidOperation);
+                }
+                current = self.parent_cluster(&current)?;
+            }
+        }
+
+        entry.filename = filename;
+        entry.long_name = long_name;
+        self.insert_dir_entry(&parent, &entry)?;
+        self.delete_dir_entry(&location, &lfn)?;
+
+        if is_dir {
+            // point ".." to the new parent
+            let location = EntryLocation::new(self.cluster_to_sector(&entry.cluster), 1);
+            let mut block = Block::default();
+            self.inner.read_block(location.sector, &mut block)?;
+
+            let mut dotdot = DirEntry::parse(&block[location.range()])?;
+            dotdot.cluster = self.dotdot_cluster(&parent);
+            dotdot.write_to(&mut block.as_mut()[location.range()]);
+            self.inner.write_block(location.sector, &block)?;
+        }
+
+        Ok(())
     }
 }
 
-impl FileSystem for Fat16 {
-    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+impl<V: FatVariant> Drop for FatImpl<V> {
+    fn drop(&mut self) {
+        if let Err(e) = self.store_free_info() {
+            log::error!("Failed to store the free cluster count: {:?}", e);
+        }
+    }
+}
+
+/// Value of a raw FAT entry, without the reserved bits
+pub(super) fn parse_fat_entry<V: FatVariant>(raw: &[u8]) -> u32 {
+    let mut entry = [0u8; 4];
+    entry[..V::ENTRY_SIZE].copy_from_slice(&raw[..V::ENTRY_SIZE]);
+    u32::from_le_bytes(entry) & V::ENTRY_MASK
+}
+
+/// Split a path into its parent directory and the last component
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches(PATH_SEPARATOR);
+    match path.rfind(PATH_SEPARATOR) {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("", path),
+    }
+}
+
+/// "." and ".." are links kept by the directory itself, never removed,
+/// moved or created by name
+fn reject_dot_name(name: &str) -> FsResult {
+    match name {
+        "." | ".." => Err(FilenameError::MisplacedPeriod.into()),
+        _ => Ok(()),
+    }
+}
+
+impl<V: FatVariant> Fat<V> {
+    /// The entries of the directory at `path`, read as they are consumed
+    pub fn dir_entries(&self, path: &str) -> FsResult<DirIter<V>> {
         let dir = self.handle.get_parent_dir(path)?;
-        let mut entries = Vec::new();
 
-        self.handle.iterate_dir(&dir, |entry| {
-            entries.push(entry.as_meta());
-        })?;
+        if let Some(entry) = &dir.entry {
+            trace!("Iterating directory: {}", entry.filename());
+        }
+
+        Ok(DirIter::new(self.handle.clone(), &dir))
+    }
+}
+
+impl<V: FatVariant> FileSystem for Fat<V> {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let entries = self.dir_entries(path)?.filter_map(|entry| match entry {
+            Ok(entry) => Some(entry.as_meta()),
+            Err(e) => {
+                warn!("Failed to read directory entry: {:?}", e);
+                None
+            }
+        });
 
-        Ok(Box::new(entries.into_iter()))
+        Ok(Box::new(entries))
     }
 
     fn open_file(&self, path: &str) -> FsResult<FileHandle> {
-        let entry = self.handle.get_dir_entry(path)?;
+        let (entry, location, _) = self.handle.locate_dir_entry(path)?;
 
         if entry.is_directory() {
             return Err(FsError::NotAFile);
@@ -217,7 +873,7 @@ impl FileSystem for Fat16 {
 
         let handle = self.handle.clone();
         let meta = entry.as_meta();
-        let file = Box::new(File::new(handle, entry));
+        let file = Box::new(File::new(handle, entry, location));
 
         let file_handle = FileHandle::new(meta, file);
 
@@ -231,4 +887,83 @@ impl FileSystem for Fat16 {
     fn exists(&self, path: &str) -> FsResult<bool> {
         Ok(self.handle.get_dir_entry(path).is_ok())
     }
+
+    fn sync(&self) -> FsResult {
+        self.handle.sync()
+    }
+
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        Ok(FsStat {
+            block_size: self.handle.cluster_size(),
+            total_blocks: self.handle.cluster_count(),
+            free_blocks: self.handle.free_clusters()?,
+            max_name_len: lfn::LFN_MAX_LEN,
+            read_only: false,
+        })
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (entry, location) = self.handle.transaction(|| self.handle.create_file(path))?;
+
+        let handle = self.handle.clone();
+        let meta = entry.as_meta();
+        let file = Box::new(File::new(handle, entry, location));
+
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        let mut file_handle = self.open_file(path)?;
+        file_handle.seek(SeekFrom::End(0))?;
+        Ok(file_handle)
+    }
+
+    fn truncate_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (entry, location) = self
+            .handle
+            .transaction(|| self.handle.truncate_file(path))?;
+
+        let handle = self.handle.clone();
+        let meta = entry.as_meta();
+        let file = Box::new(File::new(handle, entry, location));
+
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.handle.transaction(|| self.handle.remove_file(path))
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.handle.transaction(|| self.handle.create_dir(path))
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        self.handle.transaction(|| self.handle.remove_dir(path))
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        let mut src = self.open_file(src)?;
+        let mut dst = self.create_file(dst)?;
+
+        let mut buf = vec![0u8; self.handle.cluster_size()];
+        loop {
+            match src.read(&mut buf)? {
+                0 => break,
+                n => dst.write_all(&buf[..n])?,
+            }
+        }
+
+        dst.flush()
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.handle
+            .transaction(|| self.handle.rename(src, dst, false))
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        self.handle
+            .transaction(|| self.handle.rename(src, dst, true))
+    }
 }
@@ -0,0 +1,244 @@
+//! VFAT Long File Name
+//!
+//! A long name is stored in a run of special entries right before the 8.3
+//! entry it belongs to, the last part of the name comes first.
+//!
+//! reference:
+//! - <https://wiki.osdev.org/FAT#Long_File_Names>
+//! - <https://en.wikipedia.org/wiki/Design_of_the_FAT_file_system#VFAT_long_file_names>
+
+use super::*;
+
+/// Characters stored in one long file name entry
+pub const LFN_CHARS: usize = 13;
+
+/// Longest name (in UTF-16 units) a LFN sequence can hold
+pub const LFN_MAX_LEN: usize = 255;
+
+/// Offsets of the UTF-16 characters in a long file name entry
+const CHAR_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Set in the sequence number of the first (last in name) entry
+const LAST_ENTRY: u8 = 0x40;
+
+/// Checksum of the short name, stored in every LFN entry of the sequence
+pub fn checksum(sfn: &ShortFileName) -> u8 {
+    sfn.name
+        .iter()
+        .chain(sfn.ext.iter())
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Collects the long file name entries preceding a short entry
+#[derive(Debug, Default)]
+pub struct LfnBuilder {
+    chars: Vec<u16>,
+    checksum: u8,
+    /// Sequence number expected for the next entry, 0 when complete
+    expected: u8,
+    slots: Vec<EntryLocation>,
+}
+
+impl LfnBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget the current sequence
+    pub fn reset(&mut self) {
+        self.chars.clear();
+        self.slots.clear();
+        self.expected = 0;
+    }
+
+    /// Feed a raw LFN entry, broken sequences are dropped
+    pub fn push(&mut self, data: &[u8], location: EntryLocation) {
+        let seq = data[0] & !LAST_ENTRY;
+
+        if data[0] & LAST_ENTRY != 0 {
+            self.reset();
+            self.chars.resize(seq as usize * LFN_CHARS, 0xFFFF);
+            self.checksum = data[13];
+            self.expected = seq;
+        }
+
+        if seq == 0 || seq != self.expected || data[13] != self.checksum {
+            self.reset();
+            return;
+        }
+
+        let start = (seq as usize - 1) * LFN_CHARS;
+        for (i, offset) in CHAR_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = u16::from_le_bytes([data[*offset], data[offset + 1]]);
+        }
+
+        self.expected -= 1;
+        self.slots.push(location);
+    }
+
+    /// Take the assembled name if it belongs to the given short entry,
+    /// along with the slots it was read from
+    pub fn finish(&mut self, sfn: &ShortFileName) -> Option<(String, Vec<EntryLocation>)> {
+        let complete = !self.chars.is_empty() && self.expected == 0;
+        let matches = complete && self.checksum == checksum(sfn);
+
+        let chars = core::mem::take(&mut self.chars);
+        let slots = core::mem::take(&mut self.slots);
+        self.reset();
+
+        if !matches {
+            return None;
+        }
+
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        Some((String::from_utf16_lossy(&chars[..len]), slots))
+    }
+}
+
+/// Whether `name` has to be stored with a long file name
+pub fn needs_lfn(name: &str) -> bool {
+    match ShortFileName::parse(name) {
+        Ok(sfn) => format!("{}", sfn) != name,
+        Err(_) => true,
+    }
+}
+
+/// Characters that cannot be used in a short name
+fn is_invalid_sfn_char(ch: char) -> bool {
+    !ch.is_ascii_graphic() || "\"*+,./:;<=>?[\\]|".contains(ch)
+}
+
+/// Build the lossy 8.3 basis of a long name, e.g. "Hello World.txt" becomes
+/// "HELLOWOR.TXT" before a numeric tail is added.
+pub fn basis_name(name: &str) -> ([u8; 8], [u8; 3]) {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rfind('.') {
+        Some(idx) => (&name[..idx], &name[idx + 1..]),
+        None => (name, ""),
+    };
+
+    let convert = |s: &str, out: &mut [u8]| {
+        let chars = s.chars().filter(|&c| c != ' ' && c != '.').map(|c| {
+            if is_invalid_sfn_char(c) {
+                b'_'
+            } else {
+                c.to_ascii_uppercase() as u8
+            }
+        });
+
+        for (slot, ch) in out.iter_mut().zip(chars) {
+            *slot = ch;
+        }
+    };
+
+    let mut sfn_name = [0x20; 8];
+    let mut sfn_ext = [0x20; 3];
+    convert(base, &mut sfn_name);
+    convert(ext, &mut sfn_ext);
+
+    if sfn_name[0] == 0x20 {
+        sfn_name[0] = b'_';
+    }
+
+    (sfn_name, sfn_ext)
+}
+
+/// Add a "~N" tail to a basis name
+pub fn with_tail(basis: &([u8; 8], [u8; 3]), n: usize) -> ShortFileName {
+    let tail = format!("~{}", n);
+    let len = basis.0.iter().position(|&c| c == 0x20).unwrap_or(8);
+    let keep = len.min(8 - tail.len());
+
+    let mut name = [0x20; 8];
+    name[..keep].copy_from_slice(&basis.0[..keep]);
+    name[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+
+    ShortFileName { name, ext: basis.1 }
+}
+
+/// Raw LFN entries for `name`, in the order they are stored on disk
+pub fn lfn_entries(name: &str, sfn: &ShortFileName) -> FsResult<Vec<[u8; DirEntry::LEN]>> {
+    let mut chars: Vec<u16> = name.encode_utf16().collect();
+
+    if chars.len() > LFN_MAX_LEN {
+        return Err(FilenameError::NameTooLong.into());
+    }
+
+    let count = chars.len().div_ceil(LFN_CHARS);
+
+    // terminated by 0x0000 and padded with 0xFFFF
+    if chars.len() < count * LFN_CHARS {
+        chars.push(0);
+        chars.resize(count * LFN_CHARS, 0xFFFF);
+    }
+
+    let checksum = checksum(sfn);
+
+    Ok((1..=count)
+        .rev()
+        .map(|seq| {
+            let mut data = [0u8; DirEntry::LEN];
+            data[0] = seq as u8 | if seq == count { LAST_ENTRY } else { 0 };
+            data[11] = Attributes::LFN.bits();
+            data[13] = checksum;
+
+            let part = &chars[(seq - 1) * LFN_CHARS..seq * LFN_CHARS];
+            for (ch, offset) in part.iter().zip(CHAR_OFFSETS) {
+                data[offset..offset + 2].copy_from_slice(&ch.to_le_bytes());
+            }
+
+            data
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfn_checksum() {
+        let sfn = ShortFileName::new(b"LONGFI~1TXT");
+        let entries = lfn_entries("LongFileName.txt", &sfn).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0][0], 0x42);
+        assert_eq!(entries[1][0], 0x01);
+        assert_eq!(entries[0][13], checksum(&sfn));
+        assert_eq!(checksum(&ShortFileName::new(b"README  TXT")), 0x73);
+
+        let mut builder = LfnBuilder::new();
+        for (i, entry) in entries.iter().enumerate() {
+            builder.push(entry, EntryLocation::new(0, i));
+        }
+
+        let (name, slots) = builder.finish(&sfn).unwrap();
+        assert_eq!(name, "LongFileName.txt");
+        assert_eq!(slots.len(), 2);
+
+        // checksum mismatch
+        for (i, entry) in entries.iter().enumerate() {
+            builder.push(entry, EntryLocation::new(0, i));
+        }
+        assert!(
+            builder
+                .finish(&ShortFileName::new(b"OTHER   TXT"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_basis_name() {
+        let basis = basis_name("Hello World.tar.gz");
+        assert_eq!(format!("{}", with_tail(&basis, 1)), "HELLOW~1.GZ");
+        assert_eq!(
+            format!("{}", with_tail(&basis_name(".bashrc"), 12)),
+            "BASHR~12"
+        );
+        assert_eq!(format!("{}", with_tail(&basis_name("a+b"), 1)), "A_B~1");
+
+        assert!(needs_lfn("hello.txt"));
+        assert!(needs_lfn("LONGFILENAME.TXT"));
+        assert!(!needs_lfn("HELLO.TXT"));
+    }
+}
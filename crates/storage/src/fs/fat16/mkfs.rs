@@ -0,0 +1,225 @@
+//! Format a volume as FAT16
+//!
+//! reference:
+//! - <https://academy.cba.mit.edu/classes/networking_communications/SD/FAT.pdf>
+//! - <https://wiki.osdev.org/FAT#BPB_.28BIOS_Parameter_Block.29>
+//!
+//! The layout is the usual one: one reserved sector for the boot sector, two
+//! FATs, the fixed-size root directory and the data area. The FAT size is
+//! computed with the formula from the Microsoft specification.
+
+use super::*;
+
+const RESERVED_SECTORS: usize = 1;
+const FAT_COUNT: usize = 2;
+const MEDIA_DESCRIPTOR: u8 = 0xF8;
+
+/// A volume with less clusters is FAT12, one with more is FAT32
+const MIN_CLUSTERS: usize = 4085;
+const MAX_CLUSTERS: usize = 65524;
+
+/// How the volume is laid out by [`format()`]
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Volume label, padded with spaces
+    pub label: [u8; 11],
+    /// Volume serial number
+    pub volume_id: u32,
+    /// Picked from the size of the volume if `None`
+    pub sectors_per_cluster: Option<u8>,
+    /// Entries in the root directory
+    pub root_entries: u16,
+    /// Sectors before the volume, the start of its partition
+    pub hidden_sectors: u32,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            label: *b"NO NAME    ",
+            volume_id: 0,
+            sectors_per_cluster: None,
+            root_entries: 512,
+            hidden_sectors: 0,
+        }
+    }
+}
+
+/// Cluster size suggested by the specification for a volume of `sectors`
+fn default_sectors_per_cluster(sectors: usize) -> FsResult<u8> {
+    Ok(match sectors {
+        0..=8400 => 1,
+        8401..=32680 => 2,
+        32681..=262144 => 4,
+        262145..=524288 => 8,
+        524289..=1048576 => 16,
+        1048577..=2097152 => 32,
+        2097153..=4194304 => 64,
+        _ => return Err(FsError::NotSupported),
+    })
+}
+
+/// Write an empty FAT16 filesystem onto `device`, the whole device is used
+///
+/// Only the boot sector, the FATs and the root directory are written, the
+/// data area keeps its content.
+pub fn format(device: &dyn BlockDevice<Block512>, options: &FormatOptions) -> FsResult {
+    let total = device.block_count()?;
+    let spc = match options.sectors_per_cluster {
+        Some(spc) if spc.is_power_of_two() && spc <= 128 => spc,
+        Some(_) => return Err(FsError::InvalidOperation),
+        None => default_sectors_per_cluster(total)?,
+    };
+
+    let root_dir_sectors = (options.root_entries as usize * DirEntry::LEN).div_ceil(BLOCK_SIZE);
+    let fat_size = total
+        .saturating_sub(RESERVED_SECTORS + root_dir_sectors)
+        .div_ceil(256 * spc as usize + FAT_COUNT);
+
+    let data_start = RESERVED_SECTORS + FAT_COUNT * fat_size + root_dir_sectors;
+    let clusters = total.saturating_sub(data_start) / spc as usize;
+
+    if !(MIN_CLUSTERS..=MAX_CLUSTERS).contains(&clusters) || fat_size > u16::MAX as usize {
+        return Err(FsError::NotSupported);
+    }
+
+    trace!(
+        "Formatting FAT16: {} sectors, {} clusters of {} sectors, FAT size {}",
+        total, clusters, spc, fat_size
+    );
+
+    let boot = boot_sector(total, spc, fat_size, options);
+    debug_assert!(Fat16Bpb::new(&boot).is_ok());
+    device.write_block(0, &Block512::new(&boot))?;
+
+    let zero = Block512::default();
+    for sector in RESERVED_SECTORS..data_start {
+        device.write_block(sector, &zero)?;
+    }
+
+    // the first two entries hold the media descriptor and the end of chain
+    let mut first = Block512::default();
+    first.as_mut()[..4].copy_from_slice(&[MEDIA_DESCRIPTOR, 0xFF, 0xFF, 0xFF]);
+    for fat in 0..FAT_COUNT {
+        device.write_block(RESERVED_SECTORS + fat * fat_size, &first)?;
+    }
+
+    device.flush()
+}
+
+fn boot_sector(total: usize, spc: u8, fat_size: usize, options: &FormatOptions) -> [u8; 512] {
+    let mut data = [0u8; 512];
+
+    data[..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+    data[0x03..0x0b].copy_from_slice(b"YSOS    ");
+    data[0x0b..0x0d].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+    data[0x0d] = spc;
+    data[0x0e..0x10].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+    data[0x10] = FAT_COUNT as u8;
+    data[0x11..0x13].copy_from_slice(&options.root_entries.to_le_bytes());
+
+    if total < 0x10000 {
+        data[0x13..0x15].copy_from_slice(&(total as u16).to_le_bytes());
+    } else {
+        data[0x20..0x24].copy_from_slice(&(total as u32).to_le_bytes());
+    }
+
+    data[0x15] = MEDIA_DESCRIPTOR;
+    data[0x16..0x18].copy_from_slice(&(fat_size as u16).to_le_bytes());
+    // a made up geometry, nobody uses CHS anymore
+    data[0x18..0x1a].copy_from_slice(&32u16.to_le_bytes());
+    data[0x1a..0x1c].copy_from_slice(&64u16.to_le_bytes());
+    data[0x1c..0x20].copy_from_slice(&options.hidden_sectors.to_le_bytes());
+
+    data[0x24] = 0x80;
+    data[0x26] = 0x29;
+    data[0x27..0x2b].copy_from_slice(&options.volume_id.to_le_bytes());
+    data[0x2b..0x36].copy_from_slice(&options.label);
+    data[0x36..0x3e].copy_from_slice(b"FAT16   ");
+    data[0x1fe..].copy_from_slice(&[0x55, 0xAA]);
+
+    data
+}
+
+impl Fat16 {
+    /// Format `device` and open the new filesystem
+    pub fn format(device: impl BlockDevice<Block512>, options: &FormatOptions) -> FsResult<Self> {
+        format(&device, options)?;
+        Ok(Self::new(device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let disk = RamDisk::new(16 * 1024 * 1024);
+        let options = FormatOptions {
+            label: *b"YSOS TEST  ",
+            volume_id: 0x1234_5678,
+            ..Default::default()
+        };
+
+        let fs = Fat16::format(disk.clone(), &options).unwrap();
+
+        let bpb = &fs.handle.bpb;
+        assert_eq!(bpb.total_sectors(), 32768);
+        assert_eq!(bpb.sectors_per_cluster(), 4);
+        assert_eq!(bpb.volume_label(), b"YSOS TEST  ");
+        assert_eq!(bpb.system_identifier_str(), "FAT16   ");
+
+        // every cluster fits in the FAT
+        let entries = bpb.sectors_per_fat() as usize * BLOCK_SIZE / 2;
+        assert!(fs.handle.cluster_count() + 2 <= entries);
+
+        assert_eq!(fs.read_dir("/").unwrap().count(), 0);
+        let stat = fs.statfs("/").unwrap();
+        assert_eq!(stat.block_size, 2048);
+        assert_eq!(stat.free_blocks, stat.total_blocks);
+
+        fs.create_dir("/DIR").unwrap();
+        fs.create_file("/DIR/a file.txt")
+            .unwrap()
+            .write_all(b"formatted")
+            .unwrap();
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, stat.total_blocks - 2);
+
+        // counted from the FAT this time
+        let fs = Fat16::new(disk);
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, stat.total_blocks - 2);
+        fs.remove_file("/DIR/a file.txt").unwrap();
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, stat.total_blocks - 1);
+        fs.create_file("/DIR/a file.txt")
+            .unwrap()
+            .write_all(b"formatted")
+            .unwrap();
+
+        let mut buf = Vec::new();
+        fs.open_file("/dir/A FILE.TXT")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"formatted");
+    }
+
+    #[test]
+    fn test_format_size() {
+        // too few clusters for FAT16
+        let small = RamDisk::new(1024 * 1024);
+        assert!(format(&small, &FormatOptions::default()).is_err());
+
+        let options = FormatOptions {
+            sectors_per_cluster: Some(3),
+            ..Default::default()
+        };
+        assert!(format(&RamDisk::new(16 * 1024 * 1024), &options).is_err());
+
+        // the 32-bit sector count
+        let disk = RamDisk::new(64 * 1024 * 1024);
+        let fs = Fat16::format(disk, &FormatOptions::default()).unwrap();
+        assert_eq!(fs.handle.bpb.total_sectors_16(), 0);
+        assert_eq!(fs.handle.bpb.total_sectors(), 131072);
+    }
+}
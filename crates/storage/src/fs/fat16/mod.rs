@@ -1,8 +1,23 @@
+//! FAT16 and the parts of the driver shared with FAT32
+//!
+//! Both variants lay out directories, cluster chains and files the same way.
+//! [`Fat`] implements all of it once, [`FatVariant`] describes the rest: the
+//! width of a FAT entry, where the root directory lives and where the free
+//! cluster count is kept.
+
 pub mod bpb;
 pub mod directory;
 pub mod direntry;
 pub mod file;
+pub mod fsck;
 pub mod impls;
+pub mod lfn;
+pub mod mkfs;
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicBool, AtomicUsize},
+};
 
 use bpb::Fat16Bpb;
 use directory::Directory;
@@ -13,39 +28,157 @@ use crate::*;
 
 const BLOCK_SIZE: usize = 512;
 
+/// What sets a FAT variant apart, read from its boot sector
+pub trait FatVariant: core::fmt::Debug + Send + Sync + Sized + 'static {
+    /// Name of the variant, for logs
+    const NAME: &'static str;
+    /// Bytes in one FAT entry
+    const ENTRY_SIZE: usize;
+    /// Bits of a FAT entry that hold the next cluster, the others are kept
+    const ENTRY_MASK: u32;
+    /// FAT entry of a cluster known to be bad
+    const BAD_CLUSTER: u32 = Self::ENTRY_MASK - 8;
+    /// FAT entries from this one on end a chain
+    const END_OF_CHAIN: u32 = Self::ENTRY_MASK - 7;
+
+    /// Parse the boot sector
+    fn parse(data: &[u8]) -> Result<Self, &'static str>;
+
+    /// Sectors before the first FAT
+    fn reserved_sectors(&self) -> usize;
+    /// Number of FAT copies
+    fn fats(&self) -> usize;
+    /// Sectors in one FAT copy
+    fn fat_sectors(&self) -> usize;
+    /// Sectors in one cluster
+    fn cluster_sectors(&self) -> usize;
+    /// Sectors in the volume
+    fn volume_sectors(&self) -> usize;
+
+    /// Sectors of the fixed-size root directory between the FATs and the
+    /// data area, none if the root directory is a cluster chain
+    fn root_dir_sectors(&self) -> usize {
+        0
+    }
+
+    /// First cluster of the root directory, `Cluster::ROOT_DIR` if it has a
+    /// fixed area
+    fn root_cluster(&self) -> Cluster {
+        Cluster::ROOT_DIR
+    }
+
+    /// FAT copies that are kept up to date
+    fn active_fats(&self) -> Range<usize> {
+        0..self.fats()
+    }
+
+    /// The free cluster count and where to look for a free cluster, if the
+    /// volume records them
+    fn load_free_info(&self, _inner: &dyn BlockDevice<Block512>) -> (Option<usize>, usize) {
+        (None, 2)
+    }
+
+    /// Record the free cluster count and the allocation hint on the volume
+    fn store_free_info(
+        &self,
+        _inner: &dyn BlockDevice<Block512>,
+        _free: Option<usize>,
+        _hint: usize,
+    ) -> FsResult {
+        Ok(())
+    }
+}
+
+/// Identifies a FAT volume on the disk.
+pub struct Fat<V: FatVariant> {
+    handle: FatHandle<V>,
+}
+
 /// Identifies a Fat16 Volume on the disk.
-pub struct Fat16 {
-    handle: Fat16Handle,
+pub type Fat16 = Fat<Fat16Bpb>;
+
+impl FatVariant for Fat16Bpb {
+    const NAME: &'static str = "Fat16";
+    const ENTRY_SIZE: usize = 2;
+    const ENTRY_MASK: u32 = 0xFFFF;
+
+    fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        Fat16Bpb::new(data)
+    }
+
+    fn reserved_sectors(&self) -> usize {
+        self.reserved_sector_count() as usize
+    }
+
+    fn fats(&self) -> usize {
+        self.fat_count() as usize
+    }
+
+    fn fat_sectors(&self) -> usize {
+        self.sectors_per_fat() as usize
+    }
+
+    fn cluster_sectors(&self) -> usize {
+        self.sectors_per_cluster() as usize
+    }
+
+    fn volume_sectors(&self) -> usize {
+        self.total_sectors() as usize
+    }
+
+    fn root_dir_sectors(&self) -> usize {
+        (self.root_entries_count() as usize * DirEntry::LEN).div_ceil(BLOCK_SIZE)
+    }
 }
 
-impl Fat16 {
+impl<V: FatVariant> Fat<V> {
     pub fn new(inner: impl BlockDevice<Block512>) -> Self {
         Self {
-            handle: Arc::new(Fat16Impl::new(inner)),
+            handle: Arc::new(FatImpl::new(inner)),
+        }
+    }
+}
+
+impl<V: FatVariant> Clone for Fat<V> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
         }
     }
 }
 
-type Fat16Handle = Arc<Fat16Impl>;
+type FatHandle<V> = Arc<FatImpl<V>>;
 
-pub struct Fat16Impl {
+pub struct FatImpl<V: FatVariant> {
     pub(crate) inner: Box<dyn BlockDevice<Block512>>,
-    pub bpb: Fat16Bpb,
+    pub bpb: V,
     pub fat_start: usize,
     pub first_data_sector: usize,
     pub first_root_dir_sector: usize,
+    /// First cluster of the root directory, `Cluster::ROOT_DIR` if it has a
+    /// fixed area
+    pub root_cluster: Cluster,
+    /// Where to start looking for a free cluster
+    free_hint: AtomicUsize,
+    /// Number of free clusters, counted when first asked if the volume does
+    /// not record it
+    free_count: AtomicUsize,
+    /// Whether the count or the hint changed since they were last stored
+    free_info_dirty: AtomicBool,
+    /// Where the entries of the open files are, once per handle
+    open_files: spin::Mutex<Vec<EntryLocation>>,
 }
 
-impl core::fmt::Debug for Fat16 {
+impl<V: FatVariant> core::fmt::Debug for Fat<V> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Fat16")
+        f.debug_struct(V::NAME)
             .field("bpb", &self.handle.bpb)
             .finish()
     }
 }
 
-impl core::fmt::Debug for Fat16Impl {
+impl<V: FatVariant> core::fmt::Debug for FatImpl<V> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Fat16Impl").field("bpb", &self.bpb).finish()
+        f.debug_struct("FatImpl").field("bpb", &self.bpb).finish()
     }
 }
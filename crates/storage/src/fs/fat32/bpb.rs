@@ -0,0 +1,230 @@
+//! Fat32 BIOS Parameter Block and FSInfo sector
+//!
+//! reference:
+//! - <https://en.wikipedia.org/wiki/Design_of_the_FAT_file_system#FAT32_Extended_BIOS_Parameter_Block>
+//! - <https://wiki.osdev.org/FAT#FAT_32>
+//! - <https://github.com/rust-embedded-community/embedded-sdmmc-rs/blob/develop/src/fat/bpb.rs>
+
+/// Represents a Boot Parameter Block. This is the first sector of a FAT 32
+/// formatted partition. The common part is shared with FAT 16, followed by
+/// the FAT 32 Extended BIOS Parameter Block.
+pub struct Fat32Bpb {
+    data: [u8; 512],
+}
+
+impl Fat32Bpb {
+    /// Attempt to parse a Boot Parameter Block from a 512 byte sector, only
+    /// succeeds if the volume is formatted as FAT 32.
+    pub fn new(data: &[u8]) -> Result<Fat32Bpb, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad BPB size")?;
+        let bpb = Fat32Bpb { data };
+
+        if bpb.trail() != 0xAA55 {
+            return Err("Bad BPB format");
+        }
+
+        // FAT 32 has no fixed root directory and no 16-bit FAT size
+        if bpb.root_entries_count() != 0
+            || bpb.sectors_per_fat_16() != 0
+            || bpb.sectors_per_fat() == 0
+        {
+            return Err("Not a FAT32 volume");
+        }
+
+        Ok(bpb)
+    }
+
+    pub fn total_sectors(&self) -> u32 {
+        if self.total_sectors_16() == 0 {
+            self.total_sectors_32()
+        } else {
+            self.total_sectors_16() as u32
+        }
+    }
+
+    /// Whether every FAT copy is updated, otherwise only the active one is
+    pub fn fat_mirroring(&self) -> bool {
+        self.ext_flags() & 0x80 == 0
+    }
+
+    /// The only FAT in use when mirroring is disabled
+    pub fn active_fat(&self) -> u8 {
+        (self.ext_flags() & 0x0f) as u8
+    }
+
+    define_field!([u8; 8], 0x03, oem_name);
+    define_field!(u16, 0x0b, bytes_per_sector);
+    define_field!(u8, 0x0d, sectors_per_cluster);
+    define_field!(u16, 0x0e, reserved_sector_count);
+    define_field!(u8, 0x10, fat_count);
+    define_field!(u16, 0x11, root_entries_count);
+    define_field!(u16, 0x13, total_sectors_16);
+    define_field!(u8, 0x15, media_descriptor);
+    define_field!(u16, 0x16, sectors_per_fat_16);
+    define_field!(u16, 0x18, sectors_per_track);
+    define_field!(u16, 0x1a, track_count);
+    define_field!(u32, 0x1c, hidden_sectors);
+    define_field!(u32, 0x20, total_sectors_32);
+    define_field!(u32, 0x24, sectors_per_fat);
+    define_field!(u16, 0x28, ext_flags);
+    define_field!(u16, 0x2a, fs_version);
+    define_field!(u32, 0x2c, root_cluster);
+    define_field!(u16, 0x30, fs_info_sector);
+    define_field!(u16, 0x32, backup_boot_sector);
+    define_field!(u8, 0x40, drive_number);
+    define_field!(u8, 0x41, reserved_flags);
+    define_field!(u8, 0x42, boot_signature);
+    define_field!(u32, 0x43, volume_id);
+    define_field!([u8; 11], 0x47, volume_label);
+    define_field!([u8; 8], 0x52, system_identifier);
+    define_field!(u16, 0x1fe, trail);
+}
+
+impl core::fmt::Debug for Fat32Bpb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32 BPB")
+            .field("OEM Name", &self.oem_name_str())
+            .field("Bytes per Sector", &self.bytes_per_sector())
+            .field("Sectors per Cluster", &self.sectors_per_cluster())
+            .field("Reserved Sector Count", &self.reserved_sector_count())
+            .field("FAT Count", &self.fat_count())
+            .field("Total Sectors", &self.total_sectors())
+            .field("Media Descriptor", &self.media_descriptor())
+            .field("Sectors per FAT", &self.sectors_per_fat())
+            .field("Sectors per Track", &self.sectors_per_track())
+            .field("Track Count", &self.track_count())
+            .field("Hidden Sectors", &self.hidden_sectors())
+            .field("Ext Flags", &self.ext_flags())
+            .field("FS Version", &self.fs_version())
+            .field("Root Cluster", &self.root_cluster())
+            .field("FSInfo Sector", &self.fs_info_sector())
+            .field("Backup Boot Sector", &self.backup_boot_sector())
+            .field("Drive Number", &self.drive_number())
+            .field("Reserved Flags", &self.reserved_flags())
+            .field("Boot Signature", &self.boot_signature())
+            .field("Volume ID", &self.volume_id())
+            .field("Volume Label", &self.volume_label_str())
+            .field("System Identifier", &self.system_identifier_str())
+            .field("Trail", &self.trail())
+            .finish()
+    }
+}
+
+/// The FSInfo sector, caches the free cluster count and where the last
+/// cluster was allocated. Both values are only hints and may be unknown
+/// (0xFFFFFFFF).
+pub struct FsInfo {
+    data: [u8; 512],
+}
+
+impl FsInfo {
+    pub const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+    /// Attempt to parse the FSInfo sector, checking all three signatures.
+    pub fn new(data: &[u8]) -> Result<FsInfo, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad FSInfo size")?;
+        let info = FsInfo { data };
+
+        if info.lead_signature() != 0x4161_5252
+            || info.struct_signature() != 0x6141_7272
+            || info.trail_signature() != 0xAA55_0000
+        {
+            return Err("Bad FSInfo signature");
+        }
+
+        Ok(info)
+    }
+
+    pub fn set_free_count(&mut self, count: u32) {
+        self.data[0x1e8..0x1ec].copy_from_slice(&count.to_le_bytes());
+    }
+
+    pub fn set_next_free(&mut self, cluster: u32) {
+        self.data[0x1ec..0x1f0].copy_from_slice(&cluster.to_le_bytes());
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 512] {
+        &self.data
+    }
+
+    define_field!(u32, 0x000, lead_signature);
+    define_field!(u32, 0x1e4, struct_signature);
+    define_field!(u32, 0x1e8, free_count);
+    define_field!(u32, 0x1ec, next_free);
+    define_field!(u32, 0x1fc, trail_signature);
+}
+
+impl core::fmt::Debug for FsInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FSInfo")
+            .field("Free Count", &self.free_count())
+            .field("Next Free", &self.next_free())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fat32_bpb() {
+        // 64 MiB volume, laid out the way mkfs.fat -F 32 does
+        const DATA: [u8; 96] = hex_literal::hex!(
+            "EB 58 90 6D 6B 66 73 2E 66 61 74 00 02 01 20 00
+            02 00 00 00 00 F8 00 00 20 00 08 00 00 00 00 00
+            00 00 02 00 00 04 00 00 00 00 00 00 02 00 00 00
+            01 00 06 00 00 00 00 00 00 00 00 00 00 00 00 00
+            80 00 29 EF BE AD DE 54 45 53 54 56 4F 4C 33 32
+            20 20 46 41 54 33 32 20 20 20 00 00 00 00 00 00"
+        );
+
+        let mut bpb_data = Vec::with_capacity(512);
+        bpb_data.extend_from_slice(&DATA);
+        bpb_data.resize(510, 0u8);
+        bpb_data.extend_from_slice(&[0x55, 0xAA]);
+
+        let bpb = Fat32Bpb::new(&bpb_data).unwrap();
+
+        assert_eq!(bpb.oem_name(), b"mkfs.fat");
+        assert_eq!(bpb.bytes_per_sector(), 512);
+        assert_eq!(bpb.sectors_per_cluster(), 1);
+        assert_eq!(bpb.reserved_sector_count(), 32);
+        assert_eq!(bpb.fat_count(), 2);
+        assert_eq!(bpb.root_entries_count(), 0);
+        assert_eq!(bpb.total_sectors(), 0x20000);
+        assert_eq!(bpb.sectors_per_fat(), 0x400);
+        assert!(bpb.fat_mirroring());
+        assert_eq!(bpb.root_cluster(), 2);
+        assert_eq!(bpb.fs_info_sector(), 1);
+        assert_eq!(bpb.backup_boot_sector(), 6);
+        assert_eq!(bpb.boot_signature(), 0x29);
+        assert_eq!(bpb.volume_id(), 0xdeadbeef);
+        assert_eq!(bpb.volume_label_str(), "TESTVOL32  ");
+        assert_eq!(bpb.system_identifier_str(), "FAT32   ");
+
+        // a FAT16 BPB has a fixed root directory
+        bpb_data[0x11] = 0x02;
+        assert!(Fat32Bpb::new(&bpb_data).is_err());
+
+        println!("{:#?}", bpb);
+    }
+
+    #[test]
+    fn test_fs_info() {
+        let mut data = [0u8; 512];
+        data[0x000..0x004].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        data[0x1e4..0x1e8].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        data[0x1fc..0x200].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+
+        let mut info = FsInfo::new(&data).unwrap();
+        info.set_free_count(1234);
+        info.set_next_free(FsInfo::UNKNOWN);
+
+        assert_eq!(info.free_count(), 1234);
+        assert_eq!(info.next_free(), FsInfo::UNKNOWN);
+
+        data[0] = 0;
+        assert!(FsInfo::new(&data).is_err());
+    }
+}
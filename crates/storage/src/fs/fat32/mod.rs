@@ -0,0 +1,115 @@
+//! Fat32 shares the directory layout with Fat16, but the root directory is an
+//! ordinary cluster chain and FAT entries are 28 bits wide.
+//!
+//! ```text
+//! [ BPB | FSInfo | Reserved ] [ FAT 1 ] [ FAT 2 ] [ Data ]
+//! ```
+//!
+//!   - The root directory starts at the cluster given in the BPB.
+//!   - FSInfo keeps the free cluster count and where to look for a free one,
+//!     both are written back when the volume is synced.
+//!
+//! Everything else is the driver in [`fat16`](crate::fat16).
+
+pub mod bpb;
+
+use core::ops::Range;
+
+use bpb::{Fat32Bpb, FsInfo};
+
+use crate::{
+    fat16::{Fat, FatVariant, direntry::Cluster},
+    *,
+};
+
+/// Identifies a Fat32 Volume on the disk.
+pub type Fat32 = Fat<Fat32Bpb>;
+
+impl Fat32Bpb {
+    /// Read the FSInfo sector, 0 or 0xFFFF in the BPB means there is none
+    fn read_fs_info(&self, inner: &dyn BlockDevice<Block512>) -> Option<(usize, FsInfo)> {
+        let sector = match self.fs_info_sector() as usize {
+            0 | 0xFFFF => return None,
+            sector => sector,
+        };
+
+        let mut block = Block512::default();
+        inner.read_block(sector, &mut block).ok()?;
+        FsInfo::new(block.as_ref()).ok().map(|info| (sector, info))
+    }
+}
+
+impl FatVariant for Fat32Bpb {
+    const NAME: &'static str = "Fat32";
+    const ENTRY_SIZE: usize = 4;
+    const ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+    fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        Fat32Bpb::new(data)
+    }
+
+    fn reserved_sectors(&self) -> usize {
+        self.reserved_sector_count() as usize
+    }
+
+    fn fats(&self) -> usize {
+        self.fat_count() as usize
+    }
+
+    fn fat_sectors(&self) -> usize {
+        self.sectors_per_fat() as usize
+    }
+
+    fn cluster_sectors(&self) -> usize {
+        self.sectors_per_cluster() as usize
+    }
+
+    fn volume_sectors(&self) -> usize {
+        self.total_sectors() as usize
+    }
+
+    fn root_cluster(&self) -> Cluster {
+        Cluster(Fat32Bpb::root_cluster(self))
+    }
+
+    fn active_fats(&self) -> Range<usize> {
+        if self.fat_mirroring() {
+            0..self.fat_count() as usize
+        } else {
+            let active = self.active_fat() as usize;
+            active..active + 1
+        }
+    }
+
+    fn load_free_info(&self, inner: &dyn BlockDevice<Block512>) -> (Option<usize>, usize) {
+        let info = self.read_fs_info(inner);
+        trace!("Fat32 FSInfo: {:?}", info);
+
+        match info {
+            Some((_, info)) => (
+                (info.free_count() != FsInfo::UNKNOWN).then_some(info.free_count() as usize),
+                info.next_free() as usize,
+            ),
+            None => (None, 2),
+        }
+    }
+
+    fn store_free_info(
+        &self,
+        inner: &dyn BlockDevice<Block512>,
+        free: Option<usize>,
+        hint: usize,
+    ) -> FsResult {
+        // a volume without a valid FSInfo does not get one
+        let Some((sector, mut info)) = self.read_fs_info(inner) else {
+            return Ok(());
+        };
+
+        info.set_free_count(free.map_or(FsInfo::UNKNOWN, |free| free as u32));
+        info.set_next_free(hint as u32);
+
+        let mut block = Block512::default();
+        block.as_mut().copy_from_slice(info.as_bytes());
+        inner.write_block(sector, &block)
+    }
+}
@@ -0,0 +1,257 @@
+//! Directory records and the names they carry
+//!
+//! reference:
+//! - <https://wiki.osdev.org/ISO_9660#Directories>
+//! - <https://studylib.net/doc/18849173/ieee-p1281-system-use-sharing-protocol>
+//! - <https://studylib.net/doc/18838383/ieee-p1282-rock-ridge-interchange-protocol>
+//!
+//! A directory is a run of records that never cross a sector, the rest of a
+//! sector is zero filled. The first two records are "." and "..", named
+//! `\0` and `\x01`. Rock Ridge keeps its data in the system use area after
+//! the name, as a list of SUSP entries.
+
+use chrono::{Duration, LocalResult::Single, TimeZone, Utc};
+
+use super::*;
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RecordFlags: u8 {
+        const HIDDEN = 0x01;
+        const DIRECTORY = 0x02;
+        const ASSOCIATED = 0x04;
+        const RECORD = 0x08;
+        const PROTECTION = 0x10;
+        const MULTI_EXTENT = 0x80;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirRecord {
+    /// First block of the data, after the extended attributes
+    pub extent: u32,
+    /// Length of the data in bytes
+    pub size: u32,
+    pub recorded: Option<FsTime>,
+    pub flags: RecordFlags,
+    /// Name as recorded
+    pub ident: Vec<u8>,
+    /// The system use area, where SUSP entries are
+    pub system_use: Vec<u8>,
+}
+
+impl DirRecord {
+    /// Parse the record at the start of `data`, `None` for the zero padding
+    /// at the end of a sector or a record that does not fit
+    pub fn parse(data: &[u8]) -> Option<DirRecord> {
+        let len = *data.first()? as usize;
+        let ident_len = *data.get(32)? as usize;
+
+        if len < 33 + ident_len || len > data.len() {
+            return None;
+        }
+
+        let both = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        // the name is padded to an even offset
+        let system_use = (33 + ident_len).next_multiple_of(2).min(len);
+
+        Some(DirRecord {
+            extent: both(2) + data[1] as u32,
+            size: both(10),
+            recorded: parse_datetime(&data[18..25]),
+            flags: RecordFlags::from_bits_truncate(data[25]),
+            ident: data[33..33 + ident_len].to_vec(),
+            system_use: data[system_use..len].to_vec(),
+        })
+    }
+
+    /// Bytes taken by the record at the start of `data`
+    pub fn len(data: &[u8]) -> usize {
+        data.first().copied().unwrap_or(0) as usize
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.flags.contains(RecordFlags::DIRECTORY)
+    }
+
+    /// The "." or ".." record
+    pub fn is_special(&self) -> bool {
+        matches!(self.ident[..], [0] | [1])
+    }
+
+    /// Blocks taken by the data
+    pub fn blocks(&self) -> usize {
+        (self.size as usize).div_ceil(BLOCK_SIZE)
+    }
+
+    pub fn as_meta(&self, name: String) -> Metadata {
+        let (kind, len) = if self.is_dir() {
+            (FileType::Directory, 0)
+        } else {
+            (FileType::File, self.size as usize)
+        };
+
+        Metadata::new(name, kind, len, self.recorded, self.recorded, None)
+    }
+}
+
+/// Seven bytes: years since 1900, month, day, hour, minute, second and the
+/// offset from GMT in 15 minute steps
+fn parse_datetime(data: &[u8]) -> Option<FsTime> {
+    let [year, month, day, hour, min, sec, gmt] = data.try_into().ok()?;
+
+    match Utc.with_ymd_and_hms(
+        1900 + year as i32,
+        month as u32,
+        day as u32,
+        hour as u32,
+        min as u32,
+        sec as u32,
+    ) {
+        Single(time) => Some(time - Duration::minutes(gmt as i8 as i64 * 15)),
+        _ => None,
+    }
+}
+
+/// A name as recorded on a volume without extensions, the ";1" version and
+/// the dot of names without extension are dropped
+pub fn iso_name(ident: &[u8]) -> String {
+    let name = String::from_utf8_lossy(ident);
+    let name = name.split(';').next().unwrap_or_default();
+    name.strip_suffix('.').unwrap_or(name).to_owned()
+}
+
+/// A Joliet name, UCS-2 big endian, may carry a version too
+pub fn joliet_name(ident: &[u8]) -> String {
+    let units = ident
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    let name: String = char::decode_utf16(units)
+        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+
+    match name.split_once(';') {
+        Some((name, _)) => name.to_owned(),
+        None => name,
+    }
+}
+
+/// The SUSP entries of a system use area: signature and data
+pub struct SuspEntries<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SuspEntries<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for SuspEntries<'a> {
+    type Item = ([u8; 2], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // signature, length and version come first
+        if self.data.len() < 4 {
+            return None;
+        }
+
+        let len = self.data[2] as usize;
+        if len < 4 || len > self.data.len() {
+            return None;
+        }
+
+        let entry = ([self.data[0], self.data[1]], &self.data[4..len]);
+        self.data = &self.data[len..];
+
+        // "ST" ends the area
+        (&entry.0 != b"ST").then_some(entry)
+    }
+}
+
+/// Where a "CE" entry continues the system use area
+pub fn continuation(data: &[u8]) -> Option<(u32, u32, u32)> {
+    let both = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    Some((both(0)?, both(8)?, both(16)?))
+}
+
+/// What Rock Ridge says about a record
+#[derive(Debug, Default)]
+pub struct RockRidge {
+    /// The POSIX name, from the "NM" entries
+    pub name: Option<String>,
+    /// Moved away from a too deep directory, "RE"
+    pub relocated: bool,
+}
+
+impl RockRidge {
+    /// Add the entries of one system use area
+    pub fn parse(&mut self, area: &[u8]) {
+        let mut name = self.name.take().map(String::into_bytes);
+
+        for (signature, data) in SuspEntries::new(area) {
+            match &signature {
+                b"NM" if !data.is_empty() => {
+                    // "." and ".." are flagged instead of named
+                    let flags = data[0];
+                    if flags & 0x06 == 0 {
+                        name.get_or_insert_default().extend_from_slice(&data[1..]);
+                    }
+                }
+                b"RE" => self.relocated = true,
+                _ => {}
+            }
+        }
+
+        self.name = name.map(|name| String::from_utf8_lossy(&name).into_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(iso_name(b"HELLO.TXT;1"), "HELLO.TXT");
+        assert_eq!(iso_name(b"EMPTY.;1"), "EMPTY");
+        assert_eq!(iso_name(b"SUB"), "SUB");
+
+        let ucs2: Vec<u8> = "Long File Name.txt;1"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        assert_eq!(joliet_name(&ucs2), "Long File Name.txt");
+    }
+
+    #[test]
+    fn test_rock_ridge() {
+        let mut area = Vec::new();
+        area.extend_from_slice(b"PX\x24\x01");
+        area.extend_from_slice(&[0; 32]);
+        area.extend_from_slice(b"NM\x0b\x01\x01long n");
+        area.extend_from_slice(b"NM\x08\x01\x00ame");
+        area.extend_from_slice(b"RE\x04\x01");
+
+        let mut rr = RockRidge::default();
+        rr.parse(&area);
+        assert_eq!(rr.name.as_deref(), Some("long name"));
+        assert!(rr.relocated);
+
+        // the name of "." is a flag
+        let mut rr = RockRidge::default();
+        rr.parse(b"NM\x05\x01\x02");
+        assert_eq!(rr.name, None);
+    }
+
+    #[test]
+    fn test_datetime() {
+        // 2025-06-16 18:00:00 at GMT+8
+        let time = parse_datetime(&[125, 6, 16, 18, 0, 0, 32]).unwrap();
+        assert_eq!(time, Utc.with_ymd_and_hms(2025, 6, 16, 10, 0, 0).unwrap());
+        assert_eq!(parse_datetime(&[0; 7]), None);
+    }
+}
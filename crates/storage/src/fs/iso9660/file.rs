@@ -0,0 +1,80 @@
+//! File
+//!
+//! The data of a file is one contiguous extent, so the offset maps straight
+//! to a sector.
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct File {
+    /// The current offset in the file.
+    offset: usize,
+    /// Directory record of this file
+    record: DirRecord,
+    /// The file system handle that contains this file.
+    handle: Iso9660Handle,
+}
+
+impl File {
+    pub fn new(handle: Iso9660Handle, record: DirRecord) -> Self {
+        Self {
+            offset: 0,
+            record,
+            handle,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.record.size as usize
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let length = self.length();
+
+        let mut block = Block2048::default();
+        let mut bytes_read = 0;
+
+        while bytes_read < buf.len() && self.offset < length {
+            let sector = self.record.extent as usize + self.offset / BLOCK_SIZE;
+            self.handle.inner.read_block(sector, &mut block)?;
+
+            let current_offset = self.offset % BLOCK_SIZE;
+            let to_read = (buf.len() - bytes_read)
+                .min(BLOCK_SIZE - current_offset)
+                .min(length - self.offset);
+
+            buf[bytes_read..bytes_read + to_read]
+                .copy_from_slice(&block[current_offset..current_offset + to_read]);
+
+            bytes_read += to_read;
+            self.offset += to_read;
+        }
+
+        Ok(bytes_read)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        self.offset = offset.ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
@@ -0,0 +1,325 @@
+use core::ops::ControlFlow;
+
+use super::*;
+
+/// Continuation areas followed for one record, against loops
+const MAX_CONTINUATIONS: usize = 16;
+
+impl Iso9660Impl {
+    pub fn new(inner: impl BlockDevice<Block2048>) -> FsResult<Self> {
+        let mut block = Block2048::default();
+        let mut primary = None;
+        let mut joliet = None;
+
+        for sector in 16..inner.block_count()? {
+            inner.read_block(sector, &mut block)?;
+            let descriptor =
+                VolumeDescriptor::new(block.as_ref()).map_err(|_| FsError::InvalidVolume)?;
+
+            match descriptor.type_code() {
+                VolumeDescriptor::TERMINATOR => break,
+                VolumeDescriptor::PRIMARY if primary.is_none() => primary = Some(descriptor),
+                VolumeDescriptor::SUPPLEMENTARY if joliet.is_none() && descriptor.is_joliet() => {
+                    joliet = Some(descriptor)
+                }
+                _ => {}
+            }
+        }
+
+        let primary = primary.ok_or(FsError::InvalidVolume)?;
+        if primary.logical_block_size() as usize != BLOCK_SIZE {
+            return Err(FsError::NotSupported);
+        }
+
+        trace!("Loading ISO 9660 Volume: {:#?}", primary);
+
+        let (volume, names) = match (rock_ridge(&inner, &primary.root_record()?)?, joliet) {
+            (Some(skip), _) => (primary, Names::RockRidge { skip }),
+            (None, Some(joliet)) => (joliet, Names::Joliet),
+            (None, None) => (primary, Names::Iso),
+        };
+
+        // the size comes from the disk, it has to fit in the volume
+        let size = volume.path_table_size() as usize;
+        if size > volume.volume_space_size() as usize * BLOCK_SIZE {
+            return Err(FsError::InvalidVolume);
+        }
+
+        let mut blocks = vec![Block2048::default(); size.div_ceil(BLOCK_SIZE)];
+        inner.read_blocks(volume.l_path_table() as usize, &mut blocks)?;
+        let data: Vec<u8> = blocks
+            .iter()
+            .flat_map(|block| block.iter().copied())
+            .collect();
+        let path_table = parse_path_table(&data[..size])?;
+
+        Ok(Self {
+            root: volume.root_record()?,
+            inner: Box::new(inner),
+            volume,
+            names,
+            path_table,
+        })
+    }
+
+    /// Walk the records of a directory in order, "." and ".." included,
+    /// until `func` breaks
+    fn walk_dir<T, F>(&self, dir: &DirRecord, mut func: F) -> FsResult<Option<T>>
+    where
+        F: FnMut(DirRecord) -> FsResult<ControlFlow<T>>,
+    {
+        let mut block = Block2048::default();
+        let start = dir.extent as usize;
+
+        for sector in start..start + dir.blocks() {
+            self.inner.read_block(sector, &mut block)?;
+
+            let mut offset = 0;
+            while offset < BLOCK_SIZE {
+                // the rest of the sector is padding
+                let Some(record) = DirRecord::parse(&block[offset..]) else {
+                    break;
+                };
+                offset += DirRecord::len(&block[offset..]);
+
+                if let ControlFlow::Break(ret) = func(record)? {
+                    return Ok(Some(ret));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Walk the entries of a directory with their names, until `func` breaks
+    pub(super) fn walk_entries<T, F>(&self, dir: &DirRecord, mut func: F) -> FsResult<Option<T>>
+    where
+        F: FnMut(String, DirRecord) -> ControlFlow<T>,
+    {
+        self.walk_dir(dir, |record| {
+            if record.is_special() {
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            Ok(match self.name_of(&record)? {
+                Some(name) => func(name, record),
+                None => ControlFlow::Continue(()),
+            })
+        })
+    }
+
+    /// The name shown for a record, `None` if it should be hidden
+    fn name_of(&self, record: &DirRecord) -> FsResult<Option<String>> {
+        Ok(match self.names {
+            Names::Iso => Some(iso_name(&record.ident)),
+            Names::Joliet => Some(joliet_name(&record.ident)),
+            Names::RockRidge { skip } => {
+                let rr = self.rock_ridge(record, skip)?;
+                (!rr.relocated).then(|| rr.name.unwrap_or_else(|| iso_name(&record.ident)))
+            }
+        })
+    }
+
+    /// Collect the Rock Ridge entries of a record, following "CE" entries
+    fn rock_ridge(&self, record: &DirRecord, skip: usize) -> FsResult<RockRidge> {
+        let mut rr = RockRidge::default();
+        let mut area = record.system_use.get(skip..).unwrap_or_default().to_vec();
+        let mut block = Block2048::default();
+
+        for _ in 0..MAX_CONTINUATIONS {
+            rr.parse(&area);
+
+            let next = SuspEntries::new(&area)
+                .find(|(signature, _)| signature == b"CE")
+                .and_then(|(_, data)| continuation(data));
+            let Some((sector, offset, len)) = next else {
+                break;
+            };
+
+            self.inner.read_block(sector as usize, &mut block)?;
+            area = block
+                .get(offset as usize..(offset + len) as usize)
+                .ok_or(FsError::InvalidVolume)?
+                .to_vec();
+        }
+
+        Ok(rr)
+    }
+
+    /// Whether `name` is how the entry called `entry` is looked up
+    fn matches(&self, entry: &str, name: &str) -> bool {
+        match self.names {
+            // upper case only, looked up the way FAT short names are
+            Names::Iso => entry.eq_ignore_ascii_case(name),
+            _ => entry == name,
+        }
+    }
+
+    /// Get an entry from the given directory
+    fn find_entry(&self, dir: &DirRecord, name: &str) -> FsResult<(String, DirRecord)> {
+        self.walk_entries(dir, |entry, record| {
+            if self.matches(&entry, name) {
+                ControlFlow::Break((entry, record))
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?
+        .ok_or(FsError::FileNotFound)
+    }
+
+    /// The "." record of the directory starting at `extent`, which holds
+    /// the size of the directory
+    fn dir_at(&self, extent: u32) -> FsResult<DirRecord> {
+        let mut block = Block2048::default();
+        self.inner.read_block(extent as usize, &mut block)?;
+
+        DirRecord::parse(block.as_ref())
+            .filter(|record| record.is_dir())
+            .ok_or(FsError::InvalidVolume)
+    }
+
+    /// Resolve a path whose every component is a directory
+    ///
+    /// The path table holds the names of the descriptor in use, so it can
+    /// only be searched when these are the names shown.
+    pub(super) fn get_dir(&self, path: &str) -> FsResult<DirRecord> {
+        let components = path.split(PATH_SEPARATOR).filter(|name| !name.is_empty());
+
+        if let Names::RockRidge { .. } = self.names {
+            let mut current = self.root.clone();
+
+            for name in components {
+                let (_, record) = self.find_entry(&current, name)?;
+
+                if !record.is_dir() {
+                    return Err(FsError::NotADirectory);
+                }
+
+                current = record;
+            }
+
+            return Ok(current);
+        }
+
+        let mut current = 0;
+        for name in components {
+            current = self
+                .path_table
+                .iter()
+                .enumerate()
+                .skip(1)
+                .find(|(_, dir)| dir.parent == current && self.matches(&self.decode(dir), name))
+                .map(|(index, _)| index)
+                .ok_or(FsError::FileNotFound)?;
+        }
+
+        match current {
+            0 => Ok(self.root.clone()),
+            index => self.dir_at(self.path_table[index].extent),
+        }
+    }
+
+    /// The name of a directory in the path table
+    fn decode(&self, dir: &PathRecord) -> String {
+        match self.names {
+            Names::Joliet => joliet_name(&dir.ident),
+            _ => iso_name(&dir.ident),
+        }
+    }
+
+    /// Get the entry at `path` with its name, the root has an empty name
+    pub(super) fn get_entry(&self, path: &str) -> FsResult<(String, DirRecord)> {
+        let (parent, name) = split_path(path);
+
+        if name.is_empty() {
+            return Ok((String::new(), self.root.clone()));
+        }
+
+        self.find_entry(&self.get_dir(parent)?, name)
+    }
+
+    pub fn max_name_len(&self) -> usize {
+        match self.names {
+            Names::Iso => 31,
+            Names::Joliet => 64,
+            Names::RockRidge { .. } => 255,
+        }
+    }
+}
+
+/// The number of bytes to skip in each system use area, if the root
+/// carries the SUSP "SP" marker
+fn rock_ridge(inner: &impl BlockDevice<Block2048>, root: &DirRecord) -> FsResult<Option<usize>> {
+    let mut block = Block2048::default();
+    inner.read_block(root.extent as usize, &mut block)?;
+
+    let Some(dot) = DirRecord::parse(block.as_ref()) else {
+        return Ok(None);
+    };
+
+    Ok(SuspEntries::new(&dot.system_use)
+        .next()
+        .filter(|(signature, data)| signature == b"SP" && data.starts_with(&[0xBE, 0xEF]))
+        .and_then(|(_, data)| data.get(2).map(|&skip| skip as usize)))
+}
+
+/// Split a path into its parent directory and the last component
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches(PATH_SEPARATOR);
+    match path.rfind(PATH_SEPARATOR) {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("", path),
+    }
+}
+
+impl FileSystem for Iso9660 {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let dir = match self.handle.get_dir(path) {
+            // the path table only knows directories
+            Err(FsError::FileNotFound) if self.exists(path)? => Err(FsError::NotADirectory),
+            dir => dir,
+        }?;
+
+        let mut entries = Vec::new();
+        self.handle.walk_entries(&dir, |name, record| {
+            entries.push(record.as_meta(name));
+            ControlFlow::<()>::Continue(())
+        })?;
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (name, record) = self.handle.get_entry(path)?;
+
+        if record.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        let meta = record.as_meta(name);
+        let file = Box::new(File::new(self.handle.clone(), record));
+
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let (name, record) = self.handle.get_entry(path)?;
+        Ok(record.as_meta(name))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        Ok(self.handle.get_entry(path).is_ok())
+    }
+
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        let total = self.handle.volume.volume_space_size() as usize;
+
+        Ok(FsStat {
+            block_size: BLOCK_SIZE,
+            total_blocks: total,
+            free_blocks: 0,
+            max_name_len: self.handle.max_name_len(),
+            read_only: true,
+        })
+    }
+}
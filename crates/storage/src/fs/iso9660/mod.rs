@@ -0,0 +1,82 @@
+//! ISO 9660 is the read-only filesystem of CD-ROM images, on 2048 byte
+//! sectors. Every file and directory is a single contiguous extent.
+//!
+//! ```text
+//! [ System Area ] [ PVD | SVD ... | Terminator ] [ Path Tables ] [ Data ]
+//! ```
+//!
+//!   - The first 16 sectors are left to the system, e.g. for a boot loader.
+//!   - The primary volume descriptor (PVD) points to the root directory and the
+//!     path table, which lists every directory with its parent.
+//!   - A Joliet supplementary descriptor (SVD) has its own tree and path table
+//!     with UCS-2 names, Rock Ridge adds POSIX names to the primary tree.
+//!
+//! Rock Ridge is preferred over Joliet when both are present. Files recorded
+//! in several extents (over 4 GiB) and directories moved by Rock Ridge to
+//! get around the depth limit are not supported.
+
+pub mod directory;
+pub mod file;
+pub mod impls;
+pub mod volume;
+
+use directory::*;
+use file::File;
+use volume::*;
+
+use crate::*;
+
+const BLOCK_SIZE: usize = Block2048::BLOCK_SIZE;
+
+/// Identifies an ISO 9660 volume on the disk.
+#[derive(Clone)]
+pub struct Iso9660 {
+    handle: Iso9660Handle,
+}
+
+impl Iso9660 {
+    pub fn new(inner: impl BlockDevice<Block2048>) -> FsResult<Self> {
+        Ok(Self {
+            handle: Arc::new(Iso9660Impl::new(inner)?),
+        })
+    }
+}
+
+type Iso9660Handle = Arc<Iso9660Impl>;
+
+/// Where the names of the entries come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Names {
+    /// Upper case names of the primary descriptor
+    Iso,
+    /// UCS-2 names of the Joliet descriptor
+    Joliet,
+    /// "NM" entries, after `skip` bytes of each system use area
+    RockRidge { skip: usize },
+}
+
+pub struct Iso9660Impl {
+    pub(crate) inner: Box<dyn BlockDevice<Block2048>>,
+    /// The descriptor whose tree is used
+    pub volume: VolumeDescriptor,
+    pub names: Names,
+    pub root: DirRecord,
+    pub path_table: Vec<PathRecord>,
+}
+
+impl core::fmt::Debug for Iso9660 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Iso9660")
+            .field("volume", &self.handle.volume)
+            .field("names", &self.handle.names)
+            .finish()
+    }
+}
+
+impl core::fmt::Debug for Iso9660Impl {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Iso9660Impl")
+            .field("volume", &self.volume)
+            .finish()
+    }
+}
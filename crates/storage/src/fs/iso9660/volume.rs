@@ -0,0 +1,122 @@
+//! ISO 9660 volume descriptors and path table
+//!
+//! reference:
+//! - <https://wiki.osdev.org/ISO_9660#Volume_Descriptors>
+//! - <https://www.ecma-international.org/wp-content/uploads/ECMA-119_4th_edition_june_2019.pdf>
+//! - <https://pismotec.com/cfs/jolietspec.html>
+
+use super::*;
+
+/// A volume descriptor, one per sector from sector 16 on
+///
+/// Numbers are stored "both-endian", the little endian half comes first so
+/// it is the one read here.
+pub struct VolumeDescriptor {
+    data: [u8; 2048],
+}
+
+impl VolumeDescriptor {
+    pub const BOOT_RECORD: u8 = 0;
+    pub const PRIMARY: u8 = 1;
+    pub const SUPPLEMENTARY: u8 = 2;
+    pub const TERMINATOR: u8 = 255;
+
+    /// Attempt to parse a volume descriptor from a 2048 byte sector.
+    pub fn new(data: &[u8]) -> Result<VolumeDescriptor, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad descriptor size")?;
+        let descriptor = VolumeDescriptor { data };
+
+        if descriptor.identifier() != b"CD001" || descriptor.version() != 1 {
+            return Err("Bad volume descriptor");
+        }
+
+        Ok(descriptor)
+    }
+
+    /// A supplementary descriptor announcing UCS-2 names
+    pub fn is_joliet(&self) -> bool {
+        // level 1, 2 and 3 escape sequences
+        self.type_code() == Self::SUPPLEMENTARY
+            && matches!(&self.escape_sequences()[..3], b"%/@" | b"%/C" | b"%/E")
+    }
+
+    /// The directory record of the root directory
+    pub fn root_record(&self) -> FsResult<DirRecord> {
+        DirRecord::parse(&self.data[156..190]).ok_or(FsError::InvalidVolume)
+    }
+
+    define_field!(u8, 0, type_code);
+    define_field!([u8; 5], 1, identifier);
+    define_field!(u8, 6, version);
+    define_field!([u8; 32], 8, system_identifier);
+    define_field!([u8; 32], 40, volume_identifier);
+    define_field!(u32, 80, volume_space_size);
+    define_field!([u8; 32], 88, escape_sequences);
+    define_field!(u16, 128, logical_block_size);
+    define_field!(u32, 132, path_table_size);
+    define_field!(u32, 140, l_path_table);
+}
+
+impl core::fmt::Debug for VolumeDescriptor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VolumeDescriptor")
+            .field("Type", &self.type_code())
+            .field(
+                "System Identifier",
+                &self.system_identifier_str().trim_end(),
+            )
+            .field(
+                "Volume Identifier",
+                &self.volume_identifier_str().trim_end(),
+            )
+            .field("Volume Space Size", &self.volume_space_size())
+            .field("Logical Block Size", &self.logical_block_size())
+            .field("Path Table Size", &self.path_table_size())
+            .field("L Path Table", &self.l_path_table())
+            .finish()
+    }
+}
+
+/// A directory as listed in the path table
+#[derive(Debug, Clone)]
+pub struct PathRecord {
+    /// First block of the directory
+    pub extent: u32,
+    /// Index of the parent in the table, the root is its own parent
+    pub parent: usize,
+    /// Name as recorded, `[0]` for the root
+    pub ident: Vec<u8>,
+}
+
+/// Parse a little endian path table, parents are turned into 0 based indices
+pub fn parse_path_table(data: &[u8]) -> FsResult<Vec<PathRecord>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= data.len() && data[offset] != 0 {
+        let len = data[offset] as usize;
+        let end = offset + 8 + len;
+        let parent = u16::from_le_bytes([data[offset + 6], data[offset + 7]]) as usize;
+
+        if end > data.len() || parent == 0 || parent > records.len().max(1) {
+            return Err(FsError::InvalidVolume);
+        }
+
+        records.push(PathRecord {
+            // skipping the extended attributes like directory records do
+            extent: u32::from_le_bytes(data[offset + 2..offset + 6].try_into().unwrap())
+                + data[offset + 1] as u32,
+            parent: parent - 1,
+            ident: data[offset + 8..end].to_vec(),
+        });
+
+        // names of odd length are padded
+        offset = end.next_multiple_of(2);
+    }
+
+    if records.is_empty() {
+        return Err(FsError::InvalidVolume);
+    }
+
+    Ok(records)
+}
@@ -1,4 +1,10 @@
+pub mod cpio;
+pub mod devfs;
+pub mod ext2;
 pub mod fat16;
+pub mod fat32;
+pub mod iso9660;
+pub mod tmpfs;
 
 #[cfg(target_arch = "x86_64")]
 pub mod random;
@@ -4,10 +4,13 @@ use rand::{Rng, RngExt, SeedableRng};
 use rand_hc::Hc128Rng;
 use x86_64::instructions::random::RdRand;
 
-use crate::*;
+use crate::{devfs::DeviceNode, *};
 
 pub static GLOBAL_RNG: spin::Once<spin::Mutex<Hc128Rng>> = spin::Once::new();
 
+/// Attempts of RDRAND before giving up, as Intel recommends
+const RDRAND_RETRIES: usize = 10;
+
 #[derive(Debug, Clone)]
 pub struct Random;
 
@@ -17,7 +20,7 @@ impl Device<u8> for Random {
             for i in (0..size).step_by(8) {
                 if let Some(num) = rng.get_u64() {
                     for j in (i..min(i + 8, size)).rev() {
-                        buf[offset + j] = (num >> (j * 8)) as u8;
+                        buf[offset + j] = (num >> ((j - i) * 8)) as u8;
                     }
                 } else {
                     return Err(DeviceError::ReadError.into());
@@ -45,6 +48,23 @@ impl Random {
         }
         Self
     }
+
+    /// Fill `buf` from RDRAND alone, for key material
+    ///
+    /// The fallback generator is seeded with a constant on a CPU without
+    /// RDRAND, so this fails with `NotSupported` there instead.
+    pub fn fill_from_hardware(buf: &mut [u8]) -> FsResult {
+        let rng = RdRand::new().ok_or(FsError::NotSupported)?;
+
+        for chunk in buf.chunks_mut(8) {
+            let num = (0..RDRAND_RETRIES)
+                .find_map(|_| rng.get_u64())
+                .ok_or(DeviceError::ReadError)?;
+            chunk.copy_from_slice(&num.to_le_bytes()[..chunk.len()]);
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for Random {
@@ -52,3 +72,58 @@ impl Default for Random {
         Self::new()
     }
 }
+
+impl Read for Random {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let len = buf.len();
+        Device::read(self, buf, 0, len)
+    }
+}
+
+impl Write for Random {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for Random {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+impl DeviceNode for Random {
+    fn open(&self) -> FsResult<Box<dyn FileIO + Send>> {
+        Ok(Box::new(Random::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_fills_buffer() {
+        // more than one u64, at an offset and not a multiple of 8
+        let mut buf = [0u8; 64];
+        assert_eq!(Device::read(&Random::new(), &mut buf, 3, 37), Ok(37));
+
+        assert!(buf[..3].iter().all(|&b| b == 0));
+        assert!(buf[40..].iter().all(|&b| b == 0));
+        // all zero bytes in a row are unlikely from either source
+        assert!(buf[3..40].windows(8).all(|w| w.iter().any(|&b| b != 0)));
+    }
+
+    #[test]
+    fn test_fill_from_hardware() {
+        let mut buf = [0u8; 37];
+        match Random::fill_from_hardware(&mut buf) {
+            Ok(()) => assert!(buf.windows(8).all(|w| w.iter().any(|&b| b != 0))),
+            Err(e) => assert_eq!(e, FsError::NotSupported),
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! In-memory filesystem
+//!
+//! Everything lives in a tree of nodes behind one lock. File contents are
+//! shared with the open handles, so a removed file stays readable until its
+//! last handle is dropped. File contents are counted against the capacity
+//! given at creation, as they take kernel heap.
+
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use spin::RwLock;
+
+use crate::*;
+
+type FileData = Arc<RwLock<Content>>;
+type Children = BTreeMap<String, Node>;
+
+/// Bytes taken by file contents, shared by the filesystem and its files
+#[derive(Debug)]
+struct Usage {
+    used: AtomicUsize,
+    capacity: usize,
+}
+
+impl Usage {
+    /// Take `len` more bytes, fails if the capacity would be exceeded
+    fn reserve(&self, len: usize) -> FsResult {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(len).filter(|&used| used <= self.capacity)
+            })
+            .map(|_| ())
+            .map_err(|_| FsError::WriteZero)
+    }
+
+    fn release(&self, len: usize) {
+        self.used.fetch_sub(len, Ordering::SeqCst);
+    }
+}
+
+/// The content of a file, given back to the usage when the last handle to
+/// it is dropped
+struct Content {
+    bytes: Vec<u8>,
+    usage: Arc<Usage>,
+}
+
+impl Content {
+    fn new(bytes: Vec<u8>, usage: Arc<Usage>) -> FsResult<FileData> {
+        usage.reserve(bytes.len())?;
+        Ok(Arc::new(RwLock::new(Content { bytes, usage })))
+    }
+
+    /// Drop every byte, the space goes back to the usage
+    fn clear(&mut self) {
+        self.usage.release(self.bytes.len());
+        self.bytes = Vec::new();
+    }
+
+    /// Grow to `len` bytes, filled with zeros
+    fn grow(&mut self, len: usize) -> FsResult {
+        if let Some(extra) = len.checked_sub(self.bytes.len()) {
+            self.usage.reserve(extra)?;
+            self.bytes.resize(len, 0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Content {
+    fn drop(&mut self) {
+        self.usage.release(self.bytes.len());
+    }
+}
+
+enum Node {
+    File(FileData),
+    Dir(Children),
+}
+
+impl Node {
+    fn as_meta(&self, name: &str) -> Metadata {
+        let (entry_type, len) = match self {
+            Node::File(data) => (FileType::File, data.read().bytes.len()),
+            Node::Dir(_) => (FileType::Directory, 0),
+        };
+        Metadata::new(name.to_owned(), entry_type, len, None, None, None)
+    }
+}
+
+/// A filesystem kept in memory, its content is lost when dropped
+pub struct TmpFs {
+    root: RwLock<Children>,
+    usage: Arc<Usage>,
+}
+
+impl TmpFs {
+    /// Create an empty filesystem holding at most `capacity` bytes of file
+    /// content
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            root: RwLock::default(),
+            usage: Arc::new(Usage {
+                used: AtomicUsize::new(0),
+                capacity,
+            }),
+        }
+    }
+
+    fn open(&self, path: &str, offset: SeekFrom) -> FsResult<FileHandle> {
+        let (parent, name) = split_path(path)?;
+        let root = self.root.read();
+
+        match lookup(&root, &parent)?.get(name) {
+            Some(node @ Node::File(data)) => {
+                let mut file = File::new(data.clone());
+                file.seek(offset)?;
+                Ok(FileHandle::new(node.as_meta(name), Box::new(file)))
+            }
+            Some(Node::Dir(_)) => Err(FsError::NotAFile),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    /// Inserts a new node at `path`, the parent directory must exist
+    fn insert(&self, path: &str, node: Node) -> FsResult {
+        let (parent, name) = split_path(path)?;
+        let mut root = self.root.write();
+        let dir = lookup_mut(&mut root, &parent)?;
+
+        if dir.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        dir.insert(name.to_owned(), node);
+        Ok(())
+    }
+
+    fn remove(&self, path: &str, is_dir: bool) -> FsResult {
+        let (parent, name) = split_path(path)?;
+        let mut root = self.root.write();
+        let dir = lookup_mut(&mut root, &parent)?;
+
+        match (dir.get(name), is_dir) {
+            (None, _) => return Err(FsError::FileNotFound),
+            (Some(Node::Dir(_)), false) => return Err(FsError::NotAFile),
+            (Some(Node::File(_)), true) => return Err(FsError::NotADirectory),
+            (Some(Node::Dir(children)), true) if !children.is_empty() => {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+            _ => {}
+        }
+
+        dir.remove(name);
+        Ok(())
+    }
+
+    fn rename(&self, src: &str, dst: &str, is_dir: bool) -> FsResult {
+        let (src_parent, src_name) = split_path(src)?;
+        let (dst_parent, dst_name) = split_path(dst)?;
+        let mut root = self.root.write();
+
+        match (lookup(&root, &src_parent)?.get(src_name), is_dir) {
+            (None, _) => return Err(FsError::FileNotFound),
+            (Some(Node::Dir(_)), false) => return Err(FsError::NotAFile),
+            (Some(Node::File(_)), true) => return Err(FsError::NotADirectory),
+            _ => {}
+        }
+
+        // refuse to move a directory into itself
+        if is_dir && dst_parent.starts_with(&components(src)) {
+            return Err(FsError::InvalidOperation);
+        }
+
+        if lookup(&root, &dst_parent)?.contains_key(dst_name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let node = lookup_mut(&mut root, &src_parent)?
+            .remove(src_name)
+            .ok_or(FsError::FileNotFound)?;
+        lookup_mut(&mut root, &dst_parent)?.insert(dst_name.to_owned(), node);
+
+        Ok(())
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let root = self.root.read();
+        let entries: Vec<_> = lookup(&root, &components(path))?
+            .iter()
+            .map(|(name, node)| node.as_meta(name))
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.open(path, SeekFrom::Start(0))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let path = components(path);
+        let root = self.root.read();
+
+        match path.split_last() {
+            Some((name, parent)) => lookup(&root, parent)?
+                .get(*name)
+                .map(|node| node.as_meta(name))
+                .ok_or(FsError::FileNotFound),
+            None => Ok(Metadata::new(
+                String::new(),
+                FileType::Directory,
+                0,
+                None,
+                None,
+                None,
+            )),
+        }
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(FsError::FileNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn statfs(&self, _path: &str) -> FsResult<FsStat> {
+        let used = self.usage.used.load(Ordering::SeqCst);
+
+        Ok(FsStat {
+            block_size: 1,
+            total_blocks: self.usage.capacity,
+            free_blocks: self.usage.capacity.saturating_sub(used),
+            // names are only bounded by the heap
+            max_name_len: usize::MAX,
+            read_only: false,
+        })
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        let data = Content::new(Vec::new(), self.usage.clone())?;
+        self.insert(path, Node::File(data.clone()))?;
+
+        let (_, name) = split_path(path)?;
+        let meta = Metadata::new(name.to_owned(), FileType::File, 0, None, None, None);
+        Ok(FileHandle::new(meta, Box::new(File::new(data))))
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.open(path, SeekFrom::End(0))
+    }
+
+    fn truncate_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (parent, name) = split_path(path)?;
+        if let Some(Node::File(data)) = lookup(&self.root.read(), &parent)?.get(name) {
+            // handles still open see the file shrink
+            data.write().clear();
+        }
+
+        self.open(path, SeekFrom::Start(0))
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.remove(path, false)
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.insert(path, Node::Dir(Children::new()))
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        self.remove(path, true)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        let data = {
+            let (parent, name) = split_path(src)?;
+            let root = self.root.read();
+            match lookup(&root, &parent)?.get(name) {
+                Some(Node::File(data)) => data.read().bytes.clone(),
+                Some(Node::Dir(_)) => return Err(FsError::NotAFile),
+                None => return Err(FsError::FileNotFound),
+            }
+        };
+
+        self.insert(dst, Node::File(Content::new(data, self.usage.clone())?))
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.rename(src, dst, false)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        self.rename(src, dst, true)
+    }
+}
+
+impl core::fmt::Debug for TmpFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TmpFs")
+            .field("entries", &self.root.read().len())
+            .finish()
+    }
+}
+
+/// An open file, shares its content with the filesystem
+pub struct File {
+    data: FileData,
+    offset: usize,
+}
+
+impl File {
+    fn new(data: FileData) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let data = &self.data.read().bytes;
+        let start = self.offset.min(data.len());
+        let len = buf.len().min(data.len() - start);
+
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        self.offset += len;
+
+        Ok(len)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        let mut data = self.data.write();
+        let end = self
+            .offset
+            .checked_add(buf.len())
+            .ok_or(FsError::InvalidOffset)?;
+
+        // writing past the end fills the gap with zeros
+        data.grow(end)?;
+
+        data.bytes[self.offset..end].copy_from_slice(buf);
+        self.offset = end;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let data = self.data.read();
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => data.bytes.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+
+        // no file can grow past the capacity
+        self.offset = offset
+            .filter(|&offset| offset <= data.usage.capacity)
+            .ok_or(FsError::InvalidOffset)?;
+        Ok(self.offset)
+    }
+}
+
+/// Splits a path into its components, resolving "." and ".."
+fn components(path: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+
+    for part in path.split(PATH_SEPARATOR) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts
+}
+
+/// Splits a path into its parent components and the last name
+fn split_path(path: &str) -> FsResult<(Vec<&str>, &str)> {
+    let mut parts = components(path);
+    let name = parts
+        .pop()
+        .ok_or_else(|| FsError::InvalidPath(path.to_owned()))?;
+    Ok((parts, name))
+}
+
+fn lookup<'a>(root: &'a Children, path: &[&str]) -> FsResult<&'a Children> {
+    path.iter()
+        .try_fold(root, |dir, name| match dir.get(*name) {
+            Some(Node::Dir(children)) => Ok(children),
+            Some(Node::File(_)) => Err(FsError::NotADirectory),
+            None => Err(FsError::FileNotFound),
+        })
+}
+
+fn lookup_mut<'a>(root: &'a mut Children, path: &[&str]) -> FsResult<&'a mut Children> {
+    path.iter()
+        .try_fold(root, |dir, name| match dir.get_mut(*name) {
+            Some(Node::Dir(children)) => Ok(children),
+            Some(Node::File(_)) => Err(FsError::NotADirectory),
+            None => Err(FsError::FileNotFound),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 64 * 1024;
+
+    fn names(fs: &TmpFs, path: &str) -> Vec<String> {
+        fs.read_dir(path).unwrap().map(|meta| meta.name).collect()
+    }
+
+    fn read(fs: &TmpFs, path: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_files() {
+        let fs = TmpFs::new(CAPACITY);
+
+        let mut file = fs.create_file("/a.txt").unwrap();
+        file.write_all(b"hello world").unwrap();
+        file.seek(SeekFrom::Start(6)).unwrap();
+        file.write_all(b"there").unwrap();
+        assert_eq!(read(&fs, "/a.txt"), b"hello there");
+
+        // seeking past the end leaves a hole of zeros
+        file.seek(SeekFrom::End(2)).unwrap();
+        file.write_all(b"!").unwrap();
+        assert_eq!(read(&fs, "a.txt"), b"hello there\0\0!");
+        assert_eq!(
+            file.seek(SeekFrom::Current(-20)),
+            Err(FsError::InvalidOffset)
+        );
+
+        let mut file = fs.append_file("/a.txt").unwrap();
+        file.write_all(b"?").unwrap();
+        assert_eq!(fs.metadata("/a.txt").unwrap().len, 15);
+
+        // truncating gives the space back
+        let mut file = fs.truncate_file("/a.txt").unwrap();
+        assert_eq!(fs.metadata("/a.txt").unwrap().len, 0);
+        file.write_all(b"hello").unwrap();
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, CAPACITY - 5);
+
+        assert_eq!(
+            fs.create_file("/a.txt").unwrap_err(),
+            FsError::AlreadyExists
+        );
+        assert_eq!(fs.open_file("/b.txt").unwrap_err(), FsError::FileNotFound);
+        assert_eq!(
+            fs.create_file("/x/b.txt").unwrap_err(),
+            FsError::FileNotFound
+        );
+        assert_eq!(
+            fs.create_file("/a.txt/b.txt").unwrap_err(),
+            FsError::NotADirectory
+        );
+
+        // an open file outlives its directory entry
+        let mut file = fs.open_file("/a.txt").unwrap();
+        fs.remove_file("/a.txt").unwrap();
+        assert!(!fs.exists("/a.txt").unwrap());
+        let mut buf = [0u8; 5];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    fn test_dirs() {
+        let fs = TmpFs::new(CAPACITY);
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_dir("/dir/sub/").unwrap();
+        fs.create_file("/dir/sub/f").unwrap();
+        assert_eq!(names(&fs, "/"), ["dir"]);
+        assert_eq!(names(&fs, "/dir/./sub/../sub"), ["f"]);
+        assert!(fs.metadata("/dir").unwrap().is_dir());
+        assert!(fs.metadata("/").unwrap().is_dir());
+
+        assert_eq!(fs.remove_dir("/dir"), Err(FsError::DirectoryNotEmpty));
+        assert_eq!(fs.remove_file("/dir"), Err(FsError::NotAFile));
+        assert_eq!(fs.remove_dir("/dir/sub/f"), Err(FsError::NotADirectory));
+        assert_eq!(fs.open_file("/dir").unwrap_err(), FsError::NotAFile);
+
+        fs.remove_file("/dir/sub/f").unwrap();
+        fs.remove_dir("/dir/sub").unwrap();
+        assert!(names(&fs, "/dir").is_empty());
+    }
+
+    #[test]
+    fn test_rename_copy() {
+        let fs = TmpFs::new(CAPACITY);
+
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/b").unwrap();
+        fs.create_file("/a/f").unwrap().write_all(b"data").unwrap();
+
+        fs.copy_file("/a/f", "/b/g").unwrap();
+        fs.move_file("/a/f", "/a/h").unwrap();
+        assert_eq!(names(&fs, "/a"), ["h"]);
+        assert_eq!(read(&fs, "/b/g"), b"data");
+
+        // copies do not share content
+        fs.open_file("/b/g").unwrap().write_all(b"DA").unwrap();
+        assert_eq!(read(&fs, "/a/h"), b"data");
+        assert_eq!(read(&fs, "/b/g"), b"DAta");
+
+        fs.move_dir("/a", "/b/a").unwrap();
+        assert_eq!(names(&fs, "/"), ["b"]);
+        assert_eq!(read(&fs, "/b/a/h"), b"data");
+
+        assert_eq!(fs.move_dir("/b", "/b/a/b"), Err(FsError::InvalidOperation));
+        assert_eq!(fs.move_dir("/b/g", "/c"), Err(FsError::NotADirectory));
+        assert_eq!(fs.move_file("/b/a", "/c"), Err(FsError::NotAFile));
+        assert_eq!(fs.move_file("/b/g", "/b/a/h"), Err(FsError::AlreadyExists));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let fs = TmpFs::new(CAPACITY);
+        let stat = fs.statfs("/").unwrap();
+        assert_eq!(stat.total_blocks, CAPACITY);
+        assert_eq!(stat.free_blocks, CAPACITY);
+
+        // a hole counts as much as data
+        let mut file = fs.create_file("/big").unwrap();
+        assert_eq!(
+            file.seek(SeekFrom::Start(usize::MAX)),
+            Err(FsError::InvalidOffset)
+        );
+        file.seek(SeekFrom::Start(CAPACITY - 4)).unwrap();
+        file.write_all(b"full").unwrap();
+        assert_eq!(fs.statfs("/").unwrap().free_blocks, 0);
+        assert_eq!(file.write(b"!"), Err(FsError::WriteZero));
+        assert_eq!(fs.copy_file("/big", "/copy"), Err(FsError::WriteZero));
+
+        // the space comes back once the last handle is gone
+        fs.remove_file("/big").unwrap();
+        assert_eq!(
+            fs.create_file("/small").unwrap().write(b"!"),
+            Err(FsError::WriteZero)
+        );
+        drop(file);
+        fs.create_file("/other")
+            .unwrap()
+            .write_all(&[1; CAPACITY])
+            .unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! GPT Partition Entry
+//!
+//! Every entry describes one partition, unused entries have a zero type GUID.
+
+use super::*;
+
+/// A GUID as stored on disk, the first three fields are little endian.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// Unused partition entry
+    pub const UNUSED: Guid = Guid([0; 16]);
+    /// EFI System Partition
+    pub const EFI_SYSTEM: Guid = Guid::from_fields(
+        0xC12A7328,
+        0xF81F,
+        0x11D2,
+        [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
+    );
+    /// Microsoft Basic Data, used for FAT volumes
+    pub const BASIC_DATA: Guid = Guid::from_fields(
+        0xEBD0A0A2,
+        0xB9E5,
+        0x4433,
+        [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7],
+    );
+    /// Linux filesystem data
+    pub const LINUX_FILESYSTEM: Guid = Guid::from_fields(
+        0x0FC63DAF,
+        0x8483,
+        0x4772,
+        [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4],
+    );
+
+    /// Build a GUID from the fields of its text form
+    pub const fn from_fields(d1: u32, d2: u16, d3: u16, d4: [u8; 8]) -> Guid {
+        let d1 = d1.to_le_bytes();
+        let d2 = d2.to_le_bytes();
+        let d3 = d3.to_le_bytes();
+        Guid([
+            d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], d4[0], d4[1], d4[2], d4[3],
+            d4[4], d4[5], d4[6], d4[7],
+        ])
+    }
+}
+
+impl core::fmt::Display for Guid {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let g = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([g[0], g[1], g[2], g[3]]),
+            u16::from_le_bytes([g[4], g[5]]),
+            u16::from_le_bytes([g[6], g[7]]),
+            g[8],
+            g[9]
+        )?;
+        g[10..].iter().try_for_each(|b| write!(f, "{:02X}", b))
+    }
+}
+
+impl core::fmt::Debug for Guid {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct GptPartition {
+    data: [u8; GptPartition::LEN],
+}
+
+impl GptPartition {
+    /// Size of the fields we know, entries may be larger on disk
+    pub const LEN: usize = 128;
+
+    pub fn parse(data: &[u8; GptPartition::LEN]) -> GptPartition {
+        GptPartition {
+            data: data.to_owned(),
+        }
+    }
+
+    define_field!(u64, 0x20, first_lba);
+    define_field!(u64, 0x28, last_lba);
+    define_field!(u64, 0x30, attributes);
+
+    pub fn type_guid(&self) -> Guid {
+        Guid(self.data[0x00..0x10].try_into().unwrap())
+    }
+
+    pub fn unique_guid(&self) -> Guid {
+        Guid(self.data[0x10..0x20].try_into().unwrap())
+    }
+
+    pub fn is_used(&self) -> bool {
+        self.type_guid() != Guid::UNUSED
+    }
+
+    /// Number of blocks in the partition, `last_lba` is inclusive
+    pub fn total_lba(&self) -> u64 {
+        (self.last_lba() + 1).saturating_sub(self.first_lba())
+    }
+
+    /// Partition name, stored as up to 36 UTF-16 characters
+    pub fn name(&self) -> String {
+        let chars: Vec<u16> = self.data[0x38..0x80]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        String::from_utf16_lossy(&chars)
+    }
+}
+
+impl core::fmt::Debug for GptPartition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GPT Partition")
+            .field("Type GUID", &self.type_guid())
+            .field("Unique GUID", &self.unique_guid())
+            .field("First LBA", &format!("0x{:08x}", self.first_lba()))
+            .field("Last LBA", &format!("0x{:08x}", self.last_lba()))
+            .field("Attributes", &format!("0x{:016x}", self.attributes()))
+            .field("Name", &self.name())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_test() {
+        let mut data = [0u8; GptPartition::LEN];
+        data[0x00..0x10].copy_from_slice(&hex_literal::hex!(
+            "28 73 2A C1 1F F8 D2 11 BA 4B 00 A0 C9 3E C9 3B"
+        ));
+        data[0x20..0x28].copy_from_slice(&2048u64.to_le_bytes());
+        data[0x28..0x30].copy_from_slice(&206847u64.to_le_bytes());
+        for (i, c) in "EFI system".encode_utf16().enumerate() {
+            data[0x38 + i * 2..0x3a + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+
+        let part = GptPartition::parse(&data);
+
+        println!("{:#?}", part);
+
+        assert!(part.is_used());
+        assert_eq!(part.type_guid(), Guid::EFI_SYSTEM);
+        assert_eq!(
+            format!("{}", part.type_guid()),
+            "C12A7328-F81F-11D2-BA4B-00A0C93EC93B"
+        );
+        assert_eq!(part.first_lba(), 2048);
+        assert_eq!(part.total_lba(), 204800);
+        assert_eq!(part.name(), "EFI system");
+    }
+}
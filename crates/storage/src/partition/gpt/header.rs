@@ -0,0 +1,91 @@
+//! GPT Header
+//!
+//! The primary header is stored at LBA 1, the backup one usually at the
+//! last block of the disk. Both are protected by a CRC32.
+
+use super::*;
+
+pub struct GptHeader {
+    data: [u8; 512],
+}
+
+impl GptHeader {
+    pub const SIGNATURE: &'static [u8; 8] = b"EFI PART";
+    /// Largest entry array accepted, disks usually have 128 entries of
+    /// 128 bytes
+    pub const MAX_ENTRIES_LEN: usize = 1024 * 1024;
+
+    /// Attempt to parse and validate a GPT header from the start of a block.
+    pub fn parse(data: &[u8]) -> FsResult<GptHeader> {
+        let header = GptHeader {
+            data: data
+                .get(..512)
+                .and_then(|data| data.try_into().ok())
+                .ok_or(FsError::InvalidPartitionTable)?,
+        };
+
+        if header.signature() != Self::SIGNATURE {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        let size = header.header_size() as usize;
+        if !(92..=512).contains(&size) {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        // the CRC is computed with its own field zeroed
+        let mut raw = header.data;
+        raw[0x10..0x14].fill(0);
+        if crc32fast::hash(&raw[..size]) != header.header_crc32() {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        if header.entry_size() < GptPartition::LEN as u32
+            || !header.entry_size().is_multiple_of(128)
+            || header.entries_len() > Self::MAX_ENTRIES_LEN
+        {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        Ok(header)
+    }
+
+    define_field!([u8; 8], 0x00, signature);
+    define_field!(u32, 0x08, revision);
+    define_field!(u32, 0x0c, header_size);
+    define_field!(u32, 0x10, header_crc32);
+    define_field!(u64, 0x18, current_lba);
+    define_field!(u64, 0x20, backup_lba);
+    define_field!(u64, 0x28, first_usable_lba);
+    define_field!(u64, 0x30, last_usable_lba);
+    define_field!(u64, 0x48, entries_lba);
+    define_field!(u32, 0x50, entry_count);
+    define_field!(u32, 0x54, entry_size);
+    define_field!(u32, 0x58, entries_crc32);
+
+    pub fn disk_guid(&self) -> Guid {
+        Guid(self.data[0x38..0x48].try_into().unwrap())
+    }
+
+    /// Bytes taken by the partition entry array
+    pub fn entries_len(&self) -> usize {
+        self.entry_count() as usize * self.entry_size() as usize
+    }
+}
+
+impl core::fmt::Debug for GptHeader {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GPT Header")
+            .field("Revision", &format!("0x{:08x}", self.revision()))
+            .field("Header Size", &self.header_size())
+            .field("Current LBA", &self.current_lba())
+            .field("Backup LBA", &self.backup_lba())
+            .field("First Usable LBA", &self.first_usable_lba())
+            .field("Last Usable LBA", &self.last_usable_lba())
+            .field("Disk GUID", &self.disk_guid())
+            .field("Entries LBA", &self.entries_lba())
+            .field("Entry Count", &self.entry_count())
+            .field("Entry Size", &self.entry_size())
+            .finish()
+    }
+}
@@ -0,0 +1,261 @@
+//! GptTable
+//!
+//! reference: <https://uefi.org/specs/UEFI/2.10/05_GUID_Partition_Table_Format.html>
+//! reference: <https://wiki.osdev.org/GPT>
+
+mod entry;
+mod header;
+
+use core::marker::PhantomData;
+
+pub use entry::*;
+pub use header::*;
+
+use crate::{mbr::MbrPartition, *};
+
+pub struct GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    inner: T,
+    header: GptHeader,
+    partitions: Vec<GptPartition>,
+    _block: PhantomData<B>,
+}
+
+impl<T, B> GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    pub fn header(&self) -> &GptHeader {
+        &self.header
+    }
+
+    /// The used partition entries, with their type GUIDs and names
+    pub fn entries(&self) -> &[GptPartition] {
+        &self.partitions
+    }
+
+    /// Check for the protective MBR, a single 0xEE partition covering the disk
+    fn check_protective_mbr(inner: &T) -> FsResult {
+        let mut block = B::default();
+        inner.read_block(0, &mut block)?;
+        let buffer = block.as_ref();
+
+        if buffer[0x1fe..0x200] != [0x55, 0xAA] {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        let protective = (0..4).any(|i| {
+            let offset = 0x1be + i * 16;
+            MbrPartition::parse(buffer[offset..offset + 16].try_into().unwrap()).filesystem_flag()
+                == 0xEE
+        });
+
+        if protective {
+            Ok(())
+        } else {
+            Err(FsError::InvalidPartitionTable)
+        }
+    }
+
+    /// Read the header at `lba` and its entry array, checking both CRCs
+    fn read_header(inner: &T, lba: u64) -> FsResult<(GptHeader, Vec<GptPartition>)> {
+        let mut block = B::default();
+        inner.read_block(lba as usize, &mut block)?;
+
+        let header = GptHeader::parse(block.as_ref())?;
+        if header.current_lba() != lba {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        let block_size = B::size();
+        let len = header.entries_len();
+        let mut raw = Vec::with_capacity(len.div_ceil(block_size) * block_size);
+
+        for i in 0..len.div_ceil(block_size) {
+            inner.read_block(header.entries_lba() as usize + i, &mut block)?;
+            raw.extend_from_slice(block.as_ref());
+        }
+
+        if crc32fast::hash(&raw[..len]) != header.entries_crc32() {
+            return Err(FsError::InvalidPartitionTable);
+        }
+
+        let partitions = raw[..len]
+            .chunks_exact(header.entry_size() as usize)
+            .map(|data| GptPartition::parse(data[..GptPartition::LEN].try_into().unwrap()))
+            .filter(|part| part.is_used())
+            .collect();
+
+        Ok((header, partitions))
+    }
+}
+
+impl<T, B> PartitionTable<T, B> for GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    fn parse(inner: T) -> FsResult<Self> {
+        Self::check_protective_mbr(&inner)?;
+
+        let (header, partitions) = match Self::read_header(&inner, 1) {
+            Ok(primary) => {
+                // the backup is not used, but a broken one is worth knowing
+                if let Err(e) = Self::read_header(&inner, primary.0.backup_lba()) {
+                    warn!("GPT backup header is corrupted: {:?}", e);
+                }
+                primary
+            }
+            Err(e) => {
+                warn!("GPT primary header is corrupted: {:?}, trying backup", e);
+                let last_lba = inner.block_count()?.saturating_sub(1);
+                Self::read_header(&inner, last_lba as u64)?
+            }
+        };
+
+        for (i, part) in partitions.iter().enumerate() {
+            trace!("Partition {}: {:#?}", i, part);
+        }
+
+        Ok(Self {
+            inner,
+            header,
+            partitions,
+            _block: PhantomData,
+        })
+    }
+
+    fn partitions(&self) -> FsResult<Vec<Partition<T, B>>> {
+        Ok(self
+            .partitions
+            .iter()
+            .map(|part| {
+                Partition::new(
+                    self.inner.clone(),
+                    part.first_lba() as usize,
+                    part.total_lba() as usize,
+                    PartitionKind::Gpt(part.type_guid()),
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 128 blocks, one "data" partition at 40..80
+    fn make_disk() -> Vec<u8> {
+        let mut disk = vec![0u8; 128 * 512];
+
+        // protective MBR
+        disk[0x1be + 4] = 0xEE;
+        disk[0x1be + 8..0x1be + 12].copy_from_slice(&1u32.to_le_bytes());
+        disk[0x1be + 12..0x1be + 16].copy_from_slice(&127u32.to_le_bytes());
+        disk[0x1fe..0x200].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut entries = vec![0u8; 128 * 128];
+        entries[0x00..0x10].copy_from_slice(&Guid::BASIC_DATA.0);
+        entries[0x10..0x20].copy_from_slice(&[0x42; 16]);
+        entries[0x20..0x28].copy_from_slice(&40u64.to_le_bytes());
+        entries[0x28..0x30].copy_from_slice(&79u64.to_le_bytes());
+        for (i, c) in "data".encode_utf16().enumerate() {
+            entries[0x38 + i * 2..0x3a + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+        let entries_crc = crc32fast::hash(&entries);
+
+        for (current, backup, entries_lba) in [(1u64, 127u64, 2u64), (127, 1, 95)] {
+            let mut header = [0u8; 92];
+            header[0x00..0x08].copy_from_slice(GptHeader::SIGNATURE);
+            header[0x08..0x0c].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+            header[0x0c..0x10].copy_from_slice(&92u32.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&current.to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&backup.to_le_bytes());
+            header[0x28..0x30].copy_from_slice(&34u64.to_le_bytes());
+            header[0x30..0x38].copy_from_slice(&94u64.to_le_bytes());
+            header[0x48..0x50].copy_from_slice(&entries_lba.to_le_bytes());
+            header[0x50..0x54].copy_from_slice(&128u32.to_le_bytes());
+            header[0x54..0x58].copy_from_slice(&128u32.to_le_bytes());
+            header[0x58..0x5c].copy_from_slice(&entries_crc.to_le_bytes());
+            let crc = crc32fast::hash(&header);
+            header[0x10..0x14].copy_from_slice(&crc.to_le_bytes());
+
+            let offset = current as usize * 512;
+            disk[offset..offset + 92].copy_from_slice(&header);
+            let offset = entries_lba as usize * 512;
+            disk[offset..offset + entries.len()].copy_from_slice(&entries);
+        }
+
+        disk
+    }
+
+    #[test]
+    fn gpt_test() {
+        let disk = RamDisk::from_vec(make_disk());
+        let table = GptTable::<_, Block512>::parse(disk.clone()).unwrap();
+
+        assert_eq!(table.header().entry_count(), 128);
+        assert_eq!(table.entries().len(), 1);
+        assert_eq!(table.entries()[0].type_guid(), Guid::BASIC_DATA);
+        assert_eq!(table.entries()[0].name(), "data");
+
+        let parts = table.partitions().unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].block_count().unwrap(), 40);
+
+        let block = Block512::new(&[0x5a; 512]);
+        parts[0].write_block(0, &block).unwrap();
+        assert_eq!(disk.to_vec()[40 * 512], 0x5a);
+        assert!(parts[0].write_block(40, &block).is_err());
+    }
+
+    #[test]
+    fn gpt_backup_test() {
+        let mut data = make_disk();
+        // corrupt the primary header
+        data[512 + 0x20] ^= 0xff;
+
+        let disk = RamDisk::from_vec(data);
+        let table = GptTable::<_, Block512>::parse(disk.clone()).unwrap();
+        assert_eq!(table.header().current_lba(), 127);
+        assert_eq!(table.entries()[0].name(), "data");
+
+        // corrupt the backup entries as well
+        let mut data = disk.to_vec();
+        data[95 * 512 + 0x38] ^= 0xff;
+        assert_eq!(
+            GptTable::<_, Block512>::parse(RamDisk::from_vec(data)).err(),
+            Some(FsError::InvalidPartitionTable)
+        );
+
+        // no protective MBR
+        let mut data = make_disk();
+        data[0x1be + 4] = 0x0b;
+        let disk = RamDisk::from_vec(data);
+        assert!(GptTable::<_, Block512>::parse(disk).is_err());
+    }
+
+    #[test]
+    fn gpt_oversized_test() {
+        let mut data = make_disk();
+
+        // a valid header claiming 4G entries, in both copies
+        for offset in [512, 127 * 512] {
+            let header = &mut data[offset..offset + 92];
+            header[0x50..0x54].copy_from_slice(&u32::MAX.to_le_bytes());
+            header[0x10..0x14].fill(0);
+            let crc = crc32fast::hash(header);
+            header[0x10..0x14].copy_from_slice(&crc.to_le_bytes());
+        }
+
+        assert_eq!(
+            GptTable::<_, Block512>::parse(RamDisk::from_vec(data)).err(),
+            Some(FsError::InvalidPartitionTable)
+        );
+    }
+}
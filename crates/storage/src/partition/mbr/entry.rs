@@ -32,8 +32,22 @@ impl MbrPartition {
         self.status() == 0x80
     }
 
+    /// Whether the entry describes a partition at all
+    pub fn is_used(&self) -> bool {
+        self.filesystem_flag() != 0x00 && self.total_lba() != 0
+    }
+
+    /// Extended partitions hold an EBR chain of logical partitions
     pub fn is_extended(&self) -> bool {
-        self.filesystem_flag() == 0x05
+        matches!(self.filesystem_flag(), 0x05 | 0x0f | 0x85)
+    }
+
+    /// A copy of this entry with `begin_lba` moved by `base`, entries in an
+    /// EBR are relative to it
+    pub fn rebase(&self, base: u32) -> MbrPartition {
+        let mut data = self.data;
+        data[0x08..0x0c].copy_from_slice(&(self.begin_lba() + base).to_le_bytes());
+        MbrPartition { data }
     }
 
     pub fn begin_sector(&self) -> u8 {
@@ -98,5 +112,10 @@ mod tests {
         assert_eq!(meta.end_cylinder(), 764);
         assert_eq!(meta.begin_lba(), 63);
         assert_eq!(meta.total_lba(), 12289662);
+
+        let logical = meta.rebase(0x1000);
+        assert_eq!(logical.begin_lba(), 0x1000 + 63);
+        assert_eq!(logical.total_lba(), 12289662);
+        assert!(logical.is_used() && !logical.is_extended());
     }
 }
@@ -17,10 +17,78 @@ where
     B: BlockTrait,
 {
     inner: T,
-    partitions: [MbrPartition; 4],
+    /// Primary and logical partitions, `begin_lba` is absolute
+    partitions: Vec<MbrPartition>,
     _block: PhantomData<B>,
 }
 
+/// Longest EBR chain to follow, guards against loops
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+/// Parse the four partition entries of a MBR or EBR
+fn parse_entries<B: BlockTrait>(block: &B) -> [MbrPartition; 4] {
+    let buffer = block.as_ref();
+    core::array::from_fn(|i| {
+        MbrPartition::parse(
+            buffer[0x1be + (i * 16)..0x1be + (i * 16) + 16]
+                .try_into()
+                .unwrap(),
+        )
+    })
+}
+
+impl<T, B> MbrTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    /// The partition entries in the same order as `partitions()`, logical
+    /// partitions come after the primary ones
+    pub fn entries(&self) -> &[MbrPartition] {
+        &self.partitions
+    }
+
+    /// Follow the EBR chain of an extended partition
+    ///
+    /// The first entry of each EBR is a logical partition relative to the
+    /// EBR, the second one links to the next EBR relative to the start of
+    /// the extended partition.
+    fn parse_logical(inner: &T, extended: &MbrPartition) -> FsResult<Vec<MbrPartition>> {
+        let base = extended.begin_lba();
+        let mut logical = Vec::new();
+        let mut ebr = base;
+        let mut visited = Vec::new();
+        let mut block = B::default();
+
+        while visited.len() < MAX_LOGICAL_PARTITIONS && !visited.contains(&ebr) {
+            visited.push(ebr);
+            inner.read_block(ebr as usize, &mut block)?;
+
+            if block.as_ref()[0x1fe..0x200] != [0x55, 0xAA] {
+                warn!("Bad EBR signature at LBA {}", ebr);
+                break;
+            }
+
+            let [part, next, ..] = parse_entries(&block);
+
+            if part.is_used() {
+                let part = part.rebase(ebr);
+                trace!("Logical Partition: {:#?}", part);
+                logical.push(part);
+            }
+
+            if !next.is_extended() {
+                return Ok(logical);
+            }
+
+            ebr = base + next.begin_lba();
+        }
+
+        warn!("EBR chain is too long or broken, stopped at LBA {}", ebr);
+        Ok(logical)
+    }
+}
+
 impl<T, B> PartitionTable<T, B> for MbrTable<T, B>
 where
     T: BlockDevice<B> + Clone,
@@ -31,40 +99,110 @@ where
         inner.read_block(0, &mut block)?;
 
         let mut partitions = Vec::with_capacity(4);
-        let buffer = block.as_ref();
+        let mut logical = Vec::new();
+
+        for (i, part) in parse_entries(&block).into_iter().enumerate() {
+            if !part.is_used() {
+                continue;
+            }
 
-        for i in 0..4 {
-            partitions.push(MbrPartition::parse(
-                buffer[0x1be + (i * 16)..0x1be + (i * 16) + 16]
-                    .try_into()
-                    .unwrap(),
-            ));
+            trace!("Partition {}: {:#?}", i, part);
 
-            if partitions[i].is_active() {
-                trace!("Partition {}: {:#?}", i, partitions[i]);
+            if part.is_extended() {
+                logical.extend(Self::parse_logical(&inner, &part)?);
+            } else {
+                partitions.push(part);
             }
         }
 
+        partitions.extend(logical);
+
         Ok(Self {
             inner,
-            partitions: partitions.try_into().unwrap(),
+            partitions,
             _block: PhantomData,
         })
     }
 
     fn partitions(&self) -> FsResult<Vec<Partition<T, B>>> {
-        let mut parts = Vec::new();
-
-        for part in self.partitions {
-            if part.is_active() {
-                parts.push(Partition::new(
+        Ok(self
+            .partitions
+            .iter()
+            .map(|part| {
+                Partition::new(
                     self.inner.clone(),
                     part.begin_lba() as usize,
                     part.total_lba() as usize,
-                ));
-            }
-        }
+                    PartitionKind::Mbr(part.filesystem_flag()),
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_entry(disk: &mut [u8], lba: usize, index: usize, kind: u8, begin: u32, total: u32) {
+        let offset = lba * 512 + 0x1be + index * 16;
+        disk[offset + 4] = kind;
+        disk[offset + 8..offset + 12].copy_from_slice(&begin.to_le_bytes());
+        disk[offset + 12..offset + 16].copy_from_slice(&total.to_le_bytes());
+        disk[lba * 512 + 0x1fe..lba * 512 + 0x200].copy_from_slice(&[0x55, 0xAA]);
+    }
+
+    #[test]
+    fn ebr_chain_test() {
+        let mut disk = vec![0u8; 128 * 512];
+
+        set_entry(&mut disk, 0, 0, 0x06, 1, 10);
+        set_entry(&mut disk, 0, 1, 0x0f, 20, 100);
+        // first EBR, the logical partition is relative to the EBR
+        set_entry(&mut disk, 20, 0, 0x0c, 2, 10);
+        // the next EBR is relative to the extended partition
+        set_entry(&mut disk, 20, 1, 0x05, 40, 20);
+        set_entry(&mut disk, 60, 0, 0x83, 2, 5);
+
+        let disk = RamDisk::from_vec(disk);
+        let table = MbrTable::<_, Block512>::parse(disk.clone()).unwrap();
+
+        let lbas: Vec<_> = table
+            .entries()
+            .iter()
+            .map(|part| (part.filesystem_flag(), part.begin_lba(), part.total_lba()))
+            .collect();
+        assert_eq!(lbas, [(0x06, 1, 10), (0x0c, 22, 10), (0x83, 62, 5)]);
+
+        let parts = table.partitions().unwrap();
+        assert_eq!(parts[1].kind(), PartitionKind::Mbr(0x0c));
+
+        // the size of each partition, not of the disk
+        let sizes: Vec<_> = parts
+            .iter()
+            .map(|part| part.block_count().unwrap())
+            .collect();
+        assert_eq!(sizes, [10, 10, 5]);
+
+        let block = Block512::new(&[0x5a; 512]);
+        parts[2].write_block(4, &block).unwrap();
+        assert_eq!(disk.to_vec()[66 * 512], 0x5a);
+        assert!(parts[2].write_block(5, &block).is_err());
+    }
+
+    #[test]
+    fn ebr_loop_test() {
+        let mut disk = vec![0u8; 64 * 512];
+
+        set_entry(&mut disk, 0, 0, 0x05, 10, 50);
+        // the second EBR points back to the first one
+        set_entry(&mut disk, 10, 0, 0x06, 1, 4);
+        set_entry(&mut disk, 10, 1, 0x05, 20, 10);
+        set_entry(&mut disk, 30, 0, 0x06, 1, 4);
+        set_entry(&mut disk, 30, 1, 0x05, 0, 50);
 
-        Ok(parts)
+        let disk = RamDisk::from_vec(disk);
+        let table = MbrTable::<_, Block512>::parse(disk).unwrap();
+        assert_eq!(table.entries().len(), 2);
     }
 }
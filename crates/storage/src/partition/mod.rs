@@ -2,6 +2,7 @@ use core::marker::PhantomData;
 
 use crate::*;
 
+pub mod gpt;
 pub mod mbr;
 
 /// Partition table trait
@@ -18,7 +19,17 @@ where
     fn partitions(&self) -> FsResult<Vec<Partition<T, B>>>;
 }
 
+/// What a partition table says a partition contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// The MBR partition type byte, e.g. 0x06 for FAT16, 0x0c for FAT32
+    Mbr(u8),
+    /// The GPT partition type GUID
+    Gpt(gpt::Guid),
+}
+
 /// Identifies a partition on the disk.
+#[derive(Clone)]
 pub struct Partition<T, B>
 where
     T: BlockDevice<B>,
@@ -27,6 +38,7 @@ where
     inner: T,
     offset: usize,
     size: usize,
+    kind: PartitionKind,
     _block: PhantomData<B>,
 }
 
@@ -35,14 +47,19 @@ where
     T: BlockDevice<B>,
     B: BlockTrait,
 {
-    pub fn new(inner: T, offset: usize, size: usize) -> Self {
+    pub fn new(inner: T, offset: usize, size: usize, kind: PartitionKind) -> Self {
         Self {
             inner,
             offset,
             size,
+            kind,
             _block: PhantomData,
         }
     }
+
+    pub fn kind(&self) -> PartitionKind {
+        self.kind
+    }
 }
 
 impl<T, B> core::fmt::Debug for Partition<T, B>
@@ -54,6 +71,7 @@ where
         f.debug_struct("Partition")
             .field("offset", &self.offset)
             .field("size", &self.size)
+            .field("kind", &self.kind)
             .finish()
     }
 }
@@ -64,7 +82,7 @@ where
     B: BlockTrait,
 {
     fn block_count(&self) -> FsResult<usize> {
-        self.inner.block_count()
+        Ok(self.size)
     }
 
     fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
@@ -84,4 +102,25 @@ where
         let offset = offset + self.offset;
         self.inner.write_block(offset, block)
     }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> FsResult {
+        if offset + blocks.len() > self.size {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let offset = offset + self.offset;
+        self.inner.read_blocks(offset, blocks)
+    }
+
+    fn flush(&self) -> FsResult {
+        self.inner.flush()
+    }
+
+    fn begin_transaction(&self) -> FsResult {
+        self.inner.begin_transaction()
+    }
+
+    fn end_transaction(&self) -> FsResult {
+        self.inner.end_transaction()
+    }
 }
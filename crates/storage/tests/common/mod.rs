@@ -0,0 +1,867 @@
+//! Helpers shared by the integration tests
+//!
+//! `fat_disk` lays out a partitioned FAT16 / FAT32 disk the way `mkfs.fat`
+//! and `mcopy` would, so the drivers can be tested without host tools.
+//! `mkfs_fat_disk` uses the real tools when they are installed.
+
+#![allow(dead_code)]
+
+use std::{path::Path, process::Command};
+
+use ysos_storage::{mbr::MbrTable, *};
+
+/// First sector of the partition in the generated disks
+pub const PART_START: usize = 2048;
+
+pub enum Node {
+    File(&'static str, Vec<u8>),
+    Dir(&'static str, Vec<Node>),
+}
+
+/// Content of BIG.BIN, spans many clusters
+pub fn big_data() -> Vec<u8> {
+    (0..10000).map(|i| ((i * 7) % 251) as u8).collect()
+}
+
+/// The tree every fixture disk holds
+pub fn sample_tree() -> Vec<Node> {
+    vec![
+        Node::File("HELLO.TXT", b"Hello, world!\n".to_vec()),
+        Node::File("BIG.BIN", big_data()),
+        Node::File("EMPTY", Vec::new()),
+        Node::File("Long File Name.txt", b"long\n".to_vec()),
+        Node::Dir(
+            "SUB",
+            vec![
+                Node::File("INNER.TXT", b"inner file\n".to_vec()),
+                Node::Dir(
+                    "nested dir",
+                    vec![Node::File("DEEP.TXT", b"deep\n".to_vec())],
+                ),
+            ],
+        ),
+    ]
+}
+
+/// Names as `read_dir` reports them
+pub fn names(fs: &dyn FileSystem, path: &str) -> Vec<String> {
+    fs.read_dir(path).unwrap().map(|meta| meta.name).collect()
+}
+
+pub fn read(fs: &dyn FileSystem, path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+    buf
+}
+
+/// Every file and directory of `nodes` can be found under `dir` with the
+/// same content
+pub fn check_tree(fs: &dyn FileSystem, dir: &str, nodes: &[Node]) {
+    let mut listed: Vec<_> = names(fs, dir)
+        .into_iter()
+        .filter(|name| name != "." && name != "..")
+        .collect();
+    listed.sort();
+
+    let mut expected: Vec<_> = nodes
+        .iter()
+        .map(|node| match node {
+            Node::File(name, _) | Node::Dir(name, _) => name.to_string(),
+        })
+        .collect();
+    expected.sort();
+
+    assert_eq!(listed, expected, "listing of {}", dir);
+
+    for node in nodes {
+        match node {
+            Node::File(name, data) => {
+                assert_eq!(&read(fs, &format!("{}/{}", dir, name)), data);
+            }
+            Node::Dir(name, children) => {
+                check_tree(fs, &format!("{}/{}", dir, name), children);
+            }
+        }
+    }
+}
+
+/// The first partition of a disk
+pub fn first_partition<T>(disk: T) -> Partition<T, Block512>
+where
+    T: BlockDevice<Block512> + Clone,
+{
+    MbrTable::parse(disk)
+        .unwrap()
+        .partitions()
+        .unwrap()
+        .remove(0)
+}
+
+struct Layout {
+    fat32: bool,
+    total: usize,
+    spc: usize,
+    reserved: usize,
+    fat_size: usize,
+    root_entries: usize,
+}
+
+impl Layout {
+    fn fat16() -> Self {
+        // 16 MiB, 2 KiB clusters
+        Self {
+            fat32: false,
+            total: 32768,
+            spc: 4,
+            reserved: 1,
+            fat_size: 32,
+            root_entries: 512,
+        }
+    }
+
+    fn fat32() -> Self {
+        // 64 MiB, 512 B clusters, enough clusters to be a valid FAT32
+        Self {
+            fat32: true,
+            total: 131072,
+            spc: 1,
+            reserved: 32,
+            fat_size: 1024,
+            root_entries: 0,
+        }
+    }
+
+    fn root_start(&self) -> usize {
+        self.reserved + 2 * self.fat_size
+    }
+
+    fn data_start(&self) -> usize {
+        self.root_start() + self.root_entries * 32 / 512
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.spc * 512
+    }
+}
+
+struct Builder {
+    layout: Layout,
+    image: Vec<u8>,
+    fat: Vec<u32>,
+    next: u32,
+}
+
+impl Builder {
+    fn alloc(&mut self, count: usize) -> Vec<u32> {
+        let chain: Vec<u32> = (self.next..self.next + count as u32).collect();
+        self.next += count as u32;
+
+        let eoc = if self.layout.fat32 {
+            0x0FFF_FFFF
+        } else {
+            0xFFFF
+        };
+        for pair in chain.windows(2) {
+            self.fat[pair[0] as usize] = pair[1];
+        }
+        if let Some(&last) = chain.last() {
+            self.fat[last as usize] = eoc;
+        }
+
+        chain
+    }
+
+    fn write_chain(&mut self, chain: &[u32], data: &[u8]) {
+        let cluster_size = self.layout.cluster_size();
+        for (cluster, chunk) in chain.iter().zip(data.chunks(cluster_size)) {
+            let sector = self.layout.data_start() + (*cluster as usize - 2) * self.layout.spc;
+            self.image[sector * 512..sector * 512 + chunk.len()].copy_from_slice(chunk);
+        }
+    }
+
+    fn write_file(&mut self, data: &[u8]) -> u32 {
+        let chain = self.alloc(data.len().div_ceil(self.layout.cluster_size()));
+        self.write_chain(&chain, data);
+        chain.first().copied().unwrap_or(0)
+    }
+
+    /// Raw entries of a directory, `parent` is 0 for the root
+    fn dir_entries(&mut self, nodes: &[Node], own: u32, parent: u32) -> Vec<[u8; 32]> {
+        let mut entries = Vec::new();
+
+        if own != 0 {
+            entries.push(short_entry(b".          ", 0x10, own, 0));
+            entries.push(short_entry(b"..         ", 0x10, parent, 0));
+        }
+
+        for (index, node) in nodes.iter().enumerate() {
+            let (name, attr, cluster, size) = match node {
+                Node::File(name, data) => (*name, 0x20, self.write_file(data), data.len()),
+                Node::Dir(name, children) => (*name, 0x10, self.write_dir(children, own), 0),
+            };
+
+            let sfn = match short_name(name) {
+                Some(sfn) => sfn,
+                None => {
+                    let sfn = tailed_name(name, index + 1);
+                    entries.extend(lfn_entries(name, &sfn));
+                    sfn
+                }
+            };
+
+            entries.push(short_entry(&sfn, attr, cluster, size as u32));
+        }
+
+        entries
+    }
+
+    fn write_dir(&mut self, nodes: &[Node], parent: u32) -> u32 {
+        // allocate first so the entries can point to "."
+        let slots = nodes.len() * 3 + 2;
+        let clusters = (slots * 32).div_ceil(self.layout.cluster_size());
+        let chain = self.alloc(clusters);
+
+        let entries = self.dir_entries(nodes, chain[0], parent);
+        self.write_chain(&chain, &entries.concat());
+        chain[0]
+    }
+
+    fn write_root(&mut self, nodes: &[Node]) -> u32 {
+        if self.layout.fat32 {
+            let slots = nodes.len() * 3;
+            let chain = self.alloc((slots * 32).div_ceil(self.layout.cluster_size()).max(1));
+            let entries = self.dir_entries(nodes, 0, 0);
+            self.write_chain(&chain, &entries.concat());
+            chain[0]
+        } else {
+            let entries = self.dir_entries(nodes, 0, 0).concat();
+            let start = self.layout.root_start() * 512;
+            self.image[start..start + entries.len()].copy_from_slice(&entries);
+            0
+        }
+    }
+}
+
+fn short_entry(sfn: &[u8; 11], attr: u8, cluster: u32, size: u32) -> [u8; 32] {
+    // 2025-06-16 10:00:00
+    let time: u32 = (45 << 25) | (6 << 21) | (16 << 16) | (10 << 11);
+
+    let mut entry = [0u8; 32];
+    entry[..11].copy_from_slice(sfn);
+    entry[11] = attr;
+    entry[14..18].copy_from_slice(&time.to_le_bytes());
+    entry[18..20].copy_from_slice(&((time >> 16) as u16).to_le_bytes());
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[22..26].copy_from_slice(&time.to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// The 8.3 form of `name`, if it is a plain upper case 8.3 name
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let valid = |s: &str, len| {
+        s.len() <= len
+            && s.bytes()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b'_')
+    };
+
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) {
+        return None;
+    }
+
+    let mut sfn = [b' '; 11];
+    sfn[..base.len()].copy_from_slice(base.as_bytes());
+    sfn[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(sfn)
+}
+
+/// A "NAME~N" short name, unique within a directory thanks to `n`
+fn tailed_name(name: &str, n: usize) -> [u8; 11] {
+    let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let clean = |s: &str| -> Vec<u8> {
+        s.bytes()
+            .filter(u8::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    };
+
+    let tail = format!("~{}", n);
+    let base = clean(base);
+    let keep = base.len().min(8 - tail.len());
+
+    let mut sfn = [b' '; 11];
+    sfn[..keep].copy_from_slice(&base[..keep]);
+    sfn[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+    for (slot, c) in sfn[8..].iter_mut().zip(clean(ext)) {
+        *slot = c;
+    }
+    sfn
+}
+
+fn lfn_entries(name: &str, sfn: &[u8; 11]) -> Vec<[u8; 32]> {
+    const OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+    let checksum = sfn
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c));
+
+    let mut chars: Vec<u16> = name.encode_utf16().collect();
+    let count = chars.len().div_ceil(13);
+    if chars.len() < count * 13 {
+        chars.push(0);
+        chars.resize(count * 13, 0xFFFF);
+    }
+
+    (1..=count)
+        .rev()
+        .map(|seq| {
+            let mut entry = [0u8; 32];
+            entry[0] = seq as u8 | if seq == count { 0x40 } else { 0 };
+            entry[11] = 0x0f;
+            entry[13] = checksum;
+            for (ch, offset) in chars[(seq - 1) * 13..seq * 13].iter().zip(OFFSETS) {
+                entry[offset..offset + 2].copy_from_slice(&ch.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+fn boot_sector(layout: &Layout) -> [u8; 512] {
+    let mut bpb = [0u8; 512];
+    bpb[..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    bpb[3..11].copy_from_slice(b"mkfs.fat");
+    bpb[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+    bpb[0x0d] = layout.spc as u8;
+    bpb[0x0e..0x10].copy_from_slice(&(layout.reserved as u16).to_le_bytes());
+    bpb[0x10] = 2;
+    bpb[0x11..0x13].copy_from_slice(&(layout.root_entries as u16).to_le_bytes());
+    bpb[0x15] = 0xf8;
+    bpb[0x18..0x1a].copy_from_slice(&32u16.to_le_bytes());
+    bpb[0x1a..0x1c].copy_from_slice(&8u16.to_le_bytes());
+    bpb[0x1c..0x20].copy_from_slice(&(PART_START as u32).to_le_bytes());
+    bpb[0x20..0x24].copy_from_slice(&(layout.total as u32).to_le_bytes());
+
+    let ebpb = if layout.fat32 {
+        bpb[0x24..0x28].copy_from_slice(&(layout.fat_size as u32).to_le_bytes());
+        // root cluster, FSInfo sector and backup boot sector
+        bpb[0x2c..0x30].copy_from_slice(&2u32.to_le_bytes());
+        bpb[0x30..0x32].copy_from_slice(&1u16.to_le_bytes());
+        bpb[0x32..0x34].copy_from_slice(&6u16.to_le_bytes());
+        bpb[0x52..0x5a].copy_from_slice(b"FAT32   ");
+        0x40
+    } else {
+        bpb[0x16..0x18].copy_from_slice(&(layout.fat_size as u16).to_le_bytes());
+        bpb[0x36..0x3e].copy_from_slice(b"FAT16   ");
+        0x24
+    };
+
+    bpb[ebpb] = 0x80;
+    bpb[ebpb + 2] = 0x29;
+    bpb[ebpb + 3..ebpb + 7].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    bpb[ebpb + 7..ebpb + 18].copy_from_slice(b"YSOS TEST  ");
+    bpb[0x1fe..].copy_from_slice(&[0x55, 0xAA]);
+    bpb
+}
+
+/// A MBR with a single partition starting at `PART_START`
+fn mbr(kind: u8, sectors: usize) -> [u8; 512] {
+    let mut mbr = [0u8; 512];
+    mbr[0x1be] = 0x80;
+    mbr[0x1be + 4] = kind;
+    mbr[0x1be + 8..0x1be + 12].copy_from_slice(&(PART_START as u32).to_le_bytes());
+    mbr[0x1be + 12..0x1be + 16].copy_from_slice(&(sectors as u32).to_le_bytes());
+    mbr[0x1fe..].copy_from_slice(&[0x55, 0xAA]);
+    mbr
+}
+
+/// A MBR partitioned disk with one FAT16 or FAT32 volume holding `tree`
+pub fn fat_disk(fat32: bool, tree: &[Node]) -> RamDisk {
+    let layout = if fat32 {
+        Layout::fat32()
+    } else {
+        Layout::fat16()
+    };
+
+    let mut builder = Builder {
+        image: vec![0; layout.total * 512],
+        fat: vec![0; layout.fat_size * 512 / if fat32 { 4 } else { 2 }],
+        next: 2,
+        layout,
+    };
+
+    builder.fat[0] = if fat32 { 0x0FFF_FFF8 } else { 0xFFF8 };
+    builder.fat[1] = if fat32 { 0x0FFF_FFFF } else { 0xFFFF };
+
+    builder.write_root(tree);
+
+    let Builder {
+        layout,
+        mut image,
+        fat,
+        next,
+    } = builder;
+
+    let boot = boot_sector(&layout);
+    image[..512].copy_from_slice(&boot);
+
+    let raw_fat: Vec<u8> = if fat32 {
+        fat.iter().flat_map(|e| e.to_le_bytes()).collect()
+    } else {
+        fat.iter().flat_map(|e| (*e as u16).to_le_bytes()).collect()
+    };
+    for copy in 0..2 {
+        let start = (layout.reserved + copy * layout.fat_size) * 512;
+        image[start..start + raw_fat.len()].copy_from_slice(&raw_fat);
+    }
+
+    if fat32 {
+        let clusters = (layout.total - layout.data_start()) / layout.spc;
+        let info = &mut image[512..1024];
+        info[..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        info[0x1e4..0x1e8].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        info[0x1e8..0x1ec].copy_from_slice(&(clusters as u32 + 2 - next).to_le_bytes());
+        info[0x1ec..0x1f0].copy_from_slice(&next.to_le_bytes());
+        info[0x1fc..0x200].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+        image[6 * 512..7 * 512].copy_from_slice(&boot);
+    }
+
+    let mut disk = vec![0u8; PART_START * 512];
+    disk[..512].copy_from_slice(&mbr(if fat32 { 0x0c } else { 0x06 }, layout.total));
+    disk.extend(image);
+
+    RamDisk::from_vec(disk)
+}
+
+pub fn has_tool(name: &str) -> bool {
+    Command::new(name)
+        .arg("--help")
+        .output()
+        .is_ok_and(|out| out.status.success() || !out.stdout.is_empty() || !out.stderr.is_empty())
+}
+
+/// Fail if a host tool is missing, for the tests that are ignored unless
+/// asked for with `cargo test -- --ignored`
+pub fn require_tools(names: &[&str]) {
+    for name in names {
+        assert!(
+            has_tool(name),
+            "{} not found, install it to run this test",
+            name
+        );
+    }
+}
+
+fn run(cmd: &mut Command) {
+    // the generated images have no meaningful CHS geometry
+    let out = cmd.env("MTOOLS_SKIP_CHECK", "1").output().unwrap();
+    assert!(
+        out.status.success(),
+        "{:?} failed: {}",
+        cmd,
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+/// Create a MBR partitioned disk image with `mkfs.fat` and fill it with
+/// `mcopy`
+pub fn mkfs_fat_disk(path: &Path, fat_bits: u8, tree: &[Node]) -> ImageFile {
+    require_tools(&["mkfs.fat", "mcopy", "mmd"]);
+
+    let sectors = if fat_bits == 32 { 131072 } else { 32768 };
+    let image = ImageFile::create(path, ((PART_START + sectors) * 512) as u64).unwrap();
+
+    let kind = if fat_bits == 32 { 0x0c } else { 0x06 };
+    image
+        .write_block(0, &Block512::new(&mbr(kind, sectors)))
+        .unwrap();
+
+    run(Command::new("mkfs.fat")
+        .arg("-F")
+        .arg(fat_bits.to_string())
+        .arg("--offset")
+        .arg(PART_START.to_string())
+        .arg(path)
+        .arg((sectors / 2).to_string()));
+
+    let target = format!("{}@@{}", path.display(), PART_START * 512);
+    let staging = path.with_extension("files");
+    std::fs::create_dir_all(&staging).unwrap();
+
+    fn copy(nodes: &[Node], target: &str, staging: &Path, dir: &str) {
+        for node in nodes {
+            match node {
+                Node::File(name, data) => {
+                    let local = staging.join(name);
+                    std::fs::write(&local, data).unwrap();
+                    run(Command::new("mcopy")
+                        .arg("-i")
+                        .arg(target)
+                        .arg(&local)
+                        .arg(format!("::{}/{}", dir, name)));
+                }
+                Node::Dir(name, children) => {
+                    let path = format!("{}/{}", dir, name);
+                    run(Command::new("mmd")
+                        .arg("-i")
+                        .arg(target)
+                        .arg(format!("::{}", path)));
+                    copy(children, target, staging, &path);
+                }
+            }
+        }
+    }
+
+    copy(tree, &target, &staging, "");
+    std::fs::remove_dir_all(&staging).unwrap();
+
+    image
+}
+
+/// Write `nodes` under `dir` on the host
+pub fn stage(nodes: &[Node], dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+    for node in nodes {
+        match node {
+            Node::File(name, data) => std::fs::write(dir.join(name), data).unwrap(),
+            Node::Dir(name, children) => stage(children, &dir.join(name)),
+        }
+    }
+}
+
+/// Create a MBR partitioned disk image with an ext2 volume made by
+/// `mkfs.ext2` from the files in `staging`
+pub fn mkfs_ext2_disk(path: &Path, block_size: usize, staging: &Path) -> ImageFile {
+    require_tools(&["mkfs.ext2"]);
+
+    let sectors = 32768;
+    let image = ImageFile::create(path, ((PART_START + sectors) * 512) as u64).unwrap();
+    image
+        .write_block(0, &Block512::new(&mbr(0x83, sectors)))
+        .unwrap();
+
+    run(Command::new("mkfs.ext2")
+        .args(["-q", "-F", "-b"])
+        .arg(block_size.to_string())
+        .arg("-E")
+        .arg(format!("offset={}", PART_START * 512))
+        .arg("-d")
+        .arg(staging)
+        .arg(path)
+        .arg(format!("{}k", sectors / 2)));
+
+    image
+}
+
+/// Names recorded on a generated ISO 9660 image
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IsoNames {
+    /// Upper case names only
+    Iso,
+    /// A Joliet tree next to the primary one
+    Joliet,
+    /// Rock Ridge names in the primary tree
+    RockRidge,
+}
+
+/// The name `mkisofs` records for `name` without extensions
+pub fn iso_name(name: &str, dir: bool) -> String {
+    let mut iso: String = name
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            c @ ('A'..='Z' | '0'..='9' | '_' | '.') => c,
+            _ => '_',
+        })
+        .collect();
+
+    if !dir {
+        if !iso.contains('.') {
+            iso.push('.');
+        }
+        iso.push_str(";1");
+    }
+    iso
+}
+
+fn both16(value: u16) -> [u8; 4] {
+    let (le, be) = (value.to_le_bytes(), value.to_be_bytes());
+    [le[0], le[1], be[0], be[1]]
+}
+
+fn both32(value: u32) -> [u8; 8] {
+    let mut both = [0u8; 8];
+    both[..4].copy_from_slice(&value.to_le_bytes());
+    both[4..].copy_from_slice(&value.to_be_bytes());
+    both
+}
+
+fn iso_record(ident: &[u8], extent: u32, size: u32, dir: bool, system_use: &[u8]) -> Vec<u8> {
+    let mut record = vec![0u8; 33];
+    record[2..10].copy_from_slice(&both32(extent));
+    record[10..18].copy_from_slice(&both32(size));
+    // 2025-06-16 18:00:00 GMT+8
+    record[18..25].copy_from_slice(&[125, 6, 16, 18, 0, 0, 32]);
+    record[25] = if dir { 0x02 } else { 0 };
+    record[28..32].copy_from_slice(&both16(1));
+    record[32] = ident.len() as u8;
+    record.extend_from_slice(ident);
+    record.resize(record.len().next_multiple_of(2), 0);
+    record.extend_from_slice(system_use);
+    record.resize(record.len().next_multiple_of(2), 0);
+    record[0] = record.len() as u8;
+    record
+}
+
+/// Records packed into sectors, none of them crossing a sector
+fn iso_pack(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for record in records {
+        if data.len() % 2048 + record.len() > 2048 {
+            data.resize(data.len().next_multiple_of(2048), 0);
+        }
+        data.extend_from_slice(record);
+    }
+    data.resize(data.len().next_multiple_of(2048), 0);
+    data
+}
+
+struct IsoBuilder {
+    image: Vec<u8>,
+    next: u32,
+    names: IsoNames,
+}
+
+/// A directory of one tree, with the extents it points to
+#[derive(Clone)]
+struct IsoDir {
+    extent: u32,
+    size: u32,
+    children: Vec<IsoChild>,
+}
+
+/// A name with the extent and size of a file, or a directory
+type IsoChild = (String, Result<(u32, u32), IsoDir>);
+
+impl IsoBuilder {
+    fn alloc(&mut self, len: usize) -> u32 {
+        let extent = self.next;
+        self.next += len.div_ceil(2048).max(1) as u32;
+        self.image.resize(self.next as usize * 2048, 0);
+        extent
+    }
+
+    fn write(&mut self, extent: u32, data: &[u8]) {
+        let start = extent as usize * 2048;
+        self.image[start..start + data.len()].copy_from_slice(data);
+    }
+
+    /// File data is shared by both trees
+    fn files(&mut self, nodes: &[Node]) -> Vec<IsoChild> {
+        nodes
+            .iter()
+            .map(|node| match node {
+                Node::File(name, data) => {
+                    let extent = self.alloc(data.len());
+                    self.write(extent, data);
+                    (name.to_string(), Ok((extent, data.len() as u32)))
+                }
+                Node::Dir(name, children) => {
+                    let children = self.files(children);
+                    let dir = IsoDir {
+                        extent: 0,
+                        size: 0,
+                        children,
+                    };
+                    (name.to_string(), Err(dir))
+                }
+            })
+            .collect()
+    }
+
+    fn ident(&self, name: &str, dir: bool, joliet: bool) -> Vec<u8> {
+        if joliet {
+            name.encode_utf16().flat_map(u16::to_be_bytes).collect()
+        } else {
+            iso_name(name, dir).into_bytes()
+        }
+    }
+
+    /// The system use area of a record, long names are moved to a
+    /// continuation area
+    fn system_use(&mut self, name: &str, root: bool, real: bool) -> Vec<u8> {
+        if self.names != IsoNames::RockRidge {
+            return Vec::new();
+        }
+
+        if root {
+            return b"SP\x07\x01\xbe\xef\x00".to_vec();
+        }
+
+        let mut nm = vec![b'N', b'M', 5 + name.len() as u8, 1, 0];
+        nm.extend_from_slice(name.as_bytes());
+
+        if name.len() <= 16 {
+            return nm;
+        }
+
+        let extent = if real {
+            let extent = self.alloc(nm.len());
+            self.write(extent, &nm);
+            extent
+        } else {
+            0
+        };
+
+        let mut ce = vec![b'C', b'E', 28, 1];
+        ce.extend_from_slice(&both32(extent));
+        ce.extend_from_slice(&both32(0));
+        ce.extend_from_slice(&both32(nm.len() as u32));
+        ce
+    }
+
+    /// The records of `dir`, `parent` is `None` for the root
+    fn records(
+        &mut self,
+        dir: &IsoDir,
+        parent: Option<(u32, u32)>,
+        joliet: bool,
+        real: bool,
+    ) -> Vec<u8> {
+        let dot = self.system_use("", parent.is_none(), real);
+        let parent = parent.unwrap_or((dir.extent, dir.size));
+        let mut records = vec![
+            iso_record(&[0], dir.extent, dir.size, true, &dot),
+            iso_record(&[1], parent.0, parent.1, true, &[]),
+        ];
+
+        for (name, child) in &dir.children {
+            let (extent, size, is_dir) = match child {
+                Ok((extent, size)) => (*extent, *size, false),
+                Err(sub) => (sub.extent, sub.size, true),
+            };
+            let ident = self.ident(name, is_dir, joliet);
+            let system_use = self.system_use(name, false, real);
+            records.push(iso_record(&ident, extent, size, is_dir, &system_use));
+        }
+
+        iso_pack(&records)
+    }
+
+    /// Give every directory its extent, then write them
+    fn layout(&mut self, dir: &mut IsoDir, root: bool, joliet: bool) {
+        let parent = (!root).then_some((0, 0));
+        let size = self.records(dir, parent, joliet, false).len();
+        dir.extent = self.alloc(size);
+        dir.size = size as u32;
+
+        for (_, child) in dir.children.iter_mut() {
+            if let Err(sub) = child {
+                self.layout(sub, false, joliet);
+            }
+        }
+    }
+
+    fn write_dirs(&mut self, dir: &IsoDir, parent: Option<(u32, u32)>, joliet: bool) {
+        let data = self.records(dir, parent, joliet, true);
+        self.write(dir.extent, &data);
+
+        for (_, child) in &dir.children {
+            if let Err(sub) = child {
+                self.write_dirs(sub, Some((dir.extent, dir.size)), joliet);
+            }
+        }
+    }
+
+    /// Little endian path table, directories in breadth first order
+    fn path_table(&self, root: &IsoDir, joliet: bool) -> Vec<u8> {
+        let mut table = Vec::new();
+        let mut queue = vec![(root, Vec::from([0u8]), 1u16)];
+        let mut number = 0;
+
+        while number < queue.len() {
+            let (dir, ident, parent) = queue[number].clone();
+            number += 1;
+
+            table.push(ident.len() as u8);
+            table.push(0);
+            table.extend_from_slice(&dir.extent.to_le_bytes());
+            table.extend_from_slice(&parent.to_le_bytes());
+            table.extend_from_slice(&ident);
+            table.resize(table.len().next_multiple_of(2), 0);
+
+            for (name, child) in &dir.children {
+                if let Err(sub) = child {
+                    queue.push((sub, self.ident(name, true, joliet), number as u16));
+                }
+            }
+        }
+
+        table
+    }
+}
+
+fn iso_descriptor(kind: u8, root: &IsoDir, table: (u32, usize)) -> Vec<u8> {
+    let mut descriptor = vec![0u8; 2048];
+    descriptor[0] = kind;
+    descriptor[1..6].copy_from_slice(b"CD001");
+    descriptor[6] = 1;
+    descriptor[8..72].fill(b' ');
+    descriptor[40..49].copy_from_slice(b"YSOS TEST");
+    if kind == 2 {
+        descriptor[88..91].copy_from_slice(b"%/E");
+    }
+    descriptor[120..124].copy_from_slice(&both16(1));
+    descriptor[124..128].copy_from_slice(&both16(1));
+    descriptor[128..132].copy_from_slice(&both16(2048));
+    descriptor[132..140].copy_from_slice(&both32(table.1 as u32));
+    descriptor[140..144].copy_from_slice(&table.0.to_le_bytes());
+    descriptor[156..190].copy_from_slice(&iso_record(&[0], root.extent, root.size, true, &[]));
+    descriptor[881] = 1;
+    descriptor
+}
+
+/// An ISO 9660 image holding `tree` laid out the way `mkisofs` would
+pub fn iso_image(names: IsoNames, tree: &[Node]) -> RamDisk {
+    let mut builder = IsoBuilder {
+        image: Vec::new(),
+        // system area, descriptors and one sector for each path table
+        next: 21,
+        names,
+    };
+    builder.image.resize(21 * 2048, 0);
+
+    let files = IsoDir {
+        extent: 0,
+        size: 0,
+        children: builder.files(tree),
+    };
+
+    let mut trees = vec![(1, 19, false)];
+    if names == IsoNames::Joliet {
+        trees.push((2, 20, true));
+    }
+
+    let mut descriptors = Vec::new();
+    for (kind, table_extent, joliet) in trees {
+        let mut root = files.clone();
+        builder.layout(&mut root, true, joliet);
+        builder.write_dirs(&root, None, joliet);
+
+        let table = builder.path_table(&root, joliet);
+        builder.write(table_extent, &table);
+        descriptors.push(iso_descriptor(kind, &root, (table_extent, table.len())));
+    }
+
+    let total = builder.next;
+    let mut sector = 16;
+    for mut descriptor in descriptors {
+        descriptor[80..88].copy_from_slice(&both32(total));
+        builder.write(sector, &descriptor);
+        sector += 1;
+    }
+    builder.write(sector, b"\xffCD001\x01");
+
+    RamDisk::from_vec(builder.image)
+}
@@ -0,0 +1,41 @@
+//! FAT16 on an encrypted partition of a host image file
+
+mod common;
+
+use common::*;
+use ysos_storage::{fat16::Fat16, *};
+
+#[test]
+fn test_encrypted_image() {
+    let path = std::env::temp_dir().join(format!("ysos-crypt-{}.img", std::process::id()));
+    let image = ImageFile::create(&path, 16 * 1024 * 1024 + HEADER_SIZE as u64).unwrap();
+
+    CryptDevice::<Block512, _>::format(&image, b"correct horse", 64).unwrap();
+    let device = CryptDevice::open(image, b"correct horse").unwrap();
+    let fs = Fat16::format(device, &Default::default()).unwrap();
+
+    fs.create_dir("/SUB").unwrap();
+    fs.create_file("/SUB/SECRET.TXT")
+        .unwrap()
+        .write_all(b"attack at dawn")
+        .unwrap();
+    fs.sync().unwrap();
+    drop(fs);
+
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!raw.windows(14).any(|window| window == b"attack at dawn"));
+    assert!(!raw.windows(8).any(|window| window == b"FAT16   "));
+
+    let image = ImageFile::open(&path).unwrap();
+    let fs = Fat16::new(CryptDevice::open(image, b"correct horse").unwrap());
+    assert_eq!(read(&fs, "/SUB/SECRET.TXT"), b"attack at dawn");
+    assert!(fs.check(false).unwrap().is_clean());
+
+    let image = ImageFile::open(&path).unwrap();
+    assert_eq!(
+        CryptDevice::<Block512, _>::open(image, b"wrong horse").unwrap_err(),
+        FsError::WrongPassphrase
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
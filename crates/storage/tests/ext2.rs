@@ -0,0 +1,153 @@
+//! Ext2 driver, on images made by `mkfs.ext2` on the host and mounted
+//! through `MbrTable`. Ignored by default, run with `cargo test -- --ignored`
+//! where `mkfs.ext2` is installed.
+
+mod common;
+
+use std::{
+    os::unix::fs as unix,
+    path::{Path, PathBuf},
+};
+
+use common::*;
+use ysos_storage::{ext2::Ext2, *};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ysos-ext2-{}-{}", name, std::process::id()))
+}
+
+/// Past the direct and single indirect blocks with 1 KiB blocks
+fn huge_data() -> Vec<u8> {
+    (0..300_000).map(|i| ((i * 13) % 253) as u8).collect()
+}
+
+/// `sample_tree` plus links, a sparse file and unusual permissions
+fn staging(name: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_path(name);
+    stage(&sample_tree(), &dir);
+
+    std::fs::write(dir.join("HUGE.BIN"), huge_data()).unwrap();
+    std::fs::hard_link(dir.join("HELLO.TXT"), dir.join("SUB/hello link")).unwrap();
+    unix::symlink("SUB/nested dir", dir.join("nested")).unwrap();
+    unix::symlink("/HELLO.TXT", dir.join("SUB/absolute")).unwrap();
+    unix::symlink("loop", dir.join("loop")).unwrap();
+    // too long to be kept in the inode
+    let target = format!("{}SUB/INNER.TXT", "./".repeat(40));
+    unix::symlink(&target, dir.join("slow")).unwrap();
+
+    std::fs::set_permissions(dir.join("EMPTY"), std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    let sparse = std::fs::File::create(dir.join("SPARSE")).unwrap();
+    sparse.set_len(5 * 1024 * 1024).unwrap();
+    drop(sparse);
+
+    dir
+}
+
+fn mount(name: &str, block_size: usize) -> (Ext2, PathBuf) {
+    let dir = staging(name);
+    let path = temp_path(name).with_extension("img");
+    let image = mkfs_ext2_disk(&path, block_size, &dir);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let fs = Ext2::new(first_partition(image)).unwrap();
+    (fs, path)
+}
+
+fn check(fs: &Ext2, path: &Path) {
+    use std::os::unix::fs::MetadataExt;
+
+    for name in ["HELLO.TXT", "BIG.BIN", "EMPTY", "SUB", "nested", "slow"] {
+        assert!(fs.exists(name).unwrap(), "{}", name);
+    }
+
+    // the absolute link resolves from the root of the volume
+    let Node::Dir(_, mut sub) = sample_tree().remove(4) else {
+        unreachable!()
+    };
+    sub.push(Node::File("hello link", b"Hello, world!\n".to_vec()));
+    sub.push(Node::File("absolute", b"Hello, world!\n".to_vec()));
+    check_tree(fs, "/SUB", &sub);
+
+    assert_eq!(read(fs, "/BIG.BIN"), big_data());
+    assert_eq!(read(fs, "/HUGE.BIN"), huge_data());
+    assert_eq!(read(fs, "/nested/DEEP.TXT"), b"deep\n");
+    assert_eq!(read(fs, "/slow"), b"inner file\n");
+
+    let sparse = read(fs, "/SPARSE");
+    assert_eq!(sparse.len(), 5 * 1024 * 1024);
+    assert!(sparse.iter().all(|&b| b == 0));
+
+    // hard links share the inode, "." of the root is inode 2
+    let hello = fs.metadata("/HELLO.TXT").unwrap();
+    assert_eq!(fs.metadata("/SUB/hello link").unwrap().inode, hello.inode);
+    assert_eq!(fs.metadata("/").unwrap().inode, Some(2));
+    assert_eq!(fs.metadata("/EMPTY").unwrap().mode, Some(0o100640));
+
+    // `mkfs.ext2 -d` keeps the owner of the files, who also owns the image
+    let owner = std::fs::metadata(path).unwrap();
+    assert_eq!(hello.uid, Some(owner.uid()));
+    assert_eq!(hello.gid, Some(owner.gid()));
+
+    let root: Vec<_> = fs.read_dir("/").unwrap().collect();
+    let nested = root.iter().find(|meta| meta.name == "nested").unwrap();
+    assert!(nested.is_symlink());
+    assert_eq!(nested.len, "SUB/nested dir".len());
+    assert!(root.iter().any(|meta| meta.name == "." && meta.is_dir()));
+
+    assert_eq!(fs.read_link("/nested").unwrap(), "SUB/nested dir");
+    assert!(matches!(
+        fs.open_file("/loop").unwrap_err(),
+        FsError::InvalidPath(_)
+    ));
+    assert_eq!(fs.open_file("/SUB").unwrap_err(), FsError::NotAFile);
+    assert_eq!(fs.read_dir("/EMPTY").err(), Some(FsError::NotADirectory));
+    assert_eq!(fs.create_file("/NEW").unwrap_err(), FsError::NotSupported);
+
+    let mut file = fs.open_file("/HUGE.BIN").unwrap();
+    file.seek(SeekFrom::Start(270_000)).unwrap();
+    let mut buf = [0u8; 1000];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[..], huge_data()[270_000..271_000]);
+    assert_eq!(file.write(b"x"), Err(FsError::ReadOnly));
+
+    assert!(fs.statfs("/").unwrap().read_only);
+}
+
+#[test]
+#[ignore = "needs mkfs.ext2"]
+fn test_mkfs_ext2_1k() {
+    let (fs, path) = mount("1k", 1024);
+    check(&fs, &path);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[ignore = "needs mkfs.ext2"]
+fn test_mkfs_ext2_4k() {
+    let (fs, path) = mount("4k", 4096);
+    check(&fs, &path);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[ignore = "needs mkfs.ext2"]
+fn test_unsupported_features() {
+    let dir = staging("ext4");
+    let path = temp_path("ext4").with_extension("img");
+    let image = mkfs_ext2_disk(&path, 1024, &dir);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    // mark the volume as using extents, like ext4 does
+    let part = first_partition(image);
+    let mut block = Block512::default();
+    part.read_block(2, &mut block).unwrap();
+    block.as_mut()[0x60] |= 0x40;
+    part.write_block(2, &block).unwrap();
+
+    assert!(!Ext2::detect(&part));
+    assert_eq!(Ext2::new(part).unwrap_err(), FsError::NotSupported);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,348 @@
+//! Fat16 driver, mounted through `MbrTable` on a generated disk
+
+mod common;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use common::*;
+use ysos_storage::{fat16::Fat16, *};
+
+fn mount(disk: &RamDisk) -> Fat16 {
+    Fat16::new(first_partition(disk.clone()))
+}
+
+#[test]
+fn test_read() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+
+    assert_eq!(
+        names(&fs, "/"),
+        ["HELLO.TXT", "BIG.BIN", "EMPTY", "Long File Name.txt", "SUB"]
+    );
+    assert_eq!(names(&fs, "/SUB"), [".", "..", "INNER.TXT", "nested dir"]);
+    assert_eq!(names(&fs, "/SUB/nested dir/.."), names(&fs, "/SUB"));
+
+    assert_eq!(read(&fs, "/HELLO.TXT"), b"Hello, world!\n");
+    assert_eq!(read(&fs, "/hello.txt"), b"Hello, world!\n");
+    assert_eq!(read(&fs, "/BIG.BIN"), big_data());
+    assert_eq!(read(&fs, "/EMPTY"), b"");
+    assert_eq!(read(&fs, "/long file name.TXT"), b"long\n");
+    assert_eq!(read(&fs, "/LONGFI~4.TXT"), b"long\n");
+    assert_eq!(read(&fs, "/SUB/nested dir/DEEP.TXT"), b"deep\n");
+
+    let meta = fs.metadata("/BIG.BIN").unwrap();
+    assert_eq!(meta.len, 10000);
+    assert!(fs.metadata("/SUB").unwrap().is_dir());
+
+    assert_eq!(fs.open_file("/MISSING").unwrap_err(), FsError::FileNotFound);
+    assert_eq!(fs.open_file("/SUB").unwrap_err(), FsError::NotAFile);
+    assert_eq!(
+        fs.open_file("/HELLO.TXT/X").unwrap_err(),
+        FsError::NotADirectory
+    );
+}
+
+#[test]
+fn test_write() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+
+    let mut file = fs.open_file("/HELLO.TXT").unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(&[b'x'; 5000]).unwrap();
+    drop(file);
+
+    let data: Vec<u8> = (0..7000u32).map(|i| (i % 13) as u8).collect();
+    fs.open_file("/EMPTY").unwrap().write_all(&data).unwrap();
+
+    // everything is on the disk after a remount
+    let fs = mount(&disk);
+    let hello = read(&fs, "/HELLO.TXT");
+    assert_eq!(hello.len(), 5014);
+    assert_eq!(&hello[..14], b"Hello, world!\n");
+    assert!(hello[14..].iter().all(|&c| c == b'x'));
+    assert_eq!(read(&fs, "/EMPTY"), data);
+    assert_eq!(read(&fs, "/BIG.BIN"), big_data());
+}
+
+#[test]
+fn test_seek() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+    let expected = big_data();
+
+    let mut file = fs.open_file("/BIG.BIN").unwrap();
+    let mut buf = [0u8; 100];
+    for pos in [9000, 10, 4095, 2048, 0, 8190] {
+        assert_eq!(file.seek(SeekFrom::Start(pos)).unwrap(), pos);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &expected[pos..pos + 100]);
+    }
+
+    assert_eq!(file.seek(SeekFrom::End(-50)).unwrap(), 9950);
+    assert_eq!(file.read(&mut buf).unwrap(), 50);
+    assert_eq!(file.seek(SeekFrom::Current(-100)).unwrap(), 9900);
+    assert!(file.seek(SeekFrom::Current(-100000)).is_err());
+
+    // writing past the end leaves a zeroed hole
+    file.seek(SeekFrom::Start(12000)).unwrap();
+    file.write_all(b"tail").unwrap();
+    drop(file);
+
+    let data = read(&fs, "/BIG.BIN");
+    assert_eq!(data.len(), 12004);
+    assert_eq!(&data[..10000], &expected[..]);
+    assert!(data[10000..12000].iter().all(|&c| c == 0));
+    assert_eq!(&data[12000..], b"tail");
+
+    // the size of a file is 32 bits, nothing is allocated past it
+    let free = fs.statfs("/").unwrap().free_blocks;
+    let mut file = fs.open_file("/BIG.BIN").unwrap();
+    let max = u32::MAX as usize;
+    assert_eq!(file.seek(SeekFrom::Start(max)).unwrap(), max);
+    assert_eq!(file.write(b"x").unwrap_err(), FsError::WriteZero);
+    assert!(file.seek(SeekFrom::Current(1)).is_err());
+    drop(file);
+    assert_eq!(fs.metadata("/BIG.BIN").unwrap().len, 12004);
+    assert_eq!(fs.statfs("/").unwrap().free_blocks, free);
+}
+
+#[test]
+fn test_create_remove_rename() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+
+    fs.create_file("/NEW.TXT")
+        .unwrap()
+        .write_all(b"new file")
+        .unwrap();
+    assert_eq!(
+        fs.create_file("/new.txt").unwrap_err(),
+        FsError::AlreadyExists
+    );
+
+    // more entries than a single cluster holds
+    fs.create_dir("/DIR").unwrap();
+    fs.create_dir("/DIR/NESTED").unwrap();
+    for i in 0..100 {
+        fs.create_file(&format!("/DIR/F{}.TXT", i)).unwrap();
+    }
+    assert_eq!(names(&fs, "/DIR").len(), 103);
+    assert_eq!(names(&fs, "/DIR/NESTED"), [".", ".."]);
+
+    fs.copy_file("/BIG.BIN", "/DIR/NESTED/COPY.BIN").unwrap();
+    assert_eq!(read(&fs, "/DIR/NESTED/COPY.BIN"), big_data());
+    assert_eq!(
+        fs.remove_dir("/DIR/NESTED").unwrap_err(),
+        FsError::DirectoryNotEmpty
+    );
+
+    fs.move_file("/DIR/NESTED/COPY.BIN", "/SUB/MOVED.BIN")
+        .unwrap();
+    assert!(!fs.exists("/DIR/NESTED/COPY.BIN").unwrap());
+    fs.remove_dir("/DIR/NESTED").unwrap();
+
+    fs.move_dir("/DIR", "/SUB/DIR2").unwrap();
+    assert_eq!(
+        fs.move_dir("/SUB", "/SUB/DIR2/X").unwrap_err(),
+        FsError::InvalidOperation
+    );
+    assert_eq!(names(&fs, "/SUB/DIR2/.."), names(&fs, "/SUB"));
+
+    for i in 0..100 {
+        fs.remove_file(&format!("/SUB/DIR2/F{}.TXT", i)).unwrap();
+    }
+    fs.remove_dir("/SUB/DIR2").unwrap();
+
+    fs.append_file("/NEW.TXT").unwrap().write_all(b"!").unwrap();
+    assert_eq!(read(&fs, "/NEW.TXT"), b"new file!");
+
+    let fs = mount(&disk);
+    assert_eq!(
+        names(&fs, "/SUB"),
+        [".", "..", "INNER.TXT", "nested dir", "MOVED.BIN"]
+    );
+    assert_eq!(read(&fs, "/SUB/MOVED.BIN"), big_data());
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+}
+
+#[test]
+fn test_dot_entries_stay() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+    let misplaced = FsError::FileNameError(FilenameError::MisplacedPeriod);
+
+    for path in ["/SUB/.", "/SUB/..", "/SUB/./"] {
+        assert_eq!(fs.remove_dir(path).unwrap_err(), misplaced);
+        assert_eq!(fs.move_dir(path, "/MOVED").unwrap_err(), misplaced);
+    }
+    assert_eq!(fs.create_dir("/SUB/..").unwrap_err(), misplaced);
+    assert_eq!(names(&fs, "/SUB"), [".", "..", "INNER.TXT", "nested dir"]);
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+}
+
+#[test]
+fn test_truncate_open_file() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+    let free = fs.statfs("/").unwrap().free_blocks;
+    let created = fs.metadata("/BIG.BIN").unwrap().created;
+
+    // the entry stays, the clusters are freed
+    let mut file = fs.truncate_file("/BIG.BIN").unwrap();
+    let meta = fs.metadata("/BIG.BIN").unwrap();
+    assert_eq!(meta.len, 0);
+    assert_eq!(meta.created, created);
+    assert!(fs.statfs("/").unwrap().free_blocks > free);
+
+    // an open file keeps its entry where it is
+    assert_eq!(fs.remove_file("/BIG.BIN").unwrap_err(), FsError::Busy);
+    assert_eq!(fs.move_file("/BIG.BIN", "/B").unwrap_err(), FsError::Busy);
+    assert_eq!(fs.truncate_file("/BIG.BIN").unwrap_err(), FsError::Busy);
+
+    file.write_all(b"small").unwrap();
+    drop(file);
+    fs.move_file("/BIG.BIN", "/SMALL.BIN").unwrap();
+    assert_eq!(read(&fs, "/SMALL.BIN"), b"small");
+    assert_eq!(fs.truncate_file("/SUB").unwrap_err(), FsError::NotAFile);
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+}
+
+#[test]
+fn test_long_names() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+
+    fs.create_file("/Hello World.txt")
+        .unwrap()
+        .write_all(b"hi")
+        .unwrap();
+    fs.create_file("/Hello Wonder.txt").unwrap();
+    fs.create_file("/lower.txt").unwrap();
+    fs.create_dir("/A very long directory name that spans several entries")
+        .unwrap();
+
+    assert_eq!(read(&fs, "/hello world.TXT"), b"hi");
+    assert!(fs.exists("/HELLOW~1.TXT").unwrap());
+    assert!(fs.exists("/HELLOW~2.TXT").unwrap());
+    assert!(fs.exists("/LOWER.TXT").unwrap());
+
+    let dir = "/A very long directory name that spans several entries";
+    fs.create_file(&format!("{}/inner file.md", dir)).unwrap();
+    fs.move_file(&format!("{}/inner file.md", dir), "/Moved Out.md")
+        .unwrap();
+    assert_eq!(names(&fs, dir), [".", ".."]);
+    fs.remove_dir(dir).unwrap();
+
+    fs.remove_file("/Hello World.txt").unwrap();
+    assert!(!fs.exists("/HELLOW~1.TXT").unwrap());
+    assert_eq!(
+        fs.create_file("/bad?.txt").unwrap_err(),
+        FsError::FileNameError(FilenameError::InvalidCharacter)
+    );
+
+    let fs = mount(&disk);
+    let root = names(&fs, "/");
+    assert!(root.contains(&"Hello Wonder.txt".into()));
+    assert!(root.contains(&"lower.txt".into()));
+    assert!(root.contains(&"Moved Out.md".into()));
+    assert!(!root.contains(&"Hello World.txt".into()));
+}
+
+/// Counts the reads, and fails those of one sector
+#[derive(Clone)]
+struct Probe {
+    disk: RamDisk,
+    reads: Arc<AtomicUsize>,
+    bad: Arc<AtomicUsize>,
+}
+
+impl BlockDevice<Block512> for Probe {
+    fn block_count(&self) -> FsResult<usize> {
+        BlockDevice::<Block512>::block_count(&self.disk)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+        if offset == self.bad.load(Ordering::Relaxed) {
+            return Err(DeviceError::ReadError.into());
+        }
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.disk.read_block(offset, block)
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+        self.disk.write_block(offset, block)
+    }
+}
+
+#[test]
+fn test_lazy_read_dir() {
+    let disk = fat_disk(false, &sample_tree());
+    let fs = mount(&disk);
+
+    // 2 KiB clusters hold 64 entries
+    fs.create_dir("/DIR").unwrap();
+    for i in 0..200 {
+        fs.create_file(&format!("/DIR/F{}.TXT", i)).unwrap();
+    }
+
+    let probe = Probe {
+        disk: disk.clone(),
+        reads: Arc::new(AtomicUsize::new(0)),
+        bad: Arc::new(AtomicUsize::new(usize::MAX)),
+    };
+    let fs = Fat16::new(first_partition(probe.clone()));
+
+    // the first sector of the directory is enough
+    let before = probe.reads.load(Ordering::Relaxed);
+    let first: Vec<_> = fs
+        .read_dir("/DIR")
+        .unwrap()
+        .take(5)
+        .map(|m| m.name)
+        .collect();
+    assert_eq!(first, [".", "..", "F0.TXT", "F1.TXT", "F2.TXT"]);
+    let lazy = probe.reads.load(Ordering::Relaxed) - before;
+
+    let before = probe.reads.load(Ordering::Relaxed);
+    assert_eq!(fs.read_dir("/DIR").unwrap().count(), 202);
+    let full = probe.reads.load(Ordering::Relaxed) - before;
+    assert!(
+        lazy * 4 < full,
+        "{} reads for 5 entries, {} for all",
+        lazy,
+        full
+    );
+
+    // the second sector cannot be read: the first one is listed, the error
+    // is reported, and the iteration ends
+    let dir = fs
+        .dir_entries("/")
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|entry| entry.filename() == "DIR")
+        .unwrap();
+    // the data area starts after the boot sector, two FATs of 32 sectors
+    // and 32 sectors of root directory
+    let first = PART_START + 1 + 2 * 32 + 32 + (dir.cluster.0 as usize - 2) * 4;
+    probe.bad.store(first + 1, Ordering::Relaxed);
+
+    let entries: Vec<_> = fs.dir_entries("/DIR").unwrap().collect();
+    assert_eq!(entries.len(), 17);
+    assert!(entries[..16].iter().all(Result::is_ok));
+    assert_eq!(
+        entries[16],
+        Err(FsError::DeviceError(DeviceError::ReadError))
+    );
+    assert_eq!(names(&fs, "/DIR").len(), 16);
+}
@@ -0,0 +1,95 @@
+//! Fat32 driver, mounted through `MbrTable` on a generated disk
+
+mod common;
+
+use common::*;
+use ysos_storage::{fat32::Fat32, *};
+
+fn mount(disk: &RamDisk) -> Fat32 {
+    Fat32::new(first_partition(disk.clone()))
+}
+
+/// Free cluster count stored in FSInfo
+fn fs_info_free(disk: &RamDisk) -> u32 {
+    let data = disk.to_vec();
+    let offset = (PART_START + 1) * 512 + 0x1e8;
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn test_read() {
+    let mut tree = sample_tree();
+    // root directory spans several clusters
+    tree.extend((0..40).map(|i| Node::File(Box::leak(format!("F{}.TXT", i).into()), vec![])));
+
+    let disk = fat_disk(true, &tree);
+    let fs = mount(&disk);
+
+    let root = names(&fs, "/");
+    assert_eq!(root.len(), 45);
+    assert_eq!(root[..3], ["HELLO.TXT", "BIG.BIN", "EMPTY"]);
+    assert_eq!(root[44], "F39.TXT");
+
+    assert_eq!(read(&fs, "/hello.txt"), b"Hello, world!\n");
+    assert_eq!(read(&fs, "/BIG.BIN"), big_data());
+    assert_eq!(read(&fs, "/Long File Name.txt"), b"long\n");
+    assert_eq!(read(&fs, "/SUB/nested dir/DEEP.TXT"), b"deep\n");
+    assert_eq!(names(&fs, "/SUB/nested dir/../.."), root);
+}
+
+#[test]
+fn test_write() {
+    let disk = fat_disk(true, &sample_tree());
+    let fs = mount(&disk);
+    let free = fs_info_free(&disk);
+
+    // the free count comes from FSInfo
+    let stat = fs.statfs("/").unwrap();
+    assert_eq!(stat.free_blocks, free as usize);
+    assert!(stat.free_blocks < stat.total_blocks);
+
+    // the root directory grows past its first cluster
+    for i in 0..40 {
+        fs.create_file(&format!("/new file {}.txt", i))
+            .unwrap()
+            .write_all(b"x")
+            .unwrap();
+    }
+    assert_eq!(names(&fs, "/").len(), 45);
+
+    // FSInfo is only written back on sync
+    assert_eq!(fs_info_free(&disk), free);
+    fs.sync().unwrap();
+    assert!(fs_info_free(&disk) < free - 40);
+    assert_eq!(
+        fs.statfs("/").unwrap().free_blocks,
+        fs_info_free(&disk) as usize
+    );
+
+    fs.create_dir("/d").unwrap();
+    fs.move_file("/BIG.BIN", "/d/big").unwrap();
+    fs.move_dir("/d", "/SUB/d").unwrap();
+    assert_eq!(names(&fs, "/SUB/d/.."), names(&fs, "/SUB"));
+
+    // dropping the volume stores FSInfo as well
+    drop(fs);
+    let fs = mount(&disk);
+    assert_eq!(read(&fs, "/SUB/d/big"), big_data());
+    assert_eq!(read(&fs, "/new file 39.txt"), b"x");
+
+    fs.remove_file("/SUB/d/big").unwrap();
+    fs.remove_dir("/SUB/d").unwrap();
+    for i in 0..40 {
+        fs.remove_file(&format!("/new file {}.txt", i)).unwrap();
+    }
+    fs.sync().unwrap();
+
+    // BIG.BIN is gone, only the clusters added to the root are still in use:
+    // 7 slots at first, 3 for each new file and 2 for "d" make 9 clusters
+    let grown = free + 20 - fs_info_free(&disk);
+    assert_eq!(grown, 8);
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(report.dirs, 2);
+}
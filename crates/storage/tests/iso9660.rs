@@ -0,0 +1,123 @@
+//! ISO 9660 driver, on generated images and on images made by `xorriso`
+
+mod common;
+
+use std::{path::PathBuf, process::Command};
+
+use common::*;
+use ysos_storage::{iso9660::Iso9660, *};
+
+/// `sample_tree` with the names a volume without extensions records
+fn iso_tree(nodes: &[Node]) -> Vec<Node> {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::File(name, data) => {
+                let name = iso_name(name, false);
+                let name = name.trim_end_matches(";1").trim_end_matches('.');
+                Node::File(Box::leak(name.into()), data.clone())
+            }
+            Node::Dir(name, children) => {
+                Node::Dir(Box::leak(iso_name(name, true).into()), iso_tree(children))
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_iso() {
+    let fs = Iso9660::new(iso_image(IsoNames::Iso, &sample_tree())).unwrap();
+    check_tree(&fs, "", &iso_tree(&sample_tree()));
+
+    // upper case names are looked up like FAT short names
+    assert_eq!(read(&fs, "/sub/nested_dir/deep.txt"), b"deep\n");
+    assert_eq!(read(&fs, "/LONG_FILE_NAME.TXT"), b"long\n");
+}
+
+#[test]
+fn test_joliet() {
+    let fs = Iso9660::new(iso_image(IsoNames::Joliet, &sample_tree())).unwrap();
+    check_tree(&fs, "", &sample_tree());
+    assert!(!fs.exists("/sub").unwrap());
+}
+
+#[test]
+fn test_rock_ridge() {
+    let fs = Iso9660::new(iso_image(IsoNames::RockRidge, &sample_tree())).unwrap();
+    check_tree(&fs, "", &sample_tree());
+
+    let meta = fs.metadata("/SUB/nested dir/DEEP.TXT").unwrap();
+    assert_eq!(meta.len, 5);
+    assert_eq!(
+        meta.modified.unwrap().to_rfc3339(),
+        "2025-06-16T10:00:00+00:00"
+    );
+}
+
+#[test]
+fn test_read_only() {
+    let fs = Iso9660::new(iso_image(IsoNames::RockRidge, &sample_tree())).unwrap();
+
+    let mut file = fs.open_file("/BIG.BIN").unwrap();
+    file.seek(SeekFrom::Start(4000)).unwrap();
+    let mut buf = [0u8; 3000];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[..], big_data()[4000..7000]);
+    assert_eq!(file.write(b"x"), Err(FsError::ReadOnly));
+
+    assert_eq!(fs.open_file("/SUB").unwrap_err(), FsError::NotAFile);
+    assert_eq!(
+        fs.read_dir("/HELLO.TXT").err(),
+        Some(FsError::NotADirectory)
+    );
+    assert_eq!(fs.create_file("/NEW").unwrap_err(), FsError::NotSupported);
+
+    let stat = fs.statfs("/").unwrap();
+    assert!(stat.read_only);
+    assert_eq!(stat.block_size, 2048);
+
+    assert_eq!(
+        Iso9660::new(RamDisk::new(64 * 2048)).unwrap_err(),
+        FsError::InvalidVolume
+    );
+
+    // a path table larger than the volume
+    let mut image = iso_image(IsoNames::Iso, &sample_tree()).to_vec();
+    image[16 * 2048 + 132..16 * 2048 + 136].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        Iso9660::new(RamDisk::from_vec(image)).unwrap_err(),
+        FsError::InvalidVolume
+    );
+}
+
+#[test]
+#[ignore = "needs xorriso"]
+fn test_xorriso() {
+    require_tools(&["xorriso"]);
+
+    let dir = std::env::temp_dir().join(format!("ysos-iso-{}", std::process::id()));
+    let path = PathBuf::from(format!("{}.iso", dir.display()));
+
+    stage(&sample_tree(), &dir);
+
+    // Rock Ridge, then Joliet alone
+    for flag in ["-R", "-J"] {
+        let out = Command::new("xorriso")
+            .args(["-as", "mkisofs", flag, "-o"])
+            .arg(&path)
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        let fs = Iso9660::new(ImageFile::open_readonly(&path).unwrap()).unwrap();
+        check_tree(&fs, "", &sample_tree());
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
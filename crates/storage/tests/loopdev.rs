@@ -0,0 +1,59 @@
+//! A disk image stored as a file on a FAT16 volume, attached as a loop
+//! device and mounted
+
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use ysos_storage::{fat16::Fat16, *};
+
+fn outer() -> Fat16 {
+    let outer = Fat16::format(RamDisk::new(64 * 1024 * 1024), &Default::default()).unwrap();
+
+    let image = fat_disk(false, &sample_tree()).to_vec();
+    outer.create_dir("/DATA").unwrap();
+    outer
+        .create_file("/DATA/HW1.IMG")
+        .unwrap()
+        .write_all(&image)
+        .unwrap();
+
+    outer
+}
+
+#[test]
+fn test_loop_device() {
+    let outer = outer();
+
+    let device = LoopDevice::new(outer.open_file("/DATA/HW1.IMG").unwrap()).unwrap();
+    assert_eq!(device.block_count().unwrap(), PART_START + 32768);
+
+    let fs = Fat16::new(first_partition(Arc::new(device)));
+    check_tree(&fs, "", &sample_tree());
+
+    fs.create_file("/ANSWER.TXT")
+        .unwrap()
+        .write_all(b"42\n")
+        .unwrap();
+    fs.sync().unwrap();
+    drop(fs);
+
+    // the image kept its size and holds the new file
+    assert_eq!(
+        outer.metadata("/DATA/HW1.IMG").unwrap().len,
+        (PART_START + 32768) * 512
+    );
+
+    let device = LoopDevice::new(outer.open_file("/DATA/HW1.IMG").unwrap()).unwrap();
+    let fs = Fat16::new(first_partition(Arc::new(device)));
+    assert_eq!(read(&fs, "/ANSWER.TXT"), b"42\n");
+    assert!(fs.check(false).unwrap().is_clean());
+
+    let mut block = Block512::default();
+    let device = LoopDevice::new(outer.open_file("/DATA/HW1.IMG").unwrap()).unwrap();
+    assert_eq!(
+        device.read_block(PART_START + 32768, &mut block),
+        Err(FsError::InvalidOffset)
+    );
+}
@@ -0,0 +1,102 @@
+//! Images made by `mkfs.fat` and `mtools` on the host, mounted through
+//! `ImageFile` + `MbrTable`. The tests using host tools are ignored by
+//! default, run them with `cargo test -- --ignored` where they are installed.
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::*;
+use ysos_storage::{
+    fat16::{Fat16, mkfs::FormatOptions},
+    fat32::Fat32,
+    *,
+};
+
+fn image_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ysos-{}-{}.img", name, std::process::id()))
+}
+
+#[test]
+fn test_image_file() {
+    // the generated disk, through a file instead of memory
+    let path = image_path("generated");
+    std::fs::write(&path, fat_disk(false, &sample_tree()).to_vec()).unwrap();
+
+    let image = ImageFile::open_readonly(&path).unwrap();
+    let fs = Fat16::new(first_partition(image));
+    check_tree(&fs, "", &sample_tree());
+
+    assert_eq!(
+        fs.open_file("/HELLO.TXT").unwrap().write(b"x").unwrap_err(),
+        FsError::ReadOnly
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[ignore = "needs mkfs.fat and mtools"]
+fn test_mkfs_fat16() {
+    let path = image_path("fat16");
+    let image = mkfs_fat_disk(&path, 16, &sample_tree());
+
+    let fs = Fat16::new(first_partition(image.clone()));
+    check_tree(&fs, "", &sample_tree());
+
+    fs.create_file("/SUB/written by ysos.txt")
+        .unwrap()
+        .write_all(b"hello host")
+        .unwrap();
+
+    let fs = Fat16::new(first_partition(image));
+    assert_eq!(read(&fs, "/sub/WRITTEN BY YSOS.TXT"), b"hello host");
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[ignore = "needs mkfs.fat and mtools"]
+fn test_mkfs_fat32() {
+    let path = image_path("fat32");
+    let image = mkfs_fat_disk(&path, 32, &sample_tree());
+
+    let fs = Fat32::new(first_partition(image));
+    check_tree(&fs, "", &sample_tree());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[ignore = "needs fsck.fat"]
+fn test_format_fsck_fat() {
+    // the other way round, formatted here and checked by the host
+    require_tools(&["fsck.fat"]);
+
+    let path = image_path("format");
+    let image = ImageFile::create(&path, 32 * 1024 * 1024).unwrap();
+    let fs = Fat16::format(image, &FormatOptions::default()).unwrap();
+
+    fs.create_dir("/SUB").unwrap();
+    fs.create_file("/SUB/long file name.txt")
+        .unwrap()
+        .write_all(&big_data())
+        .unwrap();
+    fs.sync().unwrap();
+
+    let out = std::process::Command::new("fsck.fat")
+        .arg("-n")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stdout)
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
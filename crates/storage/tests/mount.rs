@@ -0,0 +1,106 @@
+//! `MountTable` routing between a FAT16 root and a FAT32 disk below it
+
+mod common;
+
+use common::*;
+use ysos_storage::{fat16::Fat16, fat32::Fat32, *};
+
+fn table() -> MountTable {
+    let table = MountTable::new();
+    let root = fat_disk(false, &sample_tree());
+    let disk = fat_disk(true, &[Node::File("DISK.TXT", b"second\n".to_vec())]);
+
+    table
+        .mount("/", Box::new(Fat16::new(first_partition(root))))
+        .unwrap();
+    table
+        .mount("/mnt/disk/", Box::new(Fat32::new(first_partition(disk))))
+        .unwrap();
+
+    table
+}
+
+#[test]
+fn test_resolve() {
+    let table = table();
+
+    assert_eq!(
+        &*table.resolve("/mnt/disk/DISK.TXT").unwrap().mount_point,
+        "/mnt/disk"
+    );
+    assert_eq!(
+        &*table.resolve("/mnt/disk").unwrap().mount_point,
+        "/mnt/disk"
+    );
+    assert_eq!(&*table.resolve("/mnt/diskette").unwrap().mount_point, "/");
+    assert_eq!(&*table.resolve("/HELLO.TXT").unwrap().mount_point, "/");
+    assert_eq!(table.mount_points(), ["/mnt/disk".into(), "/".into()]);
+
+    assert_eq!(
+        table.mount("/mnt/disk", Box::new(MountTable::new())),
+        Err(FsError::AlreadyExists)
+    );
+    assert!(matches!(
+        table.mount("mnt", Box::new(MountTable::new())),
+        Err(FsError::InvalidPath(_))
+    ));
+}
+
+#[test]
+fn test_routing() {
+    let table = table();
+
+    assert_eq!(read(&table, "/HELLO.TXT"), b"Hello, world!\n");
+    assert_eq!(read(&table, "/mnt/disk/DISK.TXT"), b"second\n");
+    assert_eq!(read(&table, "/mnt/disk/disk.txt"), b"second\n");
+    assert_eq!(
+        table.open_file("/mnt/disk/HELLO.TXT").unwrap_err(),
+        FsError::FileNotFound
+    );
+
+    // "/mnt" only exists as the parent of a mount point
+    assert_eq!(names(&table, "/mnt"), ["disk"]);
+    assert_eq!(names(&table, "/mnt/disk"), ["DISK.TXT"]);
+    assert!(table.metadata("/mnt/disk").unwrap().is_dir());
+    assert!(table.exists("/mnt/disk").unwrap());
+    assert!(table.metadata("/SUB").unwrap().is_dir());
+
+    // each mount reports its own statistics
+    let stat = table.statfs("/SUB").unwrap();
+    assert_eq!(stat.block_size, 2048);
+    assert!(stat.free_blocks < stat.total_blocks);
+    assert_eq!(table.statfs("/mnt/disk").unwrap().block_size, 512);
+
+    let mut file = table.create_file("/mnt/disk/NEW.TXT").unwrap();
+    file.write_all(b"new").unwrap();
+    drop(file);
+    assert_eq!(read(&table, "/mnt/disk/NEW.TXT"), b"new");
+    assert!(!table.exists("/NEW.TXT").unwrap());
+
+    assert_eq!(
+        table.move_file("/HELLO.TXT", "/mnt/disk/HELLO.TXT"),
+        Err(FsError::CrossDevice)
+    );
+    assert_eq!(
+        table.remove_dir("/mnt/disk"),
+        Err(FsError::InvalidOperation)
+    );
+}
+
+#[test]
+fn test_unmount() {
+    let table = table();
+
+    table.unmount("/mnt/disk").unwrap();
+    assert_eq!(table.unmount("/mnt/disk"), Err(FsError::NotMounted));
+    assert_eq!(
+        table.open_file("/mnt/disk/DISK.TXT").unwrap_err(),
+        FsError::FileNotFound
+    );
+
+    table.unmount("/").unwrap();
+    assert_eq!(
+        table.open_file("/HELLO.TXT").unwrap_err(),
+        FsError::NotMounted
+    );
+}
@@ -12,6 +12,8 @@ pub enum Syscall {
     Open = 2,
     Close = 3,
 
+    Seek = 8,
+
     Brk = 12,
 
     GetPid = 39,
@@ -25,6 +27,9 @@ pub enum Syscall {
     Sem = 66,
     Time = 201,
 
+    Loop = 65527,
+    StatFs = 65528,
+    Fsck = 65529,
     Stat = 65530,
     ListDir = 65531,
     Allocate = 65533,
@@ -33,3 +38,39 @@ pub enum Syscall {
     #[num_enum(default)]
     None = 65535,
 }
+
+/// The different ways we can open a file.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum FileMode {
+    /// Open a file for reading, if it exists.
+    ReadOnly = 0,
+    /// Open a file for appending (writing to the end of the existing file), if
+    /// it exists.
+    ReadWriteAppend = 1,
+    /// Open a file and remove all contents, before writing to the start of the
+    /// existing file, if it exists.
+    ReadWriteTruncate = 2,
+    /// Create a new empty file. Fail if it exists.
+    ReadWriteCreate = 3,
+    /// Create a new empty file, or truncate an existing file.
+    ReadWriteCreateOrTruncate = 4,
+    /// Create a new empty file, or append to an existing file.
+    ReadWriteCreateOrAppend = 5,
+}
+
+/// Size and usage of a filesystem, filled in by `Syscall::StatFs`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    /// Allocation unit in bytes
+    pub block_size: u64,
+    /// Blocks available for data
+    pub total_blocks: u64,
+    /// Blocks not in use
+    pub free_blocks: u64,
+    /// Longest file name accepted
+    pub max_name_len: u64,
+    /// Nothing can be written
+    pub read_only: bool,
+}